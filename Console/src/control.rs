@@ -13,18 +13,60 @@ use std::net::TcpStream;
 use std::time::Duration;
 
 /// サーバ設定の全体像。Server 側 `config::Config` と構造を一致させること。
+/// Console で編集しない項目(`control_addr` や `encoding_rules` など)は `extra` に取っておき、
+/// set_config でそのまま送り返す(取得した値を書き換えて送れば消えない)。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfigDto {
     pub server: ServerSection,
     pub logging: LoggingSection,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerSection {
-    /// syslog 受信アドレス(例 0.0.0.0:514)。
+    /// 旧形式の syslog(UDP)受信アドレス(例 0.0.0.0:514)。Console で編集すると listeners に移す。
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub bind_addr: String,
+    /// 待ち受け口の一覧(`[[server.listeners]]`)。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listeners: Vec<ListenerDto>,
     /// GUI への配信アドレス(例 127.0.0.1:5141)。
     pub stream_addr: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 待ち受け口 1 つぶん。Server 側 `config::ListenerConfig` のうち Console で編集する項目。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerDto {
+    /// 待ち受けアドレス(例 [::]:514)。unix / unix-stream ではソケットのパス。
+    pub addr: String,
+    /// "udp" / "tcp" / "tls" / "relp" / "unix" / "unix-stream"。
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    /// 受信したメッセージに付ける名前。空なら付けない。
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// 証明書のパスや上限サイズなど、Console で編集しない項目。
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ListenerDto {
+    /// 編集欄の「追加」で足す行。
+    pub fn udp(addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            protocol: default_protocol(),
+            label: String::new(),
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+fn default_protocol() -> String {
+    "udp".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +74,8 @@ pub struct LoggingSection {
     pub level: String,
     pub max_size_mb: u64,
     pub keep_files: usize,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 // ---- レスポンス封筒 ----
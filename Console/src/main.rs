@@ -14,7 +14,6 @@
 
 mod control;
 mod net;
-mod parser;
 mod platform;
 mod service;
 mod settings;
@@ -24,7 +23,9 @@ mod macos_menu;
 
 use eframe::egui;
 use net::ConnState;
-use parser::SyslogMessage;
+use vlt_syslog_core::filter::MessageFilter;
+use vlt_syslog_core::time::DisplayZone;
+use vlt_syslog_core::{Facility, SyslogMessage};
 use service::ServiceStatus;
 use settings::Settings;
 use tokio::sync::mpsc;
@@ -65,6 +66,8 @@ struct ConsoleApp {
     logs: Vec<SyslogMessage>,
     auto_scroll: bool,
     filter: String,
+    // facility での絞り込み(None = すべて)。
+    facility_filter: Option<Facility>,
 
     // net::run_client との配線。
     msg_rx: mpsc::Receiver<SyslogMessage>,
@@ -89,15 +92,21 @@ struct ConsoleApp {
     pref_control_addr: String,
     pref_error: Option<String>,
     pref_saved: bool,
+    pref_display_tz: String,
+    display_status: Option<(bool, String)>, // (成功か, メッセージ)
+
+    // 時刻列の表示タイムゾーン(環境設定で変更)。
+    display_zone: DisplayZone,
 
     // サーバ側 syslog 設定(制御ポート経由で取得・変更)。
     srv_cfg_status: Option<(bool, String)>, // (成功か, メッセージ)
-    edit_bind_addr: String,
+    edit_listeners: Vec<control::ListenerDto>,
     edit_stream_addr: String,
     edit_log_level: String,
     edit_max_size_mb: String,
     edit_keep_files: String,
-    srv_cfg_loaded: bool,
+    // 最後に取得したサーバ設定。適用時はこれを書き換えて送り、編集欄に無い項目を保つ。
+    srv_cfg_fetched: Option<control::ServerConfigDto>,
 
     // メニューから積まれた、次の描画で egui 入力へ注入する編集イベント。
     pending_events: Vec<egui::Event>,
@@ -151,11 +160,14 @@ impl ConsoleApp {
         let addr_input = settings.server_addr.clone();
         let pref_server_addr = settings.server_addr.clone();
         let pref_control_addr = settings.control_addr.clone();
+        let pref_display_tz = settings.display_tz.clone();
+        let display_zone = settings::display_zone(&settings);
 
         Self {
             logs: Vec::new(),
             auto_scroll: true,
             filter: String::new(),
+            facility_filter: None,
             msg_rx,
             state_rx,
             addr_tx,
@@ -173,13 +185,16 @@ impl ConsoleApp {
             pref_control_addr,
             pref_error: None,
             pref_saved: false,
+            pref_display_tz,
+            display_status: None,
+            display_zone,
             srv_cfg_status: None,
-            edit_bind_addr: String::new(),
+            edit_listeners: Vec::new(),
             edit_stream_addr: String::new(),
             edit_log_level: String::new(),
             edit_max_size_mb: String::new(),
             edit_keep_files: String::new(),
-            srv_cfg_loaded: false,
+            srv_cfg_fetched: None,
             pending_events: Vec::new(),
             last_text_focus: None,
             show_about: false,
@@ -300,6 +315,30 @@ impl ConsoleApp {
         self.pref_control_addr = self.settings.control_addr.clone();
         self.pref_error = None;
         self.pref_saved = false;
+        self.pref_display_tz = self.settings.display_tz.clone();
+        self.display_status = None;
+    }
+
+    /// 表示タイムゾーンを検証して保存し、時刻列にすぐ反映する。
+    fn apply_display_prefs(&mut self) {
+        let zone = match self.pref_display_tz.parse::<DisplayZone>() {
+            Ok(z) => z,
+            Err(_) => {
+                self.display_status = Some((
+                    false,
+                    "Local / UTC / Asia/Tokyo のような IANA 名で指定してください".to_string(),
+                ));
+                return;
+            }
+        };
+        self.settings.display_tz = zone.to_string();
+        if let Err(e) = settings::save(&self.settings) {
+            self.display_status = Some((false, format!("設定の保存に失敗しました: {e}")));
+            return;
+        }
+        self.pref_display_tz = self.settings.display_tz.clone();
+        self.display_zone = zone;
+        self.display_status = Some((true, "保存しました".to_string()));
     }
 
     /// 接続設定(server_addr / control_addr)を保存して再接続する。
@@ -328,12 +367,17 @@ impl ConsoleApp {
     fn fetch_server_config(&mut self) {
         match control::get_config(&self.settings.control_addr) {
             Ok(cfg) => {
-                self.edit_bind_addr = cfg.server.bind_addr;
-                self.edit_stream_addr = cfg.server.stream_addr;
-                self.edit_log_level = cfg.logging.level;
+                // 旧形式の bind_addr は UDP の待ち受けとして一覧に並べ、適用時に listeners へ移す
+                self.edit_listeners = cfg.server.listeners.clone();
+                if !cfg.server.bind_addr.is_empty() {
+                    self.edit_listeners
+                        .push(control::ListenerDto::udp(&cfg.server.bind_addr));
+                }
+                self.edit_stream_addr = cfg.server.stream_addr.clone();
+                self.edit_log_level = cfg.logging.level.clone();
                 self.edit_max_size_mb = cfg.logging.max_size_mb.to_string();
                 self.edit_keep_files = cfg.logging.keep_files.to_string();
-                self.srv_cfg_loaded = true;
+                self.srv_cfg_fetched = Some(cfg);
                 self.srv_cfg_status = Some((true, "現在の設定を取得しました".to_string()));
             }
             Err(e) => {
@@ -360,17 +404,30 @@ impl ConsoleApp {
                 return;
             }
         };
-        let cfg = control::ServerConfigDto {
-            server: control::ServerSection {
-                bind_addr: self.edit_bind_addr.trim().to_string(),
-                stream_addr: self.edit_stream_addr.trim().to_string(),
-            },
-            logging: control::LoggingSection {
-                level: self.edit_log_level.trim().to_string(),
-                max_size_mb,
-                keep_files,
-            },
+        let Some(mut cfg) = self.srv_cfg_fetched.clone() else {
+            self.srv_cfg_status = Some((false, "先に現在の設定を取得してください".to_string()));
+            return;
         };
+        let listeners: Vec<control::ListenerDto> = self
+            .edit_listeners
+            .iter()
+            .filter(|l| !l.addr.trim().is_empty())
+            .map(|l| control::ListenerDto {
+                addr: l.addr.trim().to_string(),
+                label: l.label.trim().to_string(),
+                ..l.clone()
+            })
+            .collect();
+        if listeners.is_empty() {
+            self.srv_cfg_status = Some((false, "待ち受けを 1 つ以上指定してください".to_string()));
+            return;
+        }
+        cfg.server.bind_addr = String::new();
+        cfg.server.listeners = listeners;
+        cfg.server.stream_addr = self.edit_stream_addr.trim().to_string();
+        cfg.logging.level = self.edit_log_level.trim().to_string();
+        cfg.logging.max_size_mb = max_size_mb;
+        cfg.logging.keep_files = keep_files;
         match control::set_config(&self.settings.control_addr, &cfg) {
             Ok(restart_required) => {
                 if restart_required {
@@ -444,6 +501,31 @@ impl ConsoleApp {
                     }
                 });
 
+                ui.add_space(8.0);
+                ui.separator();
+
+                // --- 表示 ---
+                ui.strong("表示");
+                ui.horizontal(|ui| {
+                    ui.label("タイムゾーン:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.pref_display_tz)
+                            .hint_text("Local / UTC / Asia/Tokyo")
+                            .desired_width(180.0),
+                    );
+                    if ui.button("適用").clicked() {
+                        self.apply_display_prefs();
+                    }
+                });
+                if let Some((ok, msg)) = &self.display_status {
+                    let color = if *ok {
+                        egui::Color32::from_rgb(120, 200, 120)
+                    } else {
+                        egui::Color32::from_rgb(240, 90, 90)
+                    };
+                    ui.colored_label(color, msg);
+                }
+
                 ui.add_space(8.0);
                 ui.separator();
                 ui.add_space(4.0);
@@ -463,16 +545,52 @@ impl ConsoleApp {
                 );
                 ui.add_space(4.0);
 
-                ui.add_enabled_ui(self.srv_cfg_loaded, |ui| {
+                ui.add_enabled_ui(self.srv_cfg_fetched.is_some(), |ui| {
                     egui::Grid::new("srv_cfg_grid")
                         .num_columns(2)
                         .spacing([10.0, 8.0])
                         .show(ui, |ui| {
-                            ui.label("受信アドレス (bind_addr):");
-                            ui.add(
-                                egui::TextEdit::singleline(&mut self.edit_bind_addr)
-                                    .desired_width(220.0),
-                            );
+                            // 待ち受け口 1 つにつき 1 行(プロトコル・アドレス・名前)。
+                            // TLS の証明書のパスなどは config.toml で指定する(取得した値はそのまま送り返す)。
+                            ui.label("待ち受け (listeners):");
+                            ui.vertical(|ui| {
+                                let mut remove = None;
+                                for (i, listener) in self.edit_listeners.iter_mut().enumerate() {
+                                    ui.horizontal(|ui| {
+                                        egui::ComboBox::from_id_source(("listener_protocol", i))
+                                            .width(90.0)
+                                            .selected_text(&listener.protocol)
+                                            .show_ui(ui, |ui| {
+                                                for p in ["udp", "tcp", "tls", "relp", "unix", "unix-stream"] {
+                                                    ui.selectable_value(
+                                                        &mut listener.protocol,
+                                                        p.to_string(),
+                                                        p,
+                                                    );
+                                                }
+                                            });
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut listener.addr)
+                                                .hint_text("[::]:514")
+                                                .desired_width(140.0),
+                                        );
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut listener.label)
+                                                .hint_text("名前(任意)")
+                                                .desired_width(80.0),
+                                        );
+                                        if ui.small_button("削除").clicked() {
+                                            remove = Some(i);
+                                        }
+                                    });
+                                }
+                                if let Some(i) = remove {
+                                    self.edit_listeners.remove(i);
+                                }
+                                if ui.small_button("+ 追加").clicked() {
+                                    self.edit_listeners.push(control::ListenerDto::udp("[::]:514"));
+                                }
+                            });
                             ui.end_row();
 
                             ui.label("配信アドレス (stream_addr):");
@@ -713,30 +831,35 @@ impl eframe::App for ConsoleApp {
             ui.add_space(5.0);
             ui.horizontal(|ui| {
                 ui.label("Filter:");
-                ui.text_edit_singleline(&mut self.filter);
+                ui.text_edit_singleline(&mut self.filter).on_hover_text(
+                    "本文・タグ・ホスト名・送信元の名前を検索します。fields.user=alice のように書くと項目の値、listener=名前 で受けた待ち受け口、src=IP / CIDR / 名前 で送信元で絞り込めます",
+                );
                 if ui.button("x").clicked() {
                     self.filter.clear();
                 }
+                ui.label("Facility:");
+                egui::ComboBox::from_id_source("facility_filter")
+                    .selected_text(self.facility_filter.map_or("All", |f| f.name()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.facility_filter, None, "All");
+                        for f in Facility::ALL {
+                            ui.selectable_value(&mut self.facility_filter, Some(f), f.name());
+                        }
+                    });
             });
 
             ui.add_space(5.0);
             ui.separator();
             ui.add_space(5.0);
 
-            let filter = self.filter.to_lowercase();
+            // "fields.user=alice" のような項目指定も書ける(書式は vlt_syslog_core::filter)
+            let filter = MessageFilter::parse(&self.filter);
+            let facility_filter = self.facility_filter;
             let filtered_logs: Vec<_> = self
                 .logs
                 .iter()
-                .filter(|l| {
-                    filter.is_empty()
-                        || l.content.to_lowercase().contains(&filter)
-                        || l.tag
-                            .as_ref()
-                            .map_or(false, |t| t.to_lowercase().contains(&filter))
-                        || l.hostname
-                            .as_ref()
-                            .map_or(false, |h| h.to_lowercase().contains(&filter))
-                })
+                .filter(|l| facility_filter.is_none() || l.facility == facility_filter)
+                .filter(|l| filter.matches(l))
                 .collect();
 
             let row_height = 24.0;
@@ -744,13 +867,16 @@ impl eframe::App for ConsoleApp {
                 .auto_shrink([false; 2])
                 .stick_to_bottom(self.auto_scroll)
                 .show_rows(ui, row_height, filtered_logs.len(), |ui, row_range| {
-                    egui::Grid::new("log_grid_console_v1")
+                    egui::Grid::new("log_grid_console_v2")
                         .striped(true)
-                        .num_columns(5)
+                        .num_columns(8)
                         .spacing([15.0, 8.0])
                         .show(ui, |ui| {
                             ui.strong("Time");
+                            ui.strong("Host");
+                            ui.strong("Source");
                             ui.strong("Tag");
+                            ui.strong("Facility");
                             ui.strong("Severity");
                             ui.strong("Enc");
                             ui.strong("Message");
@@ -761,8 +887,34 @@ impl eframe::App for ConsoleApp {
                                 let (r, g, b) = log.severity.color();
                                 let color = egui::Color32::from_rgb(r, g, b);
 
-                                ui.label(&log.timestamp);
+                                let time_label =
+                                    ui.label(self.display_zone.format(&log.received_at));
+                                // 送信元機器の時刻はホバーで(受信時刻とずれていれば機器の時計が怪しい)
+                                if let Some(device) = &log.device_timestamp {
+                                    time_label.on_hover_text(format!(
+                                        "機器時刻: {}",
+                                        self.display_zone.format(device)
+                                    ));
+                                }
+                                ui.label(log.hostname.as_deref().unwrap_or("-"));
+                                // 送信元は名前が引けていれば名前、無ければ IP。名前のときは IP をホバーで
+                                let source_label = ui.label(
+                                    log.source_display().unwrap_or_else(|| "-".to_string()),
+                                );
+                                if log.source_name.is_some()
+                                    && let Some(addr) = log.source_addr
+                                {
+                                    source_label.on_hover_text(addr.to_string());
+                                }
                                 ui.label(log.tag.as_deref().unwrap_or("-"));
+                                if log.invalid_pri {
+                                    ui.label(
+                                        egui::RichText::new("invalid PRI")
+                                            .color(egui::Color32::from_rgb(240, 90, 90)),
+                                    );
+                                } else {
+                                    ui.label(log.facility.map_or("-", |f| f.name()));
+                                }
                                 ui.label(
                                     egui::RichText::new(format!("{:?}", log.severity))
                                         .color(color)
@@ -770,16 +922,73 @@ impl eframe::App for ConsoleApp {
                                 );
                                 ui.label(egui::RichText::new(&log.encoding).weak());
 
-                                let message_label =
+                                let mut message_label =
                                     ui.label(egui::RichText::new(&log.content).color(color));
+                                // STRUCTURED-DATA はホバーで RFC 5424 の表記のまま見せる。
+                                // ベンダー方言で取り出した項目・パース診断・受信経路があれば、その下に 1 行ずつ並べる。
+                                let sd_text: String =
+                                    log.structured_data.iter().map(|e| e.to_string()).collect();
+                                let mut hover = sd_text.clone();
+                                if let Some(dialect) = &log.dialect {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str(&format!("[{dialect}]"));
+                                    for (key, value) in &log.fields {
+                                        hover.push_str(&format!("\n{key} = {value}"));
+                                    }
+                                }
+                                // パース時に救済した規格外の箇所(種類@バイト位置)
+                                if !log.diagnostics.is_empty() {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str("[diagnostics]");
+                                    for d in &log.diagnostics {
+                                        hover.push_str(&format!("\n{d}"));
+                                    }
+                                }
+                                // 受けた待ち受け口と、TLS のクライアント証明書の Subject・Unix ソケットの送信元プロセス・切り詰めの有無
+                                if log.listener.is_some()
+                                    || log.tls_subject.is_some()
+                                    || log.peer_cred.is_some()
+                                    || log.truncated
+                                {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str("[received]");
+                                    if let Some(listener) = &log.listener {
+                                        hover.push_str(&format!("\nlistener = {listener}"));
+                                    }
+                                    if let Some(subject) = &log.tls_subject {
+                                        hover.push_str(&format!("\ntls_subject = {subject}"));
+                                    }
+                                    if let Some(cred) = &log.peer_cred {
+                                        hover.push_str(&format!("\npeer_cred = {cred}"));
+                                    }
+                                    if log.truncated {
+                                        hover.push_str("\ntruncated = true(上限を超えたため切り詰め)");
+                                    }
+                                }
+                                if !hover.is_empty() {
+                                    message_label = message_label.on_hover_text(&hover);
+                                }
 
                                 message_label.context_menu(|ui| {
                                     if ui.button("Copy Message").clicked() {
                                         ui.output_mut(|o| o.copied_text = log.content.clone());
                                         ui.close_menu();
                                     }
-                                    if ui.button("Copy as Hex").clicked() {
-                                        ui.output_mut(|o| o.copied_text = log.raw.clone());
+                                    if !sd_text.is_empty()
+                                        && ui.button("Copy Structured Data").clicked()
+                                    {
+                                        ui.output_mut(|o| o.copied_text = sd_text.clone());
+                                        ui.close_menu();
+                                    }
+                                    // 生バイト列を省いて配信された行(や内部メッセージ)には出さない
+                                    if !log.raw.is_empty() && ui.button("Copy as Hex").clicked() {
+                                        ui.output_mut(|o| o.copied_text = log.raw_hex());
                                         ui.close_menu();
                                     }
                                 });
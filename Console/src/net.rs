@@ -9,7 +9,7 @@
 //!   - `state_tx`: 接続状態の変化を GUI へ(マネージャ→GUI)
 //!   - `addr_rx` : 接続先アドレスの変更要求を GUI から受ける(GUI→マネージャ)
 
-use crate::parser::SyslogMessage;
+use vlt_syslog_core::{SyslogMessage, stream};
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::net::TcpStream;
@@ -56,7 +56,7 @@ pub async fn run_client(
                     tokio::select! {
                         line = lines.next_line() => match line {
                             Ok(Some(l)) => {
-                                if let Ok(msg) = serde_json::from_str::<SyslogMessage>(&l) {
+                                if let Ok(msg) = stream::decode_line(&l) {
                                     let _ = msg_tx.send(msg).await;
                                 }
                                 // パースできない行は無視(将来の互換やノイズに強くする)。
@@ -149,7 +149,7 @@ mod tests {
             .expect("1本目が None");
         assert_eq!(m1.content, "こんにちは syslog");
         assert_eq!(m1.tag.as_deref(), Some("myapp"));
-        assert!(matches!(m1.severity, crate::parser::Severity::Error));
+        assert!(matches!(m1.severity, vlt_syslog_core::Severity::Error));
 
         // 2 本目も続けて届くこと。
         let m2 = tokio::time::timeout(Duration::from_secs(2), msg_rx.recv())
@@ -1,10 +1,12 @@
 //! Console(GUI フロントエンド)の設定の永続化。
 //!
 //! 保存先は `platform::config_path()`(= Console 用データディレクトリ内の config.toml)。
-//! 持つのは「接続先サービスのアドレス」だけ。受信ログ本体の保存は
+//! 持つのは「接続先サービスのアドレス」と表示の好みだけ。受信ログ本体の保存は
 //! サービス側の責務なので、ここでは扱わない。
 
 use serde::{Deserialize, Serialize};
+use vlt_syslog_core::stream::{DEFAULT_CONTROL_ADDR, DEFAULT_STREAM_ADDR};
+use vlt_syslog_core::time::DisplayZone;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -15,13 +17,17 @@ pub struct Settings {
     /// 接続先サービスの制御アドレス(host:port)。設定の取得/変更に使う。
     /// 既定はサービスの既定 `control_addr` と同じ 127.0.0.1:5142。
     pub control_addr: String,
+    /// 時刻列の表示タイムゾーン("Local" / "UTC" / IANA 名)。
+    /// 解釈できない値は Local として扱う(`display_zone`)。
+    pub display_tz: String,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
-            server_addr: "127.0.0.1:5141".to_string(),
-            control_addr: "127.0.0.1:5142".to_string(),
+            server_addr: DEFAULT_STREAM_ADDR.to_string(),
+            control_addr: DEFAULT_CONTROL_ADDR.to_string(),
+            display_tz: DisplayZone::Local.to_string(),
         }
     }
 }
@@ -44,3 +50,8 @@ pub fn save(s: &Settings) -> std::io::Result<()> {
     let body = toml::to_string_pretty(s).map_err(std::io::Error::other)?;
     std::fs::write(path, body)
 }
+
+/// 設定の表示タイムゾーン。未知の名前なら Local に倒す。
+pub fn display_zone(s: &Settings) -> DisplayZone {
+    s.display_tz.parse().unwrap_or_default()
+}
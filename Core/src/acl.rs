@@ -0,0 +1,111 @@
+//! 送信元のアクセス制御(allow / deny)。
+//!
+//! 受信したパケット(TCP 系は接続)を、パースする前に送信元 IP / CIDR で受け入れるか決める。
+//! 設定ファイルでは次のように書く(上にあるルールほど優先。最初に当たったルールで決まる):
+//!
+//! ```toml
+//! [[server.acl]]
+//! action = "deny"
+//! source = "192.0.2.66"
+//!
+//! [[server.acl]]
+//! action = "allow"
+//! source = "192.0.2.0/24"
+//!
+//! [[server.acl]]
+//! action = "allow"
+//! source = "10.0.0.0/8"
+//! listener = "dmz"
+//! ```
+//!
+//! どのルールにも当たらなかった送信元は、その待ち受け口に allow のルールが 1 つでもあれば拒否し、
+//! 無ければ受け入れる(deny だけを並べれば「それ以外は受ける」、allow を書けば「それ以外は拒む」)。
+//! `listener` を書いたルールは、その名前(label か "udp://[::]:514" の形)の待ち受け口にだけ効く。
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+/// ルールに当たった送信元の扱い。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AclAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// 設定ファイル上のルール 1 件。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AclRule {
+    pub action: AclAction,
+    /// 送信元 IP アドレスまたは CIDR("192.0.2.10" / "192.0.2.0/24" / "2001:db8::/32")。
+    pub source: String,
+    /// 効かせる待ち受け口の名前。省略時はすべての待ち受け口。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listener: Option<String>,
+}
+
+struct Compiled {
+    action: AclAction,
+    source: IpNet,
+    listener: Option<String>,
+}
+
+/// 検証済みのルール一覧。
+pub struct AccessList {
+    rules: Vec<Compiled>,
+}
+
+impl AccessList {
+    /// ルールを 1 件も持たない(すべて受け入れる)一覧。
+    pub const fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// 設定のルールを検証して取り込む。CIDR が読めなければ何番目のルールかを添えて Err。
+    pub fn compile(rules: &[AclRule]) -> Result<Self, String> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        for (i, rule) in rules.iter().enumerate() {
+            let source = parse_source(&rule.source)
+                .ok_or_else(|| format!("acl rule #{}: invalid source {:?}", i + 1, rule.source))?;
+            compiled.push(Compiled {
+                action: rule.action,
+                source,
+                listener: rule.listener.clone(),
+            });
+        }
+        Ok(Self { rules: compiled })
+    }
+
+    /// ルールが 1 件も無いか。
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 待ち受け口 `listener` で `source` から届いたものを受け入れるか。
+    pub fn is_allowed(&self, source: IpAddr, listener: &str) -> bool {
+        let source = source.to_canonical();
+        let mut has_allow = false;
+        for rule in &self.rules {
+            if rule.listener.as_deref().is_some_and(|l| l != listener) {
+                continue;
+            }
+            if rule.source.contains(&source) {
+                return rule.action == AclAction::Allow;
+            }
+            has_allow |= rule.action == AclAction::Allow;
+        }
+        !has_allow
+    }
+}
+
+/// "192.0.2.10" のような単独アドレスも /32(/128)の CIDR として受け付ける。
+pub(crate) fn parse_source(s: &str) -> Option<IpNet> {
+    let s = s.trim();
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
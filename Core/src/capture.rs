@@ -0,0 +1,581 @@
+//! パケットキャプチャ(pcap / pcapng)からの syslog の取り出し。
+//!
+//! 顧客から受け取ったキャプチャを、外部ツールで再送せずにそのまま取り込むためのもの。
+//! Ethernet(VLAN タグ付きを含む)・Linux cooked(SLL / SLL2)・BSD loopback・生 IP のリンク層の
+//! IPv4 / IPv6 から、宛先ポートが syslog のもの(既定は [`DEFAULT_PORTS`])を取り出す。
+//!
+//! - UDP: 1 データグラム = 1 メッセージ
+//! - TCP: 接続ごとに順番どおりに並べ直し、RFC 6587 の区切り([`crate::framing`])で切り出す。
+//!   欠けたセグメントがあればその接続はそこから区切りを読み直す
+//!
+//! IP のフラグメントは組み立てずに捨てる。時刻はキャプチャに記録された時刻で、
+//! [`crate::parse_syslog_at`] の受信時刻にそのまま使える。
+//!
+//! ```no_run
+//! use vlt_syslog_core::capture::{self, DEFAULT_PORTS};
+//! use vlt_syslog_core::parse_syslog_at;
+//!
+//! let data = capture::read_file("customer.pcapng").unwrap();
+//! for captured in capture::read_messages(&data, &DEFAULT_PORTS).unwrap() {
+//!     let msg = parse_syslog_at(&captured.raw, captured.timestamp);
+//!     println!("{} {} {}", captured.source, msg.severity.clone() as u8, msg.content);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use chrono::{DateTime, Utc};
+
+use crate::framing::FrameDecoder;
+
+/// 宛先ポートを指定しないときに syslog とみなすポート(514 = UDP syslog / rsyslog の TCP、601 = RFC 3195)。
+pub const DEFAULT_PORTS: [u16; 2] = [514, 601];
+
+/// [`read_file`] で読むキャプチャファイルの大きさの上限(256 MiB)。
+/// ファイル全体と取り出したメッセージを同時にメモリへ載せるため。
+pub const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+/// TCP で切り出す 1 メッセージの上限バイト数。
+const MAX_TCP_MESSAGE_LEN: usize = 1024 * 1024;
+
+/// 運んでいたトランスポート。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl Transport {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Transport::Udp => "udp",
+            Transport::Tcp => "tcp",
+        }
+    }
+}
+
+/// キャプチャから取り出した syslog メッセージ 1 件(パース前のバイト列)。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedMessage {
+    /// キャプチャした時刻。TCP では最後のセグメントの時刻。
+    pub timestamp: DateTime<Utc>,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub transport: Transport,
+    pub raw: Vec<u8>,
+}
+
+/// キャプチャファイルを読めなかった。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureError {
+    /// pcap / pcapng のどちらでもない。
+    UnknownFormat,
+    /// ファイルの構造が壊れている。値は壊れていた箇所。
+    Malformed(&'static str),
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::UnknownFormat => f.write_str("not a pcap or pcapng file"),
+            CaptureError::Malformed(what) => write!(f, "malformed capture file ({what})"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// キャプチャファイルを読む。[`MAX_FILE_SIZE`] を超えるものは読まずに
+/// [`io::ErrorKind::InvalidData`] のエラーにする。
+pub fn read_file(path: impl AsRef<std::path::Path>) -> io::Result<Vec<u8>> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > MAX_FILE_SIZE {
+        return Err(too_large(len));
+    }
+    let mut data = Vec::with_capacity(len as usize);
+    // 開いたあとに伸びたファイルも上限までしか読まない
+    file.take(MAX_FILE_SIZE + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_FILE_SIZE {
+        return Err(too_large(data.len() as u64));
+    }
+    Ok(data)
+}
+
+fn too_large(len: u64) -> io::Error {
+    const MIB: u64 = 1024 * 1024;
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "capture file is too large ({} MiB; the limit is {} MiB)",
+            len.div_ceil(MIB),
+            MAX_FILE_SIZE / MIB
+        ),
+    )
+}
+
+/// キャプチャファイルの中身から、宛先ポートが `ports` のどれかである syslog メッセージを
+/// 時刻順に取り出す。`ports` が空ならすべての UDP / TCP を対象にする。
+/// 書き込み途中で切れたファイル(最後のパケットが欠けている)は、読めたところまでを返す。
+pub fn read_messages(data: &[u8], ports: &[u16]) -> Result<Vec<CapturedMessage>, CaptureError> {
+    let mut extractor = Extractor {
+        ports,
+        messages: Vec::new(),
+        flows: BTreeMap::new(),
+    };
+    let magic = data.get(..4).ok_or(CaptureError::UnknownFormat)?;
+    match magic {
+        [0x0a, 0x0d, 0x0d, 0x0a] => read_pcapng(data, &mut extractor)?,
+        _ => read_pcap(data, &mut extractor)?,
+    }
+    Ok(extractor.finish())
+}
+
+/// リンク層の種類(LINKTYPE_*)。
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// バイト順を決めた読み出し。
+#[derive(Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        if self.big {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if self.big {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    }
+}
+
+/// 旧来の pcap。ヘッダ 24 バイトの後にレコード(16 バイトのヘッダ + データ)が続く。
+fn read_pcap(data: &[u8], out: &mut Extractor<'_>) -> Result<(), CaptureError> {
+    let (endian, nano_resolution) = match data[..4] {
+        [0xa1, 0xb2, 0xc3, 0xd4] => (Endian { big: true }, false),
+        [0xd4, 0xc3, 0xb2, 0xa1] => (Endian { big: false }, false),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (Endian { big: true }, true),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (Endian { big: false }, true),
+        _ => return Err(CaptureError::UnknownFormat),
+    };
+    let header = data
+        .get(..24)
+        .ok_or(CaptureError::Malformed("pcap header"))?;
+    // 上位ビットは FCS の有無などに使われる
+    let linktype = endian.u32(&header[20..]) & 0x0fff_ffff;
+    let mut pos = 24;
+    while let Some(record) = data.get(pos..pos + 16) {
+        let secs = i64::from(endian.u32(record));
+        let frac = endian.u32(&record[4..]);
+        let len = endian.u32(&record[8..]) as usize;
+        let Some(packet) = data.get(pos + 16..pos + 16 + len) else {
+            break;
+        };
+        let nanos = if nano_resolution {
+            frac
+        } else {
+            frac.saturating_mul(1000)
+        };
+        if let Some(timestamp) = DateTime::from_timestamp(secs, nanos) {
+            out.link(linktype, packet, timestamp);
+        }
+        pos += 16 + len;
+    }
+    Ok(())
+}
+
+/// pcapng のインターフェース(IDB)1 つぶん。
+struct Interface {
+    linktype: u32,
+    /// 時刻の単位。(true, n) なら 2^-n 秒、(false, n) なら 10^-n 秒。
+    resolution: (bool, u8),
+    /// 時刻に足す秒数(if_tsoffset)。
+    offset: i64,
+}
+
+impl Interface {
+    fn timestamp(&self, units: u64) -> Option<DateTime<Utc>> {
+        let (secs, nanos) = match self.resolution {
+            (false, n) => {
+                let per_sec = 10u64.checked_pow(u32::from(n))?;
+                let frac = units % per_sec;
+                let nanos = if n <= 9 {
+                    frac * 10u64.pow(9 - u32::from(n))
+                } else {
+                    frac / 10u64.pow(u32::from(n) - 9)
+                };
+                (units / per_sec, nanos)
+            }
+            (true, n) if n < 64 => {
+                let frac = units & ((1u64 << n) - 1);
+                (units >> n, ((u128::from(frac) * 1_000_000_000) >> n) as u64)
+            }
+            (true, _) => return None,
+        };
+        // if_tsoffset はファイルに書かれた値そのままなので、溢れるものは読めない時刻として扱う
+        let secs = i64::try_from(secs).ok()?.checked_add(self.offset)?;
+        DateTime::from_timestamp(secs, nanos as u32)
+    }
+}
+
+/// pcapng。ブロックの並びで、セクション(SHB)ごとにバイト順とインターフェースの一覧が変わる。
+fn read_pcapng(data: &[u8], out: &mut Extractor<'_>) -> Result<(), CaptureError> {
+    let mut endian = Endian { big: false };
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut pos = 0;
+    while let Some(head) = data.get(pos..pos + 8) {
+        let block_type = endian.u32(head);
+        if block_type == 0x0a0d_0d0a {
+            // Section Header Block。バイト順はここで決まる
+            let magic = data
+                .get(pos + 8..pos + 12)
+                .ok_or(CaptureError::Malformed("section header"))?;
+            endian = match magic {
+                [0x1a, 0x2b, 0x3c, 0x4d] => Endian { big: true },
+                [0x4d, 0x3c, 0x2b, 0x1a] => Endian { big: false },
+                _ => return Err(CaptureError::Malformed("byte-order magic")),
+            };
+            interfaces.clear();
+        }
+        let len = endian.u32(&head[4..]) as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(CaptureError::Malformed("block length"));
+        }
+        let Some(block) = data.get(pos..pos + len) else {
+            break;
+        };
+        let body = &block[8..len - 4];
+        match block_type {
+            // Interface Description Block
+            1 => {
+                if body.len() < 8 {
+                    return Err(CaptureError::Malformed("interface description"));
+                }
+                let mut interface = Interface {
+                    linktype: u32::from(endian.u16(body)),
+                    resolution: (false, 6),
+                    offset: 0,
+                };
+                for (code, value) in options(&body[8..], endian) {
+                    match (code, value) {
+                        (9, [v, ..]) => interface.resolution = (v & 0x80 != 0, v & 0x7f),
+                        (14, v) if v.len() >= 8 => {
+                            let hi = i64::from(endian.u32(v));
+                            let lo = i64::from(endian.u32(&v[4..]));
+                            interface.offset = if endian.big {
+                                (hi << 32) | lo
+                            } else {
+                                (lo << 32) | hi
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+                interfaces.push(interface);
+            }
+            // Enhanced Packet Block / 旧 Packet Block(インターフェース番号が 16 ビット)
+            6 | 2 => {
+                if body.len() < 20 {
+                    return Err(CaptureError::Malformed("packet block"));
+                }
+                let id = if block_type == 6 {
+                    endian.u32(body) as usize
+                } else {
+                    usize::from(endian.u16(body))
+                };
+                let units =
+                    (u64::from(endian.u32(&body[4..])) << 32) | u64::from(endian.u32(&body[8..]));
+                let captured = endian.u32(&body[12..]) as usize;
+                let packet = body
+                    .get(20..20 + captured)
+                    .ok_or(CaptureError::Malformed("packet length"))?;
+                let interface = interfaces
+                    .get(id)
+                    .ok_or(CaptureError::Malformed("interface id"))?;
+                if let Some(timestamp) = interface.timestamp(units) {
+                    out.link(interface.linktype, packet, timestamp);
+                }
+            }
+            // Simple Packet Block は時刻を持たないので取り込まない。その他のブロックも読み飛ばす
+            _ => {}
+        }
+        pos += len;
+    }
+    Ok(())
+}
+
+/// pcapng のオプション(code, value)の並び。opt_endofopt か、読めなくなったところで終わる。
+fn options(mut data: &[u8], endian: Endian) -> Vec<(u16, &[u8])> {
+    let mut out = Vec::new();
+    while data.len() >= 4 {
+        let code = endian.u16(data);
+        let len = usize::from(endian.u16(&data[2..]));
+        if code == 0 {
+            break;
+        }
+        let Some(value) = data.get(4..4 + len) else {
+            break;
+        };
+        out.push((code, value));
+        let padded = 4 + len.div_ceil(4) * 4;
+        data = data.get(padded..).unwrap_or_default();
+    }
+    out
+}
+
+/// TCP の片方向の流れ 1 本。
+struct Flow {
+    /// 次に来るはずのシーケンス番号。
+    next_seq: Option<u32>,
+    decoder: FrameDecoder,
+    last_seen: DateTime<Utc>,
+}
+
+/// パケットから syslog を取り出していく途中の状態。
+struct Extractor<'a> {
+    ports: &'a [u16],
+    messages: Vec<CapturedMessage>,
+    flows: BTreeMap<(SocketAddr, SocketAddr), Flow>,
+}
+
+impl Extractor<'_> {
+    /// リンク層から IP を取り出す。
+    fn link(&mut self, linktype: u32, frame: &[u8], timestamp: DateTime<Utc>) {
+        let ip = match linktype {
+            LINKTYPE_ETHERNET => {
+                let mut pos = 12;
+                let mut ethertype = read_u16(frame, pos);
+                // 802.1Q / 802.1ad の VLAN タグ(入れ子も)を読み飛ばす
+                while matches!(ethertype, Some(0x8100 | 0x88a8 | 0x9100)) {
+                    pos += 4;
+                    ethertype = read_u16(frame, pos);
+                }
+                match ethertype {
+                    Some(0x0800 | 0x86dd) => frame.get(pos + 2..),
+                    _ => None,
+                }
+            }
+            // 先頭 4 バイトは AF_* の値(バイト順が機械によるので見ず、IP の版で見分ける)
+            LINKTYPE_NULL | LINKTYPE_LOOP => frame.get(4..),
+            LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(frame),
+            LINKTYPE_LINUX_SLL => match read_u16(frame, 14) {
+                Some(0x0800 | 0x86dd) => frame.get(16..),
+                _ => None,
+            },
+            LINKTYPE_LINUX_SLL2 => match read_u16(frame, 0) {
+                Some(0x0800 | 0x86dd) => frame.get(20..),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(ip) = ip {
+            self.ip(ip, timestamp);
+        }
+    }
+
+    /// IP からトランスポートを取り出す。フラグメントは捨てる。
+    fn ip(&mut self, packet: &[u8], timestamp: DateTime<Utc>) {
+        let Some(&first) = packet.first() else {
+            return;
+        };
+        let (src, dst, mut protocol, mut payload) = match first >> 4 {
+            4 => {
+                let header_len = usize::from(first & 0x0f) * 4;
+                if packet.len() < 20 || header_len < 20 {
+                    return;
+                }
+                let total = usize::from(read_u16(packet, 2).unwrap_or(0));
+                let flags_offset = read_u16(packet, 6).unwrap_or(0);
+                // More Fragments か Fragment Offset があれば組み立てが要る
+                if flags_offset & 0x3fff != 0 {
+                    return;
+                }
+                let Some(payload) = packet.get(header_len..total.min(packet.len())) else {
+                    return;
+                };
+                let addr = |at: usize| {
+                    IpAddr::V4(Ipv4Addr::new(
+                        packet[at],
+                        packet[at + 1],
+                        packet[at + 2],
+                        packet[at + 3],
+                    ))
+                };
+                (addr(12), addr(16), packet[9], payload)
+            }
+            6 => {
+                if packet.len() < 40 {
+                    return;
+                }
+                let len = usize::from(read_u16(packet, 4).unwrap_or(0));
+                let addr = |at: usize| {
+                    let octets: [u8; 16] = packet[at..at + 16].try_into().unwrap_or_default();
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                };
+                let end = (40 + len).min(packet.len());
+                (addr(8), addr(24), packet[6], &packet[40..end])
+            }
+            _ => return,
+        };
+        // IPv6 の拡張ヘッダ(Hop-by-Hop / Routing / Destination Options)を読み飛ばす
+        while matches!(protocol, 0 | 43 | 60) {
+            let (Some(&next), Some(&len)) = (payload.first(), payload.get(1)) else {
+                return;
+            };
+            let Some(rest) = payload.get((usize::from(len) + 1) * 8..) else {
+                return;
+            };
+            protocol = next;
+            payload = rest;
+        }
+        match protocol {
+            17 => self.udp(src, dst, payload, timestamp),
+            6 => self.tcp(src, dst, payload, timestamp),
+            // 44 は IPv6 のフラグメント
+            _ => {}
+        }
+    }
+
+    fn wanted(&self, port: u16) -> bool {
+        self.ports.is_empty() || self.ports.contains(&port)
+    }
+
+    fn udp(&mut self, src: IpAddr, dst: IpAddr, segment: &[u8], timestamp: DateTime<Utc>) {
+        let (Some(sport), Some(dport), Some(len)) = (
+            read_u16(segment, 0),
+            read_u16(segment, 2),
+            read_u16(segment, 4),
+        ) else {
+            return;
+        };
+        if !self.wanted(dport) {
+            return;
+        }
+        let end = usize::from(len).clamp(8, segment.len().max(8));
+        let Some(raw) = segment.get(8..end) else {
+            return;
+        };
+        if raw.is_empty() {
+            return;
+        }
+        self.messages.push(CapturedMessage {
+            timestamp,
+            source: SocketAddr::new(src, sport),
+            destination: SocketAddr::new(dst, dport),
+            transport: Transport::Udp,
+            raw: raw.to_vec(),
+        });
+    }
+
+    fn tcp(&mut self, src: IpAddr, dst: IpAddr, segment: &[u8], timestamp: DateTime<Utc>) {
+        if segment.len() < 20 {
+            return;
+        }
+        let sport = read_u16(segment, 0).unwrap_or(0);
+        let dport = read_u16(segment, 2).unwrap_or(0);
+        if !self.wanted(dport) {
+            return;
+        }
+        let seq = u32::from_be_bytes([segment[4], segment[5], segment[6], segment[7]]);
+        let flags = segment[13];
+        let Some(mut data) = segment.get(usize::from(segment[12] >> 4) * 4..) else {
+            return;
+        };
+        let source = SocketAddr::new(src, sport);
+        let destination = SocketAddr::new(dst, dport);
+        let flow = self
+            .flows
+            .entry((source, destination))
+            .or_insert_with(|| Flow {
+                next_seq: None,
+                decoder: FrameDecoder::new(MAX_TCP_MESSAGE_LEN),
+                last_seen: timestamp,
+            });
+        flow.last_seen = timestamp;
+        // SYN は新しい接続の始まり(同じポートの使い回しもここで区切る)
+        if flags & 0x02 != 0 {
+            flow.next_seq = Some(seq.wrapping_add(1));
+            flow.decoder = FrameDecoder::new(MAX_TCP_MESSAGE_LEN);
+            return;
+        }
+        if data.is_empty() {
+            return;
+        }
+        let next = *flow.next_seq.get_or_insert(seq);
+        // 読んだところより前で終わる再送で next_seq を戻さない(順序はシーケンス番号の周回を考えて比べる)
+        let end = seq.wrapping_add(data.len() as u32);
+        if end.wrapping_sub(next) as i32 > 0 {
+            flow.next_seq = Some(end);
+        }
+        let ahead = seq.wrapping_sub(next) as i32;
+        if ahead < 0 {
+            // 再送。既に読んだぶんを除く
+            let seen = ahead.unsigned_abs() as usize;
+            if seen >= data.len() {
+                return;
+            }
+            data = &data[seen..];
+        } else if ahead > 0 {
+            // 取りこぼしたセグメントがある。区切りを読み直す
+            flow.decoder = FrameDecoder::new(MAX_TCP_MESSAGE_LEN);
+        }
+        flow.decoder.extend(data);
+        loop {
+            match flow.decoder.next_frame() {
+                Ok(Some(raw)) => self.messages.push(CapturedMessage {
+                    timestamp,
+                    source,
+                    destination,
+                    transport: Transport::Tcp,
+                    raw,
+                }),
+                Ok(None) => break,
+                Err(_) => {
+                    flow.decoder = FrameDecoder::new(MAX_TCP_MESSAGE_LEN);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// 接続の終わりに残っていた(LF の無い最後の)メッセージを足し、時刻順に並べる。
+    fn finish(mut self) -> Vec<CapturedMessage> {
+        for ((source, destination), mut flow) in std::mem::take(&mut self.flows) {
+            if let Some(raw) = flow.decoder.finish() {
+                self.messages.push(CapturedMessage {
+                    timestamp: flow.last_seen,
+                    source,
+                    destination,
+                    transport: Transport::Tcp,
+                    raw,
+                });
+            }
+        }
+        self.messages.sort_by_key(|m| m.timestamp);
+        self.messages
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
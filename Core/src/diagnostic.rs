@@ -0,0 +1,112 @@
+//! パース時の診断(どこが規格から外れていたか)。
+//!
+//! パーサは壊れた入力でも失敗せずにメッセージを返すが、その際に救済した箇所を
+//! [`Diagnostic`] として [`crate::SyslogMessage::diagnostics`] に残す。
+//! `offset` は受信した生バイト列の先頭からのバイト位置。
+//!
+//! 常に記録するのは「読めなかった」もの(PRI・VERSION・HEADER・SD・charset の誤り)。
+//! [`crate::ParseOptions::strict`] を立てると、読めはするが規格に沿っていないもの
+//! (RFC 3164 の HEADER 欠落、RFC 5424 で BOM の無い非 ASCII の MSG)も記録し、
+//! 診断が 1 件でもあるメッセージに `nonconforming` を付ける。
+
+use serde::{Deserialize, Serialize};
+
+/// 診断の種類。JSON・ログ・統計では snake_case の名前(`as_str`)で出す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// 先頭に PRI("<PRIVAL>")が無い。
+    MissingPri,
+    /// PRI の形が崩れている、または PRIVAL が 0〜191 の範囲外。
+    InvalidPri,
+    /// RFC 5424 の VERSION が規格外("0"・先頭 0・1 以外)。
+    InvalidVersion,
+    /// RFC 5424 の HEADER が途中で終わっている。
+    TruncatedHeader,
+    /// TIMESTAMP が読めない(RFC 3339 でない、存在しない日付など)。
+    InvalidTimestamp,
+    /// HOSTNAME / APP-NAME / PROCID / MSGID が長すぎる、または PRINTUSASCII 以外を含む。
+    InvalidHeaderField,
+    /// STRUCTURED-DATA が閉じていない・形が崩れている("-" でも "[" でもない場合を含む)。
+    MalformedSd,
+    /// SD の charset に書かれた名前を知らない。
+    UnknownCharset,
+    /// UTF-8 と宣言された(SD の charset・BOM)MSG が正しい UTF-8 ではない。
+    InvalidUtf8,
+    /// (strict)RFC 3164 の HEADER(TIMESTAMP HOSTNAME)が無い。
+    MissingHeader,
+    /// (strict)RFC 5424 の MSG が非 ASCII なのに BOM も charset も無い。
+    MissingBom,
+}
+
+impl DiagnosticKind {
+    /// 全種類(宣言順)。統計の表示に使う。
+    pub const ALL: [DiagnosticKind; 11] = [
+        DiagnosticKind::MissingPri,
+        DiagnosticKind::InvalidPri,
+        DiagnosticKind::InvalidVersion,
+        DiagnosticKind::TruncatedHeader,
+        DiagnosticKind::InvalidTimestamp,
+        DiagnosticKind::InvalidHeaderField,
+        DiagnosticKind::MalformedSd,
+        DiagnosticKind::UnknownCharset,
+        DiagnosticKind::InvalidUtf8,
+        DiagnosticKind::MissingHeader,
+        DiagnosticKind::MissingBom,
+    ];
+
+    /// snake_case の名前(serde の表記と同じ)。
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticKind::MissingPri => "missing_pri",
+            DiagnosticKind::InvalidPri => "invalid_pri",
+            DiagnosticKind::InvalidVersion => "invalid_version",
+            DiagnosticKind::TruncatedHeader => "truncated_header",
+            DiagnosticKind::InvalidTimestamp => "invalid_timestamp",
+            DiagnosticKind::InvalidHeaderField => "invalid_header_field",
+            DiagnosticKind::MalformedSd => "malformed_sd",
+            DiagnosticKind::UnknownCharset => "unknown_charset",
+            DiagnosticKind::InvalidUtf8 => "invalid_utf8",
+            DiagnosticKind::MissingHeader => "missing_header",
+            DiagnosticKind::MissingBom => "missing_bom",
+        }
+    }
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 診断 1 件。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// 問題の箇所の、生バイト列の先頭からのバイト位置。
+    pub offset: usize,
+    /// 補足(問題の値など)。無ければ空。
+    #[serde(default)]
+    pub detail: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(kind: DiagnosticKind, offset: usize, detail: impl Into<String>) -> Self {
+        Self {
+            kind,
+            offset,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    /// `invalid_pri@0` / `invalid_header_field@31 (APP-NAME)` の形。
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.kind, self.offset)?;
+        if !self.detail.is_empty() {
+            write!(f, " ({})", self.detail)?;
+        }
+        Ok(())
+    }
+}
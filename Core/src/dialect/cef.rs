@@ -0,0 +1,161 @@
+//! ArcSight CEF(Common Event Format)。
+//!
+//! `CEF:Version|Device Vendor|Device Product|Device Version|Signature ID|Name|Severity|Extension`
+//! ヘッダーの各項目は `\|` と `\\` をエスケープに使う。Extension は空白区切りの `key=value` で、
+//! 値には空白を含めてよい代わりに `=` は `\=` と書く(`\\`・`\n`・`\r` も使える)。
+
+use super::Dialect;
+use crate::message::SyslogMessage;
+
+pub struct Cef;
+
+/// ヘッダー 7 項目を入れるキー。Severity だけは共通キーの `vendor_severity` にする。
+const HEADER_KEYS: [&str; 7] = [
+    "cef_version",
+    "device_vendor",
+    "device_product",
+    "device_version",
+    "signature_id",
+    "name",
+    "vendor_severity",
+];
+
+impl Dialect for Cef {
+    fn name(&self) -> &'static str {
+        "cef"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        let Some(payload) = find_payload(body, msg, "CEF") else {
+            return false;
+        };
+        let Some((header, extension)) = split_header(payload, HEADER_KEYS.len()) else {
+            return false;
+        };
+        if !header[0].bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+
+        let signature_id = header[4].clone();
+        let name = header[5].clone();
+        let product = header[2].clone();
+        for (key, value) in HEADER_KEYS.iter().zip(header) {
+            msg.fields.insert(key.to_string(), value);
+        }
+        msg.fields.insert("mnemonic".to_string(), signature_id);
+        for (key, value) in parse_extension(extension) {
+            msg.fields.insert(key, value);
+        }
+        if !product.is_empty() {
+            msg.tag = Some(product);
+        }
+        msg.content = name;
+        true
+    }
+}
+
+/// 本文から `CEF:` / `LEEF:` の後ろ(バージョン以降)を取り出す。
+/// 本文の先頭か空白の直後にあるものだけを見る。BSD ヘッダー付きで届くと
+/// `CEF:` が TAG として切り取られているので、TAG がそれなら本文全体を返す。
+pub(super) fn find_payload<'a>(body: &'a str, msg: &SyslogMessage, magic: &str) -> Option<&'a str> {
+    if msg.tag.as_deref() == Some(magic) && body.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(body);
+    }
+    let marker = format!("{magic}:");
+    let mut from = 0;
+    while let Some(p) = body[from..].find(&marker) {
+        let pos = from + p;
+        if pos == 0 || body.as_bytes()[pos - 1] == b' ' {
+            return Some(&body[pos + marker.len()..]);
+        }
+        from = pos + 1;
+    }
+    None
+}
+
+/// `|` 区切りのヘッダーを `count` 項目読む(`\|` と `\\` を戻す)。
+/// 戻り値は (項目の列, 残り)。項目が足りなければ None。
+pub(super) fn split_header(s: &str, count: usize) -> Option<(Vec<String>, &str)> {
+    let mut fields = Vec::with_capacity(count);
+    let mut current = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.clone().next() {
+                Some((_, next @ ('|' | '\\'))) => {
+                    current.push(next);
+                    chars.next();
+                }
+                _ => current.push(c),
+            },
+            '|' => {
+                fields.push(std::mem::take(&mut current));
+                if fields.len() == count {
+                    return Some((fields, &s[i + 1..]));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    None
+}
+
+/// Extension(`key=value key2=value with spaces`)を読む。
+/// キーは「先頭か空白の直後に英数字等が続き、`=` で終わる語」。値はそこから次のキーの手前まで。
+fn parse_extension(s: &str) -> Vec<(String, String)> {
+    let bytes = s.as_bytes();
+    // (キーの開始位置, "=" の位置)
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if i == 0 || bytes[i - 1] == b' ' {
+            let start = i;
+            let mut j = i;
+            while j < bytes.len() && is_key_byte(bytes[j]) {
+                j += 1;
+            }
+            if j > start && bytes.get(j) == Some(&b'=') {
+                keys.push((start, j));
+                i = j + 1;
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    keys.iter()
+        .enumerate()
+        .map(|(n, &(start, eq))| {
+            let end = keys.get(n + 1).map_or(s.len(), |&(next, _)| next);
+            let key = s[start..eq].to_string();
+            (key, unescape_value(s[eq + 1..end].trim_end()))
+        })
+        .collect()
+}
+
+fn is_key_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-' | b'[' | b']')
+}
+
+/// Extension 値のエスケープ(`\=` `\\` `\|` `\n` `\r`)を戻す。
+fn unescape_value(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(next @ ('=' | '\\' | '|')) => out.push(next),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,122 @@
+//! Cisco IOS / IOS-XE / IOS-XR / NX-OS。
+//!
+//! 本文は `[seq: ][host: ][*|.]timestamp: %FACILITY-SEVERITY-MNEMONIC: text` の形。
+//! seq・host・timestamp は機器の `service sequence-numbers` などの設定次第で付いたり付かなかったりし、
+//! NX-OS は区切りが ": " でなく空白になる。`%FAC-n-MNEMONIC` の部分を目印に見分ける。
+
+use super::Dialect;
+use crate::message::SyslogMessage;
+
+pub struct Cisco;
+
+impl Dialect for Cisco {
+    fn name(&self) -> &'static str {
+        "cisco"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        let Some((start, mnemonic, text)) = find_mnemonic(body) else {
+            return false;
+        };
+        let Some((facility, severity, name)) = split_mnemonic(mnemonic) else {
+            return false;
+        };
+
+        // BSD ヘッダー付きで届くと、通し番号が "seq:" の形で TAG に取られている
+        if let Some(tag) = &msg.tag
+            && tag.bytes().all(|b| b.is_ascii_digit())
+        {
+            msg.fields.insert("seq".to_string(), tag.clone());
+        }
+
+        // 目印より前: "000123: core-sw1: *Oct 18 12:00:00.123" のような前置き
+        let prefix = body[..start].trim_end().trim_end_matches(':');
+        for part in prefix.split(": ").map(str::trim).filter(|p| !p.is_empty()) {
+            if part.bytes().all(|b| b.is_ascii_digit()) {
+                msg.fields.insert("seq".to_string(), part.to_string());
+            } else if looks_like_time(part) {
+                // NX-OS は "2026 Oct 18 12:00:00 nxos1" と時刻の後ろに空白でホスト名が続く
+                let (time, host) = match part.rsplit_once(' ') {
+                    Some((time, host)) if is_hostname(host) => (time, Some(host)),
+                    _ => (part, None),
+                };
+                msg.fields.insert("device_time".to_string(), time.to_string());
+                if let Some(host) = host
+                    && msg.hostname.is_none()
+                {
+                    msg.hostname = Some(host.to_string());
+                }
+            } else if !part.contains(' ') && msg.hostname.is_none() {
+                msg.hostname = Some(part.to_string());
+            }
+        }
+
+        msg.fields.insert("mnemonic".to_string(), name.to_string());
+        msg.fields.insert("facility_code".to_string(), facility.to_string());
+        msg.fields.insert("vendor_severity".to_string(), severity.to_string());
+        msg.tag = Some(facility.to_string());
+        msg.content = text.trim().to_string();
+        true
+    }
+}
+
+/// `%FAC-n-MNEMONIC:` を探す。戻り値は (`%` の位置, `FAC-n-MNEMONIC`, 後ろの本文)。
+/// `%` は本文の先頭か空白・":" の直後にあるものだけを見る(本文中の "50%" などを拾わない)。
+fn find_mnemonic(body: &str) -> Option<(usize, &str, &str)> {
+    let bytes = body.as_bytes();
+    let mut from = 0;
+    while let Some(p) = body[from..].find('%') {
+        let pos = from + p;
+        from = pos + 1;
+        if pos > 0 && !matches!(bytes[pos - 1], b' ' | b':') {
+            continue;
+        }
+        let rest = &body[pos + 1..];
+        // IOS-XR は "%PKT_INFRA-LINK-3-UPDOWN :" のようにコロンの前に空白を入れる
+        let Some(colon) = rest.find(':') else {
+            continue;
+        };
+        let token = rest[..colon].trim_end();
+        if token.is_empty() || token.contains(' ') {
+            continue;
+        }
+        if split_mnemonic(token).is_some() {
+            return Some((pos, token, &rest[colon + 1..]));
+        }
+    }
+    None
+}
+
+/// `FAC[-SUB]-n-MNEMONIC` を (facility, severity, mnemonic) に分ける。
+fn split_mnemonic(token: &str) -> Option<(&str, &str, &str)> {
+    let (head, name) = token.rsplit_once('-')?;
+    let (facility, severity) = head.rsplit_once('-')?;
+    let is_code = |s: &str| {
+        !s.is_empty()
+            && s.bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+    };
+    let valid = is_code(facility)
+        && is_code(name)
+        && severity.len() == 1
+        && (b'0'..=b'7').contains(&severity.as_bytes()[0]);
+    valid.then_some((facility, severity, name))
+}
+
+/// 前置きの 1 区切りが時刻らしいか("*Mar  1 00:01:02.123"、"Oct 18 2026 12:00:00 JST" など)。
+fn looks_like_time(part: &str) -> bool {
+    let part = part.trim_start_matches(['*', '.']);
+    part.contains(':')
+        && part
+            .split_ascii_whitespace()
+            .any(|w| w.len() >= 5 && w.as_bytes()[2] == b':')
+}
+
+/// 時刻の後ろに続く語がホスト名らしいか。"JST" のようなタイムゾーン略号と区別するため、
+/// 小文字・数字・"-"・"." のどれかを含むものだけをホスト名とみなす。
+fn is_hostname(word: &str) -> bool {
+    !word.contains(':')
+        && word
+            .bytes()
+            .any(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'.')
+}
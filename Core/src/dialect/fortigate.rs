@@ -0,0 +1,51 @@
+//! Fortinet FortiGate。
+//!
+//! 本文全体が `date=2026-10-18 time=12:00:00 devname="FGT60F" logid="0000000013" type="traffic" …`
+//! のような `key=value` の列になっている。`devname` と `logid` の両方があるものを FortiGate とみなす。
+
+use super::Dialect;
+use super::kv::parse_kv;
+use crate::message::SyslogMessage;
+
+pub struct FortiGate;
+
+impl Dialect for FortiGate {
+    fn name(&self) -> &'static str {
+        "fortigate"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        if !(body.contains("devname=") && body.contains("logid=")) {
+            return false;
+        }
+        let Some(pairs) = parse_kv(body.trim()) else {
+            return false;
+        };
+        if !pairs.iter().any(|(k, _)| k == "devname") || !pairs.iter().any(|(k, _)| k == "logid") {
+            return false;
+        }
+
+        for (key, value) in pairs {
+            match key.as_str() {
+                "devname" if msg.hostname.is_none() => msg.hostname = Some(value.clone()),
+                "level" => {
+                    msg.fields.insert("vendor_severity".to_string(), value.clone());
+                }
+                "logid" => {
+                    msg.fields.insert("mnemonic".to_string(), value.clone());
+                }
+                "type" => msg.tag = Some(value.clone()),
+                _ => {}
+            }
+            msg.fields.insert(key, value);
+        }
+        // "time=12:00:00" のコロンで TAG を推測した結果が本文に残らないよう、本文は丸ごと戻す。
+        // イベントログなら msg="..." に人が読む文があるので、そちらを本文にする。
+        msg.content = msg
+            .fields
+            .get("msg")
+            .cloned()
+            .unwrap_or_else(|| body.trim().to_string());
+        true
+    }
+}
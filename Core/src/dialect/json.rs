@@ -0,0 +1,97 @@
+//! JSON の本文。CEE(`@cee: {...}`)と、本文がそのまま JSON オブジェクトのもの。
+//!
+//! 入れ子のオブジェクト・配列はキーを "." でつないで平らにする(`{"user":{"name":"alice"}}` →
+//! `user.name=alice`、配列は `tags.0`)。文字列以外の値は JSON の表記のまま(`true`、`42`)。
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::Dialect;
+use crate::message::SyslogMessage;
+
+pub struct Json;
+
+/// 人が読む本文として使うキー(先にあるものを優先)。
+pub(super) const MESSAGE_KEYS: &[&str] = &["msg", "message", "MESSAGE"];
+/// 重要度として `vendor_severity` に写すキー。
+pub(super) const LEVEL_KEYS: &[&str] = &["level", "severity", "lvl"];
+
+impl Dialect for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        // "tag: {...}" のように TAG の後ろに JSON が来ることもあるので、本文全体と TAG 除去後の両方を見る
+        let Some(object) = [body, msg.content.as_str()]
+            .into_iter()
+            .find_map(parse_object)
+        else {
+            return false;
+        };
+
+        let mut fields = BTreeMap::new();
+        flatten("", &Value::Object(object), &mut fields);
+        msg.fields.extend(fields);
+        promote_common_keys(msg);
+        true
+    }
+}
+
+/// `@cee:` の後ろ、または本文そのものが JSON オブジェクトなら読む。
+fn parse_object(text: &str) -> Option<serde_json::Map<String, Value>> {
+    let text = text.trim();
+    let json = text.strip_prefix("@cee:").unwrap_or(text).trim_start();
+    if !json.starts_with('{') {
+        return None;
+    }
+    match serde_json::from_str(json).ok()? {
+        Value::Object(map) => Some(map),
+        _ => None,
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, String>) {
+    let key = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{prefix}.{k}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                flatten(&key(k), v, out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(&key(&i.to_string()), v, out);
+            }
+        }
+        Value::String(s) => {
+            out.insert(prefix.to_string(), s.clone());
+        }
+        Value::Null => {
+            out.insert(prefix.to_string(), String::new());
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+/// JSON / logfmt 共通の後処理。`msg` などがあればそれを本文に、`level` などを `vendor_severity` にする。
+/// 本文にできるキーが無ければ、エスケープだらけの元の文字列ではなく項目を `key=value` で並べたものを本文にする。
+pub(super) fn promote_common_keys(msg: &mut SyslogMessage) {
+    if let Some(level) = LEVEL_KEYS.iter().find_map(|k| msg.fields.get(*k)) {
+        msg.fields
+            .insert("vendor_severity".to_string(), level.clone());
+    }
+    msg.content = match MESSAGE_KEYS.iter().find_map(|k| msg.fields.get(*k)) {
+        Some(text) => text.clone(),
+        None => msg.fields_logfmt(),
+    };
+}
@@ -0,0 +1,77 @@
+//! Juniper Junos。
+//!
+//! Junos はメッセージごとにイベント名(`UI_COMMIT` など)を持ち、送り方で置き場所が変わる:
+//!   - RFC 5424(`structured-data` 指定)… MSGID がイベント名、項目は SD の `[junos@2636.… k="v"]`
+//!   - BSD 形式 … `daemon[pid]: EVENT_NAME: text`、
+//!     brief 指定なら `EVENT_NAME [junos@2636.… k="v"] text`
+
+use super::Dialect;
+use crate::message::{SdElement, SyslogMessage};
+use crate::parser::parse_structured_data;
+
+pub struct Juniper;
+
+/// Juniper Networks の IANA Private Enterprise Number。
+const JUNIPER_PEN: &str = "@2636";
+
+impl Dialect for Juniper {
+    fn name(&self) -> &'static str {
+        "juniper"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        // RFC 5424: SD 側に junos@2636 の要素がある
+        if let Some(element) = msg.structured_data.iter().find(|e| is_junos(e)) {
+            let params = element.params.clone();
+            if let Some(event) = msg.msgid.clone() {
+                msg.fields.insert("mnemonic".to_string(), event);
+            }
+            msg.fields.extend(params);
+            return true;
+        }
+
+        // BSD 形式: TAG(デーモン名)の後ろの本文がイベント名で始まる
+        if msg.tag.is_none() {
+            return false;
+        }
+        let event_end = body
+            .find([':', ' '])
+            .unwrap_or(body.len());
+        let event = &body[..event_end];
+        if !is_event_name(event) {
+            return false;
+        }
+        let rest = &body[event_end..];
+        let text = if let Some(text) = rest.strip_prefix(':') {
+            text
+        } else if let Some(sd) = rest.strip_prefix(' ').filter(|r| r.starts_with('['))
+            && let Some((elements, len)) = parse_structured_data(sd.as_bytes())
+            && elements.iter().any(is_junos)
+        {
+            for element in elements.into_iter().filter(is_junos) {
+                msg.fields.extend(element.params);
+            }
+            &sd[len..]
+        } else {
+            return false;
+        };
+
+        msg.fields.insert("mnemonic".to_string(), event.to_string());
+        msg.content = text.trim().to_string();
+        true
+    }
+}
+
+fn is_junos(element: &SdElement) -> bool {
+    element.id.starts_with("junos") && element.id.contains(JUNIPER_PEN)
+}
+
+/// Junos のイベント名("UI_COMMIT"、"SNMP_TRAP_LINK_DOWN" など)か。
+/// 大文字・数字と "_" だけで、"_" を 1 つ以上含み、英字で始まるもの。
+fn is_event_name(s: &str) -> bool {
+    s.len() > 2
+        && s.as_bytes()[0].is_ascii_uppercase()
+        && s.contains('_')
+        && s.bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+}
@@ -0,0 +1,104 @@
+//! `key=value key2="quoted value"` 形式の本文を読む小さな字句解析。FortiGate などが使う。
+
+/// 空白区切りの `key=value` 列を読む。値は `"..."` で囲んでもよく、その中では
+/// `\"` と `\\` をエスケープとして扱う。
+///
+/// 1 つでも `key=value` になっていない語があれば None(この形式ではないとみなす)。
+pub(crate) fn parse_kv(s: &str) -> Option<Vec<(String, String)>> {
+    let bytes = s.as_bytes();
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && bytes[i] == b' ' {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+
+        let key_start = i;
+        while i < bytes.len() && is_key_byte(bytes[i]) {
+            i += 1;
+        }
+        if i == key_start || bytes.get(i) != Some(&b'=') {
+            return None;
+        }
+        let key = &s[key_start..i];
+        i += 1;
+
+        let value = if bytes.get(i) == Some(&b'"') {
+            i += 1;
+            let mut value = String::new();
+            let mut closed = false;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'\\' if matches!(bytes.get(i + 1), Some(b'"' | b'\\')) => {
+                        value.push(bytes[i + 1] as char);
+                        i += 2;
+                    }
+                    b'"' => {
+                        closed = true;
+                        i += 1;
+                        break;
+                    }
+                    _ => {
+                        // マルチバイト文字を壊さないよう、次の区切りまでを str のまま足す
+                        let run = bytes[i..]
+                            .iter()
+                            .position(|&b| b == b'"' || b == b'\\')
+                            .map_or(bytes.len(), |p| i + p)
+                            .max(i + 1);
+                        value.push_str(&s[i..run]);
+                        i = run;
+                    }
+                }
+            }
+            if !closed {
+                return None;
+            }
+            value
+        } else {
+            let start = i;
+            while i < bytes.len() && bytes[i] != b' ' {
+                i += 1;
+            }
+            s[start..i].to_string()
+        };
+        pairs.push((key.to_string(), value));
+    }
+    if pairs.is_empty() { None } else { Some(pairs) }
+}
+
+fn is_key_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.')
+}
+
+/// 項目を logfmt の 1 行にする(`parse_kv` で読み戻せる形)。
+/// 空白・`"`・`=` を含む値と空の値は `"..."` で囲み、`"` と `\` をエスケープする。
+pub(crate) fn format_kv<'a>(pairs: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    let mut out = String::new();
+    for (key, value) in pairs {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(key);
+        out.push('=');
+        let needs_quote = value.is_empty()
+            || value
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '"' | '=' | '\\'));
+        if needs_quote {
+            out.push('"');
+            for c in value.chars() {
+                if matches!(c, '"' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        } else {
+            out.push_str(value);
+        }
+    }
+    out
+}
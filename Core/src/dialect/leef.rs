@@ -0,0 +1,96 @@
+//! IBM QRadar LEEF(Log Event Extended Format)1.0 / 2.0。
+//!
+//! - 1.0: `LEEF:1.0|Vendor|Product|Version|EventID|key=value<TAB>key=value…`
+//! - 2.0: `LEEF:2.0|Vendor|Product|Version|EventID|DelimiterChar|key=value<DELIM>…`
+//!
+//! 2.0 の区切り文字は 1 文字そのまま(`^` など)か、`0x09` / `x09` の 16 進表記。
+//! 省略・空なら 1.0 と同じタブ。ヘッダーのエスケープは CEF と同じく `\|` と `\\`。
+
+use super::Dialect;
+use super::cef::{find_payload, split_header};
+use crate::message::SyslogMessage;
+
+pub struct Leef;
+
+const HEADER_KEYS: [&str; 5] = [
+    "leef_version",
+    "device_vendor",
+    "device_product",
+    "device_version",
+    "event_id",
+];
+
+impl Dialect for Leef {
+    fn name(&self) -> &'static str {
+        "leef"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        let Some(payload) = find_payload(body, msg, "LEEF") else {
+            return false;
+        };
+        let Some((header, mut attributes)) = split_header(payload, HEADER_KEYS.len()) else {
+            return false;
+        };
+        let version = header[0].as_str();
+        if !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return false;
+        }
+
+        let mut delimiter = '\t';
+        if version.starts_with('2')
+            && let Some((spec, rest)) = attributes.split_once('|')
+            && let Some(d) = parse_delimiter(spec)
+        {
+            delimiter = d;
+            attributes = rest;
+        }
+
+        let event_id = header[4].clone();
+        let product = header[2].clone();
+        for (key, value) in HEADER_KEYS.iter().zip(header) {
+            msg.fields.insert(key.to_string(), value);
+        }
+        msg.fields.insert("mnemonic".to_string(), event_id);
+        for attr in attributes.split(delimiter) {
+            if let Some((key, value)) = attr.split_once('=')
+                && !key.trim().is_empty()
+            {
+                msg.fields.insert(key.trim().to_string(), value.to_string());
+            }
+        }
+        if let Some(sev) = msg.fields.get("sev").cloned() {
+            msg.fields.insert("vendor_severity".to_string(), sev);
+        }
+        if !product.is_empty() {
+            msg.tag = Some(product);
+        }
+        if let Some(text) = msg.fields.get("msg") {
+            msg.content = text.clone();
+        }
+        true
+    }
+}
+
+/// 2.0 ヘッダー 6 項目目の区切り文字指定を読む。区切り指定として読めなければ None
+/// (そのときは 6 項目目は無く、属性がすぐ始まっているとみなす)。
+fn parse_delimiter(spec: &str) -> Option<char> {
+    if spec.is_empty() {
+        return Some('\t');
+    }
+    let hex = spec
+        .strip_prefix("0x")
+        .or_else(|| spec.strip_prefix("0X"))
+        .or_else(|| spec.strip_prefix('x'))
+        .or_else(|| spec.strip_prefix('X'));
+    if let Some(hex) = hex
+        && !hex.is_empty()
+    {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    let mut chars = spec.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '=' => Some(c),
+        _ => None,
+    }
+}
@@ -0,0 +1,30 @@
+//! logfmt(`level=info msg="user logged in" user=alice`)の本文。
+//!
+//! どのアプリの出力でもありうる形なので、FortiGate など特定の機器の `key=value` より後に試す。
+//! 誤検出を避けるため、本文全体が `key=value` の列で 2 組以上あるものだけを扱う。
+
+use super::Dialect;
+use super::json::promote_common_keys;
+use super::kv::parse_kv;
+use crate::message::SyslogMessage;
+
+pub struct Logfmt;
+
+impl Dialect for Logfmt {
+    fn name(&self) -> &'static str {
+        "logfmt"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        let Some(pairs) = [body, msg.content.as_str()]
+            .into_iter()
+            .filter_map(|text| parse_kv(text.trim()))
+            .find(|pairs| pairs.len() >= 2)
+        else {
+            return false;
+        };
+        msg.fields.extend(pairs);
+        promote_common_keys(msg);
+        true
+    }
+}
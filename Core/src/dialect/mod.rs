@@ -0,0 +1,94 @@
+//! ベンダー方言: syslog の枠(PRI / HEADER)の中身、つまり MSG 本文の形式ごとの解釈。
+//!
+//! 機器によって本文の書き方が決まっている(Cisco の `%LINK-3-UPDOWN:`、FortiGate の
+//! `key=value`、Palo Alto の CSV、セキュリティ製品の CEF / LEEF、アプリの JSON / logfmt など)。[`Dialect`] はそれを見分けて項目を取り出し、
+//! [`SyslogMessage::fields`] に入れる。どの方言にも当てはまらなければ何もしない。
+//!
+//! 方言をまたいで同じ意味の項目は、次の共通キーにそろえる:
+//!
+//! | キー              | 内容                                              |
+//! |-------------------|---------------------------------------------------|
+//! | `mnemonic`        | ベンダーのメッセージ ID(Cisco の mnemonic、Junos のイベント名など) |
+//! | `facility_code`   | ベンダー独自の facility(Cisco の `LINK` など)     |
+//! | `vendor_severity` | ベンダー独自の重要度(syslog の PRI とは別に本文に書かれたもの。JSON / logfmt の `level` も) |
+//! | `seq`             | 機器が振った通し番号                              |
+//!
+//! それ以外のキーは各ベンダーの項目名をそのまま使う(FortiGate の `srcip` など)。
+//! JSON の入れ子は "." でつないだキーになる(`user.name`)。
+
+mod cef;
+mod cisco;
+mod fortigate;
+mod json;
+mod juniper;
+pub(crate) mod kv;
+mod leef;
+mod logfmt;
+mod paloalto;
+
+use std::sync::LazyLock;
+
+use crate::message::SyslogMessage;
+
+pub use cef::Cef;
+pub use cisco::Cisco;
+pub use fortigate::FortiGate;
+pub use json::Json;
+pub use juniper::Juniper;
+pub use leef::Leef;
+pub use logfmt::Logfmt;
+pub use paloalto::PaloAlto;
+
+/// 本文の形式 1 つ分の解釈。
+pub trait Dialect: Send + Sync {
+    /// `SyslogMessage::dialect` に入れる名前(小文字)。
+    fn name(&self) -> &'static str;
+
+    /// `body`(TAG を推測で切り出す前の MSG 全体)がこの方言なら `msg` に項目を書き込んで true。
+    /// 違えば `msg` に触らず false を返す。
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool;
+}
+
+/// 順に試す方言の一覧。最初に当てはまった 1 つだけを使う。
+pub struct DialectSet {
+    dialects: Vec<Box<dyn Dialect>>,
+}
+
+impl DialectSet {
+    /// 方言を 1 つも持たない一覧(本文を解釈しない)。
+    pub fn empty() -> Self {
+        Self {
+            dialects: Vec::new(),
+        }
+    }
+
+    /// 組み込みの方言。CEF / LEEF / JSON は形がはっきりしているのでベンダー別より先に、
+    /// どこにでもありうる logfmt は最後に試す。
+    pub fn builtin() -> &'static DialectSet {
+        static BUILTIN: LazyLock<DialectSet> = LazyLock::new(|| {
+            let mut set = DialectSet::empty();
+            set.push(Box::new(Cef));
+            set.push(Box::new(Leef));
+            set.push(Box::new(Json));
+            set.push(Box::new(Cisco));
+            set.push(Box::new(Juniper));
+            set.push(Box::new(FortiGate));
+            set.push(Box::new(PaloAlto));
+            set.push(Box::new(Logfmt));
+            set
+        });
+        &BUILTIN
+    }
+
+    /// 方言を末尾に足す。先に足したものほど優先される。
+    pub fn push(&mut self, dialect: Box<dyn Dialect>) {
+        self.dialects.push(dialect);
+    }
+
+    /// 当てはまる方言を探して `msg` に適用する。適用した方言の名前を返す。
+    pub fn apply(&self, body: &str, msg: &mut SyslogMessage) -> Option<&'static str> {
+        let dialect = self.dialects.iter().find(|d| d.apply(body, msg))?;
+        msg.dialect = Some(dialect.name().to_string());
+        Some(dialect.name())
+    }
+}
@@ -0,0 +1,175 @@
+//! Palo Alto Networks PAN-OS。
+//!
+//! 本文は CSV で、先頭が `FUTURE_USE,受信時刻,シリアル,ログ種別,サブタイプ,…` と決まっている。
+//! 列の並びはログ種別ごとに違うので、よく使う種別(TRAFFIC / THREAT / SYSTEM / CONFIG)は
+//! PAN-OS のドキュメントの項目名で、それ以外や末尾の列は `col<番号>` で入れる。
+
+use super::Dialect;
+use crate::message::SyslogMessage;
+
+pub struct PaloAlto;
+
+/// 4 列目(ログ種別)に入りうる値。
+const LOG_TYPES: &[&str] = &[
+    "TRAFFIC",
+    "THREAT",
+    "SYSTEM",
+    "CONFIG",
+    "HIPMATCH",
+    "GLOBALPROTECT",
+    "USERID",
+    "DECRYPTION",
+    "AUTHENTICATION",
+    "CORRELATION",
+    "TUNNEL",
+    "SCTP",
+    "IPTAG",
+];
+
+/// どの種別でも共通の先頭 7 列("" は FUTURE_USE で捨てる)。
+const COMMON: &[&str] = &[
+    "",
+    "receive_time",
+    "serial",
+    "type",
+    "subtype",
+    "",
+    "time_generated",
+];
+
+/// TRAFFIC / THREAT の 8 列目以降(両者で共通の部分)。
+const TRAFFIC: &[&str] = &[
+    "src",
+    "dst",
+    "natsrc",
+    "natdst",
+    "rule",
+    "srcuser",
+    "dstuser",
+    "app",
+    "vsys",
+    "from",
+    "to",
+    "inbound_if",
+    "outbound_if",
+    "logset",
+    "",
+    "sessionid",
+    "repeatcnt",
+    "sport",
+    "dport",
+    "natsport",
+    "natdport",
+    "flags",
+    "proto",
+    "action",
+];
+
+/// SYSTEM の 8 列目以降。
+const SYSTEM: &[&str] = &[
+    "vsys",
+    "eventid",
+    "object",
+    "",
+    "",
+    "module",
+    "severity",
+    "opaque",
+];
+
+/// CONFIG の 8 列目以降。
+const CONFIG: &[&str] = &[
+    "host",
+    "vsys",
+    "cmd",
+    "admin",
+    "client",
+    "result",
+    "path",
+];
+
+impl Dialect for PaloAlto {
+    fn name(&self) -> &'static str {
+        "paloalto"
+    }
+
+    fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+        let Some(columns) = split_csv(body.trim()) else {
+            return false;
+        };
+        let log_type = match columns.get(3) {
+            Some(t) if LOG_TYPES.contains(&t.as_str()) => t.clone(),
+            _ => return false,
+        };
+        // 2 列目は "2026/10/18 12:00:00" 形式の受信時刻、3 列目は数字のシリアル
+        let looks_like_pan = columns.len() > 6
+            && columns[1].len() == 19
+            && columns[1].as_bytes()[4] == b'/'
+            && !columns[2].is_empty()
+            && columns[2].bytes().all(|b| b.is_ascii_digit());
+        if !looks_like_pan {
+            return false;
+        }
+
+        let rest: &[&str] = match log_type.as_str() {
+            "TRAFFIC" | "THREAT" => TRAFFIC,
+            "SYSTEM" => SYSTEM,
+            "CONFIG" => CONFIG,
+            _ => &[],
+        };
+        let names = COMMON
+            .iter()
+            .chain(rest)
+            .copied()
+            .map(Some)
+            .chain(std::iter::repeat(None));
+        for (i, (value, name)) in columns.into_iter().zip(names).enumerate() {
+            if value.is_empty() || name == Some("") {
+                continue;
+            }
+            let key = name.map_or_else(|| format!("col{}", i + 1), str::to_string);
+            msg.fields.insert(key, value);
+        }
+
+        if let Some(subtype) = msg.fields.get("subtype").cloned() {
+            msg.fields.insert("mnemonic".to_string(), format!("{log_type}/{subtype}"));
+        }
+        if let Some(severity) = msg.fields.get("severity").cloned() {
+            msg.fields.insert("vendor_severity".to_string(), severity);
+        }
+        msg.tag = Some(log_type);
+        // SYSTEM ログは opaque 列が人の読む説明文
+        if let Some(text) = msg.fields.get("opaque") {
+            msg.content = text.clone();
+        } else {
+            msg.content = body.trim().to_string();
+        }
+        true
+    }
+}
+
+/// 1 行の CSV を列に分ける。`"..."` で囲んだ列の中の `,` と `""`(= `"`)を扱う。
+/// 引用符が閉じていなければ None。
+fn split_csv(line: &str) -> Option<Vec<String>> {
+    let mut columns = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if current.is_empty() => quoted = true,
+            ',' if !quoted => columns.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    columns.push(current);
+    Some(columns)
+}
@@ -0,0 +1,180 @@
+//! 送信元ごとの文字コード指定(エンコーディングルール)。
+//!
+//! charset を宣言しない機器の短い Shift_JIS / EUC-JP / GBK は、chardetng の推測だけでは外れやすい。
+//! 送信元 IP / CIDR・ホスト名・タグで機器を特定し、文字コードを決め打ちするか、
+//! 推測のヒント(chardetng の TLD)を与える。
+//!
+//! 設定ファイルでは次のように書く(上にあるルールほど優先。条件を書かない項目は問わない):
+//!
+//! ```toml
+//! [[encoding_rules]]
+//! name = "legacy-switches"
+//! source = "192.0.2.0/24"
+//! encoding = "Shift_JIS"
+//!
+//! [[encoding_rules]]
+//! hostname = "cn-fw01"
+//! tld_hint = "cn"
+//! ```
+//!
+//! メッセージ自身の宣言(RFC 5424 SD の charset・BOM)はルールより優先する。
+
+use std::net::IpAddr;
+
+use encoding_rs::Encoding;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+/// 設定ファイル上のルール 1 件。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodingRule {
+    /// `encoding` ラベルに出す名前。省略時は `#<番号>`(1 始まり)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 送信元 IP アドレスまたは CIDR("192.0.2.10" / "192.0.2.0/24" / "2001:db8::/32")。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// HOSTNAME(大文字小文字を区別しない完全一致)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// TAG / APP-NAME(大文字小文字を区別しない完全一致)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// 決め打ちする文字コード("Shift_JIS"、"EUC-JP"、"GBK" など WHATWG のラベル)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// 推測に使うトップレベルドメインのヒント("jp"、"cn"、"kr"、"tw" など)。
+    /// `encoding` と両方あるときは `encoding` を使う。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tld_hint: Option<String>,
+}
+
+/// ルールが当たったときの文字コードの決め方。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// この文字コードでデコードする。
+    Force(&'static Encoding),
+    /// chardetng にこの TLD をヒントとして渡して推測する。
+    Hint(&'static [u8]),
+}
+
+/// 当たったルール。`label` は `encoding` ラベルに出す名前。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matched<'a> {
+    pub label: &'a str,
+    pub decision: Decision,
+}
+
+struct Compiled {
+    label: String,
+    source: Option<IpNet>,
+    hostname: Option<String>,
+    tag: Option<String>,
+    decision: Decision,
+}
+
+/// 検証済みのルール一覧。
+pub struct EncodingRules {
+    rules: Vec<Compiled>,
+}
+
+impl EncodingRules {
+    /// ルールを 1 件も持たない一覧。
+    pub const fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// 設定のルールを検証して取り込む。CIDR や文字コード名が読めなければ何番目のルールかを添えて Err。
+    pub fn compile(rules: &[EncodingRule]) -> Result<Self, String> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        for (i, rule) in rules.iter().enumerate() {
+            let label = rule.name.clone().unwrap_or_else(|| format!("#{}", i + 1));
+            let source = rule
+                .source
+                .as_deref()
+                .map(|s| {
+                    parse_source(s)
+                        .ok_or_else(|| format!("encoding rule {label}: invalid source {s:?}"))
+                })
+                .transpose()?;
+            let decision = match (&rule.encoding, &rule.tld_hint) {
+                (Some(name), _) => {
+                    Decision::Force(Encoding::for_label(name.trim().as_bytes()).ok_or_else(
+                        || format!("encoding rule {label}: unknown encoding {name:?}"),
+                    )?)
+                }
+                (None, Some(tld)) => Decision::Hint(known_tld(tld).ok_or_else(|| {
+                    format!("encoding rule {label}: unsupported tld_hint {tld:?}")
+                })?),
+                (None, None) => {
+                    return Err(format!("encoding rule {label}: needs encoding or tld_hint"));
+                }
+            };
+            compiled.push(Compiled {
+                label,
+                source,
+                hostname: rule.hostname.as_ref().map(|h| h.to_ascii_lowercase()),
+                tag: rule.tag.as_ref().map(|t| t.to_ascii_lowercase()),
+                decision,
+            });
+        }
+        Ok(Self { rules: compiled })
+    }
+
+    /// ルールが 1 件も無いか。
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 条件をすべて満たす最初のルール。条件に使う値が分からない(None)項目は一致しない扱い。
+    pub fn find(
+        &self,
+        source: Option<IpAddr>,
+        hostname: Option<&str>,
+        tag: Option<&str>,
+    ) -> Option<Matched<'_>> {
+        let eq = |want: &Option<String>, got: Option<&str>| match want {
+            None => true,
+            Some(w) => got.is_some_and(|g| g.eq_ignore_ascii_case(w)),
+        };
+        self.rules
+            .iter()
+            .find(|r| {
+                r.source
+                    .is_none_or(|net| source.is_some_and(|ip| net.contains(&canonical(ip))))
+                    && eq(&r.hostname, hostname)
+                    && eq(&r.tag, tag)
+            })
+            .map(|r| Matched {
+                label: &r.label,
+                decision: r.decision,
+            })
+    }
+}
+
+/// "192.0.2.10" のような単独アドレスも /32(/128)の CIDR として受け付ける。
+fn parse_source(s: &str) -> Option<IpNet> {
+    let s = s.trim();
+    s.parse::<IpNet>()
+        .ok()
+        .or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// デュアルスタックのソケットで受けた IPv4 は "::ffff:192.0.2.10" になるので IPv4 に戻して比べる。
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+/// chardetng がヒントとして意味を持つ TLD。'static なバイト列で返す。
+fn known_tld(tld: &str) -> Option<&'static [u8]> {
+    const TLDS: &[&str] = &[
+        "jp", "cn", "tw", "hk", "kr", "ru", "ua", "by", "bg", "gr", "il", "th", "vn", "tr", "pl",
+        "cz", "sk", "hu", "ro", "de", "fr", "com",
+    ];
+    let tld = tld.trim().trim_start_matches('.').to_ascii_lowercase();
+    TLDS.iter().find(|t| **t == tld).map(|t| t.as_bytes())
+}
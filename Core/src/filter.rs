@@ -0,0 +1,131 @@
+//! GUI の絞り込み欄の解釈。Portable と Console で同じ書き方が使えるようここに置く。
+//!
+//! 空白で区切った語のうち `fields.<key>=<value>` の形のものは [`SyslogMessage::fields`] の
+//! 条件(値の一致、大文字小文字は区別しない)、`listener=<name>` は受けた待ち受け口
+//! ([`SyslogMessage::listener`])の条件、`src=<IP|CIDR|名前>` は送信元
+//! ([`SyslogMessage::source_addr`] / [`SyslogMessage::source_name`])の条件、
+//! 残りは本文・タグ・ホスト名・送信元の名前に含まれる文字列として扱う。
+//! 条件はすべて満たすものだけを残す。
+//!
+//! ```
+//! use vlt_syslog_core::filter::MessageFilter;
+//! use vlt_syslog_core::parse_syslog;
+//!
+//! let msg = parse_syslog(br#"<13>app: @cee: {"user":"alice","msg":"login failed"}"#);
+//! assert!(MessageFilter::parse("fields.user=alice").matches(&msg));
+//! assert!(MessageFilter::parse("failed fields.user=ALICE").matches(&msg));
+//! assert!(!MessageFilter::parse("fields.user=bob").matches(&msg));
+//! ```
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+use crate::message::SyslogMessage;
+
+/// `src=` の条件。
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SourceCond {
+    /// IP アドレス(/32・/128 として)または CIDR。
+    Net(IpNet),
+    /// 送信元の名前に含まれる文字列(小文字化済み)。
+    Name(String),
+}
+
+/// 解釈済みの絞り込み条件。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageFilter {
+    /// 本文・タグ・ホスト名のどれかに含まれるべき文字列(小文字化済み)。空なら条件なし。
+    text: String,
+    /// `fields.<key>=<value>` の (key, 小文字化した value)。
+    fields: Vec<(String, String)>,
+    /// `listener=<name>` の name(小文字化済み)。
+    listener: Option<String>,
+    /// `src=<IP|CIDR|名前>`。
+    source: Option<SourceCond>,
+}
+
+impl MessageFilter {
+    /// 絞り込み欄の文字列を読む。
+    pub fn parse(input: &str) -> Self {
+        let mut text = Vec::new();
+        let mut fields = Vec::new();
+        let mut listener = None;
+        let mut source = None;
+        for word in input.split_whitespace() {
+            if let Some(name) = word.strip_prefix("listener=") {
+                listener = Some(name.to_lowercase());
+                continue;
+            }
+            if let Some(src) = word.strip_prefix("src=") {
+                source = Some(match src.parse::<IpNet>() {
+                    Ok(net) => SourceCond::Net(net),
+                    Err(_) => match src.parse::<IpAddr>() {
+                        Ok(ip) => SourceCond::Net(IpNet::from(ip)),
+                        Err(_) => SourceCond::Name(src.to_lowercase()),
+                    },
+                });
+                continue;
+            }
+            match word
+                .strip_prefix("fields.")
+                .and_then(|cond| cond.split_once('='))
+            {
+                Some((key, value)) if !key.is_empty() => {
+                    fields.push((key.to_string(), value.to_lowercase()));
+                }
+                _ => text.push(word),
+            }
+        }
+        Self {
+            text: text.join(" ").to_lowercase(),
+            fields,
+            listener,
+            source,
+        }
+    }
+
+    /// 条件が何も無いか。
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+            && self.fields.is_empty()
+            && self.listener.is_none()
+            && self.source.is_none()
+    }
+
+    /// メッセージが条件をすべて満たすか。
+    pub fn matches(&self, msg: &SyslogMessage) -> bool {
+        let fields_ok = self.fields.iter().all(|(key, value)| {
+            msg.fields
+                .get(key)
+                .is_some_and(|v| v.to_lowercase() == *value)
+        });
+        if !fields_ok {
+            return false;
+        }
+        if let Some(name) = &self.listener
+            && msg.listener.as_deref().map(str::to_lowercase).as_ref() != Some(name)
+        {
+            return false;
+        }
+        let source_ok = match &self.source {
+            None => true,
+            Some(SourceCond::Net(net)) => msg
+                .source_addr
+                .is_some_and(|ip| net.contains(&ip.to_canonical())),
+            Some(SourceCond::Name(name)) => msg
+                .source_name
+                .as_deref()
+                .is_some_and(|n| n.to_lowercase().contains(name)),
+        };
+        if !source_ok {
+            return false;
+        }
+        let contains = |s: &str| s.to_lowercase().contains(&self.text);
+        self.text.is_empty()
+            || contains(&msg.content)
+            || msg.tag.as_deref().is_some_and(contains)
+            || msg.hostname.as_deref().is_some_and(contains)
+            || msg.source_name.as_deref().is_some_and(contains)
+    }
+}
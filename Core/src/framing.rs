@@ -0,0 +1,176 @@
+//! TCP で届く syslog の区切り(RFC 6587)。
+//!
+//! - Octet Counting(3.4.1): `MSG-LEN SP SYSLOG-MSG`。rsyslog の RFC 5424 転送など。
+//! - Non-Transparent-Framing(3.4.2): 1 メッセージ = 1 行(LF 区切り)。
+//!   多くのファイアウォールや rsyslog `@@` の既定。NUL で区切る送信元
+//!   (Unix ストリームソケットへの `syslog(3)` など)もあるので、NUL も行末とみなす。
+//!
+//! どちらを使うかは接続ごとに最初の 1 バイトで決める(RFC 6587 3.4 の推奨どおり、
+//! 1〜9 なら Octet Counting、それ以外は LF 区切り)。1 本の接続の途中で切り替える送信元は想定しない。
+//!
+//! [`FrameDecoder`] はソケットを持たず、読んだバイト列を渡すと切り出したメッセージを返すだけ。
+//! Server の TCP 受信はこれを使う。
+//!
+//! ```
+//! use vlt_syslog_core::framing::FrameDecoder;
+//!
+//! let mut decoder = FrameDecoder::new(8192);
+//! decoder.extend(b"11 <13>app: hi13 <13>app: bye");
+//! assert_eq!(decoder.next_frame().unwrap().as_deref(), Some(&b"<13>app: hi"[..]));
+//! assert_eq!(decoder.next_frame().unwrap(), None); // 2 件目は 1 バイト足りない
+//! decoder.extend(b"!");
+//! assert_eq!(decoder.next_frame().unwrap().as_deref(), Some(&b"<13>app: bye!"[..]));
+//! ```
+
+/// 接続で使われている区切り方。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    OctetCounting,
+    NonTransparent,
+}
+
+impl Framing {
+    /// ログ用の短い名前。
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Framing::OctetCounting => "octet-counting",
+            Framing::NonTransparent => "lf",
+        }
+    }
+}
+
+/// 区切りを読めなかった。どちらの場合もこの接続の続きは読めないので、受け側は接続を閉じる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// メッセージが上限を超えている(Octet Counting の MSG-LEN、または LF が来ないまま上限に達した)。
+    TooLarge { len: usize, max: usize },
+    /// MSG-LEN が数字でない・0 など。
+    BadLength,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::TooLarge { len, max } => {
+                write!(f, "message of {len} bytes exceeds the limit of {max} bytes")
+            }
+            FrameError::BadLength => f.write_str("invalid octet-counting MSG-LEN"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// MSG-LEN の桁数の上限(上限サイズの指定より先に、数字が延々と続く入力を弾く)。
+const MAX_LEN_DIGITS: usize = 10;
+
+/// 1 本の接続ぶんの受信バッファと区切りの状態。
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    framing: Option<Framing>,
+    max_len: usize,
+}
+
+impl FrameDecoder {
+    /// `max_len` は 1 メッセージ(MSG-LEN / 1 行)の上限バイト数。
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            framing: None,
+            max_len,
+        }
+    }
+
+    /// 判定済みの区切り方。最初のバイトが届くまでは None。
+    pub fn framing(&self) -> Option<Framing> {
+        self.framing
+    }
+
+    /// ソケットから読んだバイト列を足す。
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// 揃っているメッセージを 1 件取り出す。まだ揃っていなければ Ok(None)。
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        // メッセージ間の改行(Octet Counting の後ろに LF を付ける送信元もある)や空行は読み飛ばす
+        let skip = self
+            .buf
+            .iter()
+            .take_while(|b| matches!(b, b'\n' | b'\r' | b'\0'))
+            .count();
+        self.buf.drain(..skip);
+        let Some(&first) = self.buf.first() else {
+            return Ok(None);
+        };
+        let framing = *self.framing.get_or_insert(if (b'1'..=b'9').contains(&first) {
+            Framing::OctetCounting
+        } else {
+            Framing::NonTransparent
+        });
+        match framing {
+            Framing::OctetCounting => self.next_counted(),
+            Framing::NonTransparent => self.next_line(),
+        }
+    }
+
+    /// 接続が閉じたときに残っている分を返す。LF 区切りの最後の行に LF が無い送信元のため。
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        let rest = std::mem::take(&mut self.buf);
+        let rest = rest.trim_ascii_end();
+        let complete = self.framing != Some(Framing::OctetCounting);
+        (complete && !rest.is_empty() && rest.len() <= self.max_len).then(|| rest.to_vec())
+    }
+
+    fn next_counted(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let digits = self.buf.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits > MAX_LEN_DIGITS {
+            return Err(FrameError::BadLength);
+        }
+        match self.buf.get(digits) {
+            None => return Ok(None),
+            Some(b' ') if digits > 0 && self.buf[0] != b'0' => {}
+            Some(_) => return Err(FrameError::BadLength),
+        }
+        let len: usize = std::str::from_utf8(&self.buf[..digits])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(FrameError::BadLength)?;
+        if len > self.max_len {
+            return Err(FrameError::TooLarge {
+                len,
+                max: self.max_len,
+            });
+        }
+        let start = digits + 1;
+        if self.buf.len() < start + len {
+            return Ok(None);
+        }
+        let frame = self.buf[start..start + len].to_vec();
+        self.buf.drain(..start + len);
+        Ok(Some(frame))
+    }
+
+    fn next_line(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let Some(end) = self.buf.iter().position(|&b| b == b'\n' || b == b'\0') else {
+            if self.buf.len() > self.max_len {
+                return Err(FrameError::TooLarge {
+                    len: self.buf.len(),
+                    max: self.max_len,
+                });
+            }
+            return Ok(None);
+        };
+        let mut line: Vec<u8> = self.buf.drain(..=end).collect();
+        if line.pop() == Some(b'\n') && line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.len() > self.max_len {
+            return Err(FrameError::TooLarge {
+                len: line.len(),
+                max: self.max_len,
+            });
+        }
+        Ok(Some(line))
+    }
+}
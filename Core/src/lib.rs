@@ -0,0 +1,50 @@
+//! vlt-syslogd の共通ロジック。
+//!
+//! Server(常駐サービス)・Portable(単体 GUI)・Console(サービスに接続する GUI)の 3 つが
+//! 同じ規則で syslog をデコードするよう、パーサと受信メッセージの型をここに一元化する。
+//! 社内ツールなど外部から syslog を扱う場合もこのクレートを使えばよい。
+//!
+//! - [`parse_syslog`] : 受信したバイト列を [`SyslogMessage`] にする(RFC 5424 / RFC 3164 自動判別)
+//! - [`acl`]          : 送信元 IP / CIDR による受け入れ・拒否(パースの前に判定する)
+//! - [`capture`]      : パケットキャプチャ(pcap / pcapng)から UDP / TCP の syslog を取り出す
+//! - [`diagnostic`]   : パース時に救済した規格外の箇所(種類とバイト位置)
+//! - [`dialect`]      : CEF / LEEF や Cisco / Juniper / FortiGate / Palo Alto など、本文の形式ごとの解釈
+//! - [`encoding`]     : 送信元ごとの文字コード指定(決め打ち・推測のヒント)
+//! - [`framing`]      : TCP で届く syslog の区切り(RFC 6587 の Octet Counting / LF)
+//! - [`listen`]       : 受信ソケットの作成(`[::]` はデュアルスタックで開く)
+//! - [`filter`]       : GUI の絞り込み欄の書式(`fields.user=alice` など)
+//! - [`message`]      : [`SyslogMessage`] と [`Severity`] / [`Facility`] / [`SdElement`] / [`PeerCred`]
+//! - [`names`]        : 送信元 IP の名前(hosts ファイル・逆引き DNS)
+//! - [`proxy`]        : HAProxy の PROXY プロトコル(v1 / v2)のヘッダ(ロードバランサ越しの送信元)
+//! - [`relp`]         : RELP(rsyslog の `omrelp`)のフレームと応答
+//! - [`stream`]       : Server が GUI へ配信する JSON Lines の入出力と既定アドレス
+//! - [`time`]         : 受信時刻・機器時刻を表示するときのタイムゾーン
+//!
+//! ```
+//! use vlt_syslog_core::{parse_syslog, Facility};
+//!
+//! let msg = parse_syslog(b"<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed");
+//! assert_eq!(msg.facility, Some(Facility::Auth));
+//! assert_eq!(msg.hostname.as_deref(), Some("mymachine"));
+//! assert_eq!(msg.tag.as_deref(), Some("su"));
+//! ```
+
+pub mod acl;
+pub mod capture;
+pub mod diagnostic;
+pub mod dialect;
+pub mod encoding;
+pub mod filter;
+pub mod framing;
+pub mod listen;
+pub mod message;
+pub mod names;
+pub mod parser;
+pub mod proxy;
+pub mod relp;
+pub mod stream;
+pub mod time;
+
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use message::{Facility, PeerCred, SdElement, Severity, SyslogMessage};
+pub use parser::{ParseOptions, parse_syslog, parse_syslog_at, parse_syslog_with};
@@ -0,0 +1,143 @@
+//! 受信ソケットの作成(Server・Portable 共通)。
+//!
+//! `[::]:514` のような IPv6 の未指定アドレスは、IPv4 からの送信も受けるデュアルスタックで開く。
+//! OS の既定(Windows は IPv6 のみ、Linux は `net.ipv6.bindv6only` 次第)に頼らず明示する。
+//! IPv6 が使えない環境では `[::]` を `0.0.0.0` に読み替える。
+//!
+//! 返すソケットはノンブロッキングにしてあるので、`tokio::net::UdpSocket::from_std` 等でそのまま使える。
+//!
+//! データグラムの受信には [`DatagramBuf`] を使う。上限を超えたものを黙って切り詰めず、
+//! 切り詰めたことを受信側で分かるようにする。
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// TCP の接続待ちキューの長さ。
+const TCP_BACKLOG: i32 = 1024;
+
+/// "host:port" を解決して最初のアドレスを返す。
+pub fn resolve(addr: &str) -> io::Result<SocketAddr> {
+    addr.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no address for {addr}"),
+        )
+    })
+}
+
+/// SO_REUSEPORT で同じアドレスに複数のソケットを bind でき、カーネルが受信を振り分けるか。
+pub const REUSE_PORT_SUPPORTED: bool = cfg!(unix);
+
+/// 待ち受けソケットのオプション(今のところ UDP でだけ使う)。
+#[derive(Debug, Clone, Default)]
+pub struct SocketOptions {
+    /// SO_REUSEPORT を立てる。同じアドレスに bind した複数のソケットで受信を分け合う
+    /// ([`REUSE_PORT_SUPPORTED`] でない OS では無視する)。
+    pub reuse_port: bool,
+    /// SO_RCVBUF(カーネルの受信バッファ)のバイト数。None なら OS の既定。
+    /// OS の上限(Linux の `net.core.rmem_max` 等)で頭打ちになることがある。
+    pub recv_buffer_size: Option<usize>,
+}
+
+/// UDP で待ち受けるソケットを開く。
+pub fn udp_socket(addr: &str) -> io::Result<std::net::UdpSocket> {
+    udp_socket_with(addr, &SocketOptions::default())
+}
+
+/// オプションを指定して UDP で待ち受けるソケットを開く。
+pub fn udp_socket_with(addr: &str, opts: &SocketOptions) -> io::Result<std::net::UdpSocket> {
+    let socket = bind(resolve(addr)?, Type::DGRAM, Protocol::UDP, opts)?;
+    Ok(socket.into())
+}
+
+/// 実際に効いている SO_RCVBUF のバイト数(Linux は指定の 2 倍を返す)。
+pub fn recv_buffer_size(socket: &std::net::UdpSocket) -> io::Result<usize> {
+    socket2::SockRef::from(socket).recv_buffer_size()
+}
+
+/// TCP で待ち受けるソケットを開く。
+pub fn tcp_listener(addr: &str) -> io::Result<std::net::TcpListener> {
+    let socket = bind(
+        resolve(addr)?,
+        Type::STREAM,
+        Protocol::TCP,
+        &SocketOptions::default(),
+    )?;
+    socket.listen(TCP_BACKLOG)?;
+    Ok(socket.into())
+}
+
+fn bind(
+    addr: SocketAddr,
+    ty: Type,
+    protocol: Protocol,
+    opts: &SocketOptions,
+) -> io::Result<Socket> {
+    let socket = match Socket::new(Domain::for_address(addr), ty, Some(protocol)) {
+        Ok(socket) => socket,
+        // IPv6 の無い環境で [::] を指定された場合は IPv4 の全アドレスで受ける
+        Err(_) if addr.is_ipv6() && addr.ip().is_unspecified() => {
+            let v4 = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), addr.port());
+            return bind(v4, ty, protocol, opts);
+        }
+        Err(e) => return Err(e),
+    };
+    if addr.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    // 再起動直後に TIME_WAIT の接続が残っていても bind できるようにする(tokio の bind と同じ)
+    #[cfg(not(windows))]
+    if ty == Type::STREAM {
+        socket.set_reuse_address(true)?;
+    }
+    #[cfg(unix)]
+    if opts.reuse_port {
+        socket.set_reuse_port(true)?;
+    }
+    if let Some(size) = opts.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    Ok(socket)
+}
+
+/// データグラムの受信バッファ。上限より 1 バイト多く確保して recv し、
+/// 上限を超えて届いたものを切り詰めたと判別する(OS は収まらない分を黙って捨てるため)。
+pub struct DatagramBuf {
+    buf: Vec<u8>,
+    max_len: usize,
+}
+
+impl DatagramBuf {
+    /// `max_len` は 1 メッセージの上限バイト数。
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            buf: vec![0; max_len + 1],
+            max_len,
+        }
+    }
+
+    /// recv の受け先。
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+
+    /// recv が返した長さ `len` の受信内容を、上限までに切り詰めて返す。2 つめは切り詰めたかどうか。
+    pub fn received(&self, len: usize) -> (&[u8], bool) {
+        let len = len.min(self.buf.len());
+        if len > self.max_len {
+            (&self.buf[..self.max_len], true)
+        } else {
+            (&self.buf[..len], false)
+        }
+    }
+}
+
+/// 受信バッファより大きいデータグラムで recv が失敗したか。
+/// Windows は切り詰めたうえで WSAEMSGSIZE を返し、送信元アドレスも取れない。他の OS では起きない。
+pub fn is_oversized_datagram(e: &io::Error) -> bool {
+    cfg!(windows) && e.raw_os_error() == Some(10040)
+}
@@ -0,0 +1,375 @@
+//! 受信した syslog メッセージ 1 件を表す型と、その構成要素(Severity / Facility / SD)。
+//!
+//! ここにある型はそのまま JSON Lines の配信ストリーム(`crate::stream`)に載る。
+//! フィールドを追加するときは、古い送信側の JSON でも読めるよう `#[serde(default)]` を付けること。
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
+
+use crate::diagnostic::Diagnostic;
+
+/// RFC 5424 表 2 の severity(PRIVAL % 8)。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Severity {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+impl Severity {
+    /// PRIVAL から severity を取り出す。
+    pub fn from_pri(pri: u8) -> Self {
+        match pri % 8 {
+            0 => Severity::Emergency,
+            1 => Severity::Alert,
+            2 => Severity::Critical,
+            3 => Severity::Error,
+            4 => Severity::Warning,
+            5 => Severity::Notice,
+            6 => Severity::Informational,
+            7 => Severity::Debug,
+            _ => Severity::Informational,
+        }
+    }
+
+    /// 重大度ごとの表示色(RGB)。Portable / Console で同じ配色にするためここに置く。
+    pub fn color(&self) -> (u8, u8, u8) {
+        match self {
+            Severity::Emergency | Severity::Alert | Severity::Critical => (255, 100, 100), // Red
+            Severity::Error => (255, 120, 120),   // Light Red
+            Severity::Warning => (255, 200, 100), // Orange/Yellow
+            Severity::Notice | Severity::Informational => (150, 255, 150), // Green
+            Severity::Debug => (180, 180, 255),   // Light Blue
+        }
+    }
+}
+
+/// RFC 5424 表 1 の facility(PRIVAL / 8)。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Facility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Ntp = 12,
+    Audit = 13,
+    Alert = 14,
+    Clock = 15,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+impl Facility {
+    /// 全 facility(コード順)。GUI のフィルタ候補に使う。
+    pub const ALL: [Facility; 24] = [
+        Facility::Kern,
+        Facility::User,
+        Facility::Mail,
+        Facility::Daemon,
+        Facility::Auth,
+        Facility::Syslog,
+        Facility::Lpr,
+        Facility::News,
+        Facility::Uucp,
+        Facility::Cron,
+        Facility::Authpriv,
+        Facility::Ftp,
+        Facility::Ntp,
+        Facility::Audit,
+        Facility::Alert,
+        Facility::Clock,
+        Facility::Local0,
+        Facility::Local1,
+        Facility::Local2,
+        Facility::Local3,
+        Facility::Local4,
+        Facility::Local5,
+        Facility::Local6,
+        Facility::Local7,
+    ];
+
+    /// PRIVAL から facility を取り出す。PRIVAL が 192 以上(facility 24 以上)なら None。
+    pub fn from_pri(pri: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(pri / 8)).copied()
+    }
+
+    /// syslog.conf 等で使われる小文字の名前(kern / authpriv / local7 ...)。
+    pub fn name(&self) -> &'static str {
+        match self {
+            Facility::Kern => "kern",
+            Facility::User => "user",
+            Facility::Mail => "mail",
+            Facility::Daemon => "daemon",
+            Facility::Auth => "auth",
+            Facility::Syslog => "syslog",
+            Facility::Lpr => "lpr",
+            Facility::News => "news",
+            Facility::Uucp => "uucp",
+            Facility::Cron => "cron",
+            Facility::Authpriv => "authpriv",
+            Facility::Ftp => "ftp",
+            Facility::Ntp => "ntp",
+            Facility::Audit => "audit",
+            Facility::Alert => "alert",
+            Facility::Clock => "clock",
+            Facility::Local0 => "local0",
+            Facility::Local1 => "local1",
+            Facility::Local2 => "local2",
+            Facility::Local3 => "local3",
+            Facility::Local4 => "local4",
+            Facility::Local5 => "local5",
+            Facility::Local6 => "local6",
+            Facility::Local7 => "local7",
+        }
+    }
+}
+
+/// STRUCTURED-DATA の SD-ELEMENT 1 つ(`[SD-ID PARAM-NAME="PARAM-VALUE" ...]`)。
+/// PARAM-VALUE のエスケープ(\" \\ \])は復元済み。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SdElement {
+    /// SD-ID(例 "origin" / "exampleSDID@32473")。
+    pub id: String,
+    /// (PARAM-NAME, PARAM-VALUE) の列。同名の PARAM が複数あってもよいので出現順に持つ。
+    pub params: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for SdElement {
+    /// RFC 5424 の表記(エスケープし直した `[id k="v"]`)で書き出す。
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}", self.id)?;
+        for (name, value) in &self.params {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace(']', "\\]");
+            write!(f, " {}=\"{}\"", name, escaped)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Unix ドメインソケットで送ってきたプロセスの資格情報(SO_PASSCRED / SO_PEERCRED)。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PeerCred {
+    /// 送信元のプロセス ID。OS が教えない場合(一部のストリームソケット)は None。
+    pub pid: Option<i32>,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl std::fmt::Display for PeerCred {
+    /// "pid=123,uid=0,gid=0" の形(pid が分からなければ省く)。
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(pid) = self.pid {
+            write!(f, "pid={pid},")?;
+        }
+        write!(f, "uid={},gid={}", self.uid, self.gid)
+    }
+}
+
+/// パース済みの syslog メッセージ 1 件。
+///
+/// RFC 5424 / RFC 3164 のどちらから来ても同じ形にそろえる。
+/// 該当する項目が無い形式(RFC 3164 の MSGID など)は None / 空になる。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SyslogMessage {
+    /// PRI から取り出した severity。PRI が無い・規格外なら Informational。
+    pub severity: Severity,
+    /// PRI から取り出した facility。PRI が無い・規格外なら None。
+    #[serde(default)]
+    pub facility: Option<Facility>,
+    /// PRI らしき "<...>" はあったが値が規格外(192 以上など)だったか。
+    #[serde(default)]
+    pub invalid_pri: bool,
+    /// 受信(パース)した時刻。UTC で持ち、JSON では RFC 3339 になる。
+    /// 表示するときは [`crate::time::DisplayZone`] で利用者のタイムゾーンに直す。
+    /// 旧バージョンの `timestamp`(オフセット無しのローカル時刻)も読めるようにしてある。
+    #[serde(alias = "timestamp", deserialize_with = "de_received_at")]
+    pub received_at: DateTime<Utc>,
+    /// 送信元機器が付けた時刻。RFC 5424 TIMESTAMP はオフセットごと、
+    /// RFC 3164 の BSD 形式は受信側のローカル時刻・受信時刻に近い年とみなして解釈したもの。
+    /// NILVALUE や規格外の値なら None。
+    #[serde(default, deserialize_with = "de_device_timestamp")]
+    pub device_timestamp: Option<DateTime<FixedOffset>>,
+    /// RFC 5424 HOSTNAME / RFC 3164 HOSTNAME。
+    pub hostname: Option<String>,
+    /// RFC 5424 APP-NAME。
+    #[serde(default)]
+    pub app_name: Option<String>,
+    /// RFC 5424 PROCID。
+    #[serde(default)]
+    pub procid: Option<String>,
+    /// RFC 5424 MSGID。
+    #[serde(default)]
+    pub msgid: Option<String>,
+    /// RFC 5424 の STRUCTURED-DATA。NILVALUE や RFC 3164 なら空。
+    #[serde(default)]
+    pub structured_data: Vec<SdElement>,
+    /// 表示・フィルタ用のタグ。RFC 3164 は TAG、RFC 5424 は APP-NAME を入れる。
+    pub tag: Option<String>,
+    /// RFC 3164 の TAG[PID] の PID。
+    #[serde(default)]
+    pub pid: Option<String>,
+    /// MSG 本文(判定したエンコーディングでデコード済み)。
+    pub content: String,
+    /// 本文を解釈できたベンダー方言の名前(例 "cisco")。どれにも当てはまらなければ None。
+    #[serde(default)]
+    pub dialect: Option<String>,
+    /// 方言が本文から取り出した項目。共通のキーは [`crate::dialect`] を参照。
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    /// 受信した生バイト列。JSON では `raw_b64`(base64)に入れ、空なら省く。
+    /// 16 進で見せたいときは [`SyslogMessage::raw_hex`] を使う。
+    #[serde(
+        rename = "raw_b64",
+        with = "raw_base64",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub raw: Vec<u8>,
+    /// 本文のデコードに使ったエンコーディングと判定根拠(例 "Shift_JIS (MSG-SD/BOM-Missing)")。
+    pub encoding: String,
+    /// パース時に救済した規格外の箇所。[`crate::diagnostic`] を参照。
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// strict モードで規格に沿っていないと判定したか(診断が 1 件以上)。
+    #[serde(default)]
+    pub nonconforming: bool,
+    /// TLS で受けた接続のクライアント証明書の Subject(例 "CN=fw01, O=Example")。
+    /// パーサは設定せず、受信側(Server の TLS 受信)が入れる。
+    #[serde(default)]
+    pub tls_subject: Option<String>,
+    /// 受信した待ち受け口の名前(設定の label、無ければ "udp://[::]:514" の形)。
+    /// パーサは設定せず、受信側が入れる。
+    #[serde(default)]
+    pub listener: Option<String>,
+    /// Unix ドメインソケットで受けたときの送信元プロセスの資格情報。
+    /// パーサは設定せず、受信側(Server の Unix ソケット受信)が入れる。
+    #[serde(default)]
+    pub peer_cred: Option<PeerCred>,
+    /// 受信バッファの上限を超えたデータグラムで、上限までで切り詰めたもの。
+    /// パーサは設定せず、受信側が入れる。`raw` も切り詰めた後のバイト列。
+    #[serde(default)]
+    pub truncated: bool,
+    /// 送信元の IP アドレス。Unix ドメインソケットなど IP の無い受信では None。
+    /// パーサは設定せず、受信側が入れる。
+    #[serde(default)]
+    pub source_addr: Option<IpAddr>,
+    /// 送信元の名前(hosts ファイルか逆引き DNS、[`crate::names`])。引けなければ None。
+    /// パーサは設定せず、受信側が入れる。
+    #[serde(default)]
+    pub source_name: Option<String>,
+}
+
+impl SyslogMessage {
+    /// `fields` を logfmt の 1 行(`key=value key2="with space"`)にする。ログファイルへの書き出し用。
+    pub fn fields_logfmt(&self) -> String {
+        crate::dialect::kv::format_kv(&self.fields)
+    }
+
+    /// 生バイト列の 16 進表記(小文字)。GUI の "Copy as Hex" 用に、必要になったときだけ作る。
+    pub fn raw_hex(&self) -> String {
+        hex::encode(&self.raw)
+    }
+
+    /// vlt-syslogd 自身が生成するメッセージ(起動・bind 失敗などの内部ステータス)を作る。
+    /// facility は RFC 5424 で syslogd 内部用とされる `syslog` にする。
+    pub fn internal(severity: Severity, tag: &str, content: String) -> Self {
+        Self {
+            severity,
+            facility: Some(Facility::Syslog),
+            invalid_pri: false,
+            received_at: Utc::now(),
+            device_timestamp: None,
+            hostname: None,
+            app_name: None,
+            procid: None,
+            msgid: None,
+            structured_data: Vec::new(),
+            tag: Some(tag.to_string()),
+            pid: None,
+            content,
+            dialect: None,
+            fields: BTreeMap::new(),
+            raw: Vec::new(),
+            encoding: "system".to_string(),
+            diagnostics: Vec::new(),
+            nonconforming: false,
+            tls_subject: None,
+            listener: None,
+            peer_cred: None,
+            truncated: false,
+            source_addr: None,
+            source_name: None,
+        }
+    }
+
+    /// 送信元の表示名。名前が引けていればそれ、無ければ IP アドレス。
+    pub fn source_display(&self) -> Option<String> {
+        self.source_name
+            .clone()
+            .or_else(|| self.source_addr.map(|ip| ip.to_string()))
+    }
+}
+
+/// `received_at` の読み込み。RFC 3339 のほか、旧バージョンの `timestamp`
+/// ("2026-06-29T00:00:00.000" のようなオフセット無しのローカル時刻)も受け付ける。
+fn de_received_at<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+    let s = String::deserialize(d)?;
+    if let Ok(t) = DateTime::parse_from_rfc3339(&s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid received_at: {s:?}")))
+}
+
+/// `device_timestamp` の読み込み。解釈できない値(旧バージョンが送っていた BSD 形式の
+/// 表記そのままなど)はメッセージごと捨てずに None にする。
+fn de_device_timestamp<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    let s = Option::<String>::deserialize(d)?;
+    Ok(s.and_then(|s| DateTime::parse_from_rfc3339(&s).ok()))
+}
+
+/// `raw` の base64(標準・パディング付き)表記。
+mod raw_base64 {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(raw: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&STANDARD.encode(raw))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        STANDARD.decode(s).map_err(serde::de::Error::custom)
+    }
+}
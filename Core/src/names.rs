@@ -0,0 +1,230 @@
+//! 送信元 IP から名前を引く(hosts ファイル・逆引き DNS・TTL 付きキャッシュ)。
+//!
+//! 結果は [`SyslogMessage::source_name`](crate::SyslogMessage::source_name) に入る。
+//! hosts ファイル(`/etc/hosts` と同じ「IP 名前 [別名...]」の書式)にある送信元はその名前を使い、
+//! DNS より優先する。逆引きは受信ループを止めないよう別スレッドで行い、
+//! 結果がキャッシュに入るまでの間に届いたメッセージは名前無しのままにする。
+//!
+//! 設定ファイルでは次のように書く(Server は `[server.resolve]`、Portable は `[resolve]`):
+//!
+//! ```toml
+//! [server.resolve]
+//! reverse_dns = true
+//! ttl_secs = 300
+//! hosts_file = "/etc/vlt-syslogd/hosts"
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// キャッシュに持つ送信元の上限。超えたら期限切れのものを捨て、それでも多ければ全部捨てる。
+const MAX_CACHED: usize = 4096;
+/// 逆引き待ちの上限。溢れた分はその回は引かない(次に届いたときにまた頼む)。
+const QUEUE_LEN: usize = 1024;
+
+/// 名前解決の設定。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResolveConfig {
+    /// 送信元を DNS で逆引きする。
+    pub reverse_dns: bool,
+    /// 逆引きの結果(引けなかったことも含む)を覚えておく秒数。
+    pub ttl_secs: u64,
+    /// 送信元の名前を決め打ちする hosts ファイル。DNS より優先する。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts_file: Option<String>,
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        Self {
+            reverse_dns: false,
+            ttl_secs: 300,
+            hosts_file: None,
+        }
+    }
+}
+
+/// hosts ファイルの書式を読む。`#` 以降は注釈。1 つの IP に複数の名前があれば最初のものを使う。
+/// IP として読めない行は飛ばす。
+pub fn parse_hosts(text: &str) -> HashMap<IpAddr, String> {
+    let mut hosts = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let (Some(ip), Some(name)) = (words.next(), words.next()) else {
+            continue;
+        };
+        if let Ok(ip) = ip.parse::<IpAddr>() {
+            hosts
+                .entry(ip.to_canonical())
+                .or_insert_with(|| name.to_string());
+        }
+    }
+    hosts
+}
+
+/// hosts ファイルを読み込む。
+pub fn load_hosts(path: impl AsRef<Path>) -> std::io::Result<HashMap<IpAddr, String>> {
+    Ok(parse_hosts(&std::fs::read_to_string(path)?))
+}
+
+/// DNS で逆引きする(OS のリゾルバ。応答まで待つ)。名前が無ければ None。
+pub fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    let addr = socket2::SockAddr::from(SocketAddr::new(ip, 0));
+    let mut host = [0u8; 1025];
+    let ret = getnameinfo(&addr, &mut host);
+    if ret != 0 {
+        return None;
+    }
+    let len = host.iter().position(|b| *b == 0).unwrap_or(host.len());
+    let name = String::from_utf8_lossy(&host[..len]).into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(unix)]
+fn getnameinfo(addr: &socket2::SockAddr, host: &mut [u8]) -> i32 {
+    // SAFETY: addr は有効なソケットアドレスで、host はその長さぶん書き込める
+    unsafe {
+        libc::getnameinfo(
+            addr.as_ptr().cast(),
+            addr.len(),
+            host.as_mut_ptr().cast(),
+            host.len() as libc::socklen_t,
+            std::ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    }
+}
+
+#[cfg(windows)]
+fn getnameinfo(addr: &socket2::SockAddr, host: &mut [u8]) -> i32 {
+    use std::ffi::{c_char, c_void};
+
+    /// ws2tcpip.h の NI_NAMEREQD。
+    const NI_NAMEREQD: i32 = 0x04;
+    #[link(name = "ws2_32")]
+    unsafe extern "system" {
+        fn getnameinfo(
+            sa: *const c_void,
+            salen: i32,
+            host: *mut c_char,
+            hostlen: u32,
+            serv: *mut c_char,
+            servlen: u32,
+            flags: i32,
+        ) -> i32;
+    }
+    // SAFETY: addr は有効なソケットアドレスで、host はその長さぶん書き込める。
+    // Winsock はソケットを作った時点で std が初期化している
+    unsafe {
+        getnameinfo(
+            addr.as_ptr().cast(),
+            addr.len(),
+            host.as_mut_ptr().cast(),
+            host.len() as u32,
+            std::ptr::null_mut(),
+            0,
+            NI_NAMEREQD,
+        )
+    }
+}
+
+struct Cached {
+    name: Option<String>,
+    expires: Instant,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<IpAddr, Cached>,
+    /// 逆引きを頼んで、まだ結果の無い送信元。
+    pending: HashSet<IpAddr>,
+}
+
+impl Cache {
+    fn insert(&mut self, ip: IpAddr, name: Option<String>, expires: Instant) {
+        if self.entries.len() >= MAX_CACHED && !self.entries.contains_key(&ip) {
+            let now = Instant::now();
+            self.entries.retain(|_, c| c.expires > now);
+            if self.entries.len() >= MAX_CACHED {
+                self.entries.clear();
+            }
+        }
+        self.entries.insert(ip, Cached { name, expires });
+    }
+}
+
+/// 送信元の名前を返す。逆引きは裏のスレッドで行う。
+pub struct Resolver {
+    hosts: HashMap<IpAddr, String>,
+    cache: Arc<Mutex<Cache>>,
+    /// 逆引きスレッドへの依頼。reverse_dns が無効なら None。
+    queue: Option<SyncSender<IpAddr>>,
+}
+
+impl Resolver {
+    /// `hosts` は [`load_hosts`] で読んだもの。`reverse_dns` なら逆引きスレッドを立てる
+    /// (Resolver を捨てるとスレッドも終わる)。
+    pub fn new(hosts: HashMap<IpAddr, String>, reverse_dns: bool, ttl: Duration) -> Self {
+        let cache = Arc::new(Mutex::new(Cache::default()));
+        let queue = reverse_dns.then(|| {
+            let (tx, rx) = mpsc::sync_channel::<IpAddr>(QUEUE_LEN);
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || {
+                for ip in rx {
+                    let name = reverse_lookup(ip);
+                    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+                    cache.pending.remove(&ip);
+                    cache.insert(ip, name, Instant::now() + ttl);
+                }
+            });
+            tx
+        });
+        Self {
+            hosts,
+            cache,
+            queue,
+        }
+    }
+
+    /// 設定から作る。hosts ファイルが読めなければ Err(逆引きだけで動かすかは呼び出し側が決める)。
+    pub fn from_config(config: &ResolveConfig) -> Result<Self, String> {
+        let hosts = match &config.hosts_file {
+            Some(path) => load_hosts(path).map_err(|e| format!("hosts_file {path}: {e}"))?,
+            None => HashMap::new(),
+        };
+        Ok(Self::new(
+            hosts,
+            config.reverse_dns,
+            Duration::from_secs(config.ttl_secs),
+        ))
+    }
+
+    /// `ip` の名前。hosts ファイルにあればそれ、無ければキャッシュ済みの逆引き結果。
+    /// キャッシュに無い(期限切れの)送信元は逆引きを頼み、今回は None を返す。
+    pub fn name(&self, ip: IpAddr) -> Option<String> {
+        let ip = ip.to_canonical();
+        if let Some(name) = self.hosts.get(&ip) {
+            return Some(name.clone());
+        }
+        let queue = self.queue.as_ref()?;
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.entries.get(&ip)
+            && cached.expires > Instant::now()
+        {
+            return cached.name.clone();
+        }
+        if cache.pending.insert(ip) && queue.try_send(ip).is_err() {
+            cache.pending.remove(&ip);
+        }
+        None
+    }
+}
@@ -0,0 +1,789 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::dialect::DialectSet;
+use crate::encoding::{Decision, EncodingRules};
+use crate::message::{Facility, SdElement, Severity, SyslogMessage};
+
+/// 受信した 1 メッセージ分のバイト列をパースする。
+///
+/// RFC 5424(SD/charset 対応)と RFC 3164(BSD ヘッダー)を自動判別する。
+/// どちらの形にも合わない入力でも失敗はせず、本文をそのまま `content` に入れて返す。
+/// 受信時刻(`received_at`)には現在時刻を入れる。
+pub fn parse_syslog(bytes: &[u8]) -> SyslogMessage {
+    parse_syslog_at(bytes, Utc::now())
+}
+
+/// [`parse_syslog`] の受信時刻を指定できる版。キャプチャの取り込みやテストで使う。
+///
+/// 年を持たない RFC 3164 の時刻は、`received_at` に最も近くなる年で解釈する。
+pub fn parse_syslog_at(bytes: &[u8], received_at: DateTime<Utc>) -> SyslogMessage {
+    parse_syslog_with(
+        bytes,
+        &ParseOptions {
+            received_at,
+            ..ParseOptions::default()
+        },
+    )
+}
+
+/// 受信したときの文脈(時刻・送信元)と、本文の解釈に使う設定。
+pub struct ParseOptions<'a> {
+    /// 受信時刻。
+    pub received_at: DateTime<Utc>,
+    /// 送信元アドレス。エンコーディングルールの照合に使う。
+    pub source: Option<IpAddr>,
+    /// 本文の解釈に使う方言。社内独自の形式を足したいときは
+    /// [`DialectSet`] に [`crate::dialect::Dialect`] を追加して渡す。
+    pub dialects: &'a DialectSet,
+    /// 送信元ごとの文字コード指定。
+    pub encoding_rules: &'a EncodingRules,
+    /// 規格に沿っていない箇所も診断に残し、診断のあるメッセージに `nonconforming` を付ける。
+    pub strict: bool,
+}
+
+impl Default for ParseOptions<'static> {
+    /// 現在時刻・送信元不明・組み込みの方言・エンコーディングルール無し・strict 無し。
+    fn default() -> Self {
+        static NO_RULES: EncodingRules = EncodingRules::empty();
+        Self {
+            received_at: Utc::now(),
+            source: None,
+            dialects: DialectSet::builtin(),
+            encoding_rules: &NO_RULES,
+            strict: false,
+        }
+    }
+}
+
+/// [`parse_syslog`] の、受信時の文脈と設定を指定できる版。Server / Portable の受信経路はこれを使う。
+pub fn parse_syslog_with(bytes: &[u8], opts: &ParseOptions<'_>) -> SyslogMessage {
+    let (mut msg, body) = parse_frame(bytes, opts);
+    opts.dialects.apply(&body, &mut msg);
+    msg
+}
+
+/// PRI・HEADER・SD・MSG を読む(方言の解釈はしない)。
+/// 戻り値の 2 つ目は TAG を推測で切り出す前の MSG 全体で、方言にはこちらを渡す。
+fn parse_frame(bytes: &[u8], opts: &ParseOptions<'_>) -> (SyslogMessage, String) {
+    let received_at = opts.received_at;
+    let mut cursor = 0;
+    let mut severity = Severity::Informational;
+    let mut tag: Option<String> = None;
+    let mut diags = Vec::new();
+
+    // 1. PRI パース (<PRI>)。PRIVAL は 0〜191 のみ有効
+    let mut facility = None;
+    let mut invalid_pri = false;
+    match parse_pri(bytes) {
+        Some((pri_len, pri)) => {
+            match pri {
+                Some(pri) => {
+                    severity = Severity::from_pri(pri);
+                    facility = Facility::from_pri(pri);
+                }
+                None => {
+                    invalid_pri = true;
+                    let value = String::from_utf8_lossy(&bytes[1..pri_len - 1]);
+                    diags.push(Diagnostic::new(
+                        DiagnosticKind::InvalidPri,
+                        0,
+                        format!("PRIVAL {value}"),
+                    ));
+                }
+            }
+            cursor = pri_len;
+        }
+        None if bytes.starts_with(b"<") => {
+            diags.push(Diagnostic::new(DiagnosticKind::InvalidPri, 0, ""));
+        }
+        None => diags.push(Diagnostic::new(DiagnosticKind::MissingPri, 0, "")),
+    }
+
+    // 2. RFC 5424 VERSION チェック
+    // VERSION は NONZERO-DIGIT 0*2DIGIT の直後に SP。Cisco の "000123:" や
+    // Palo Alto の "1,2026/…" のような数字始まりの本文を RFC 5424 と取り違えない。
+    let version_len = count_digits(&bytes[cursor..]);
+    let is_rfc5424 = (1..=3).contains(&version_len)
+        && bytes[cursor] != b'0'
+        && bytes.get(cursor + version_len) == Some(&b' ');
+    if is_rfc5424 {
+        if &bytes[cursor..cursor + version_len] != b"1" {
+            let version = String::from_utf8_lossy(&bytes[cursor..cursor + version_len]);
+            diags.push(Diagnostic::new(
+                DiagnosticKind::InvalidVersion,
+                cursor,
+                format!("VERSION {version}"),
+            ));
+        }
+        cursor += version_len + 1;
+    } else if (1..=3).contains(&version_len) && bytes.get(cursor + version_len) == Some(&b' ') {
+        // "0 " や "01 " は VERSION のつもりでも受け付けない(本文として扱う)
+        let version = String::from_utf8_lossy(&bytes[cursor..cursor + version_len]);
+        diags.push(Diagnostic::new(
+            DiagnosticKind::InvalidVersion,
+            cursor,
+            format!("VERSION {version}"),
+        ));
+    }
+
+    // RFC 3164 の HEADER(BSD タイムスタンプ + ホスト名)と TAG[PID]。
+    // 見つからなければ従来どおり MSG 先頭の "tag:" を推測する。
+    let header_start = cursor;
+    let bsd_header = if is_rfc5424 {
+        None
+    } else {
+        parse_rfc3164_header(bytes, &mut cursor)
+    };
+    if !is_rfc5424 && bsd_header.is_none() && opts.strict {
+        diags.push(Diagnostic::new(DiagnosticKind::MissingHeader, cursor, ""));
+    }
+
+    let mut detected_encoding = None;
+    let mut structured_data = Vec::new();
+    let mut header = Rfc5424Header::default();
+
+    if is_rfc5424 {
+        // TIMESTAMP, HOSTNAME, APP-NAME, PROCID, MSGID をパース (ASCII前提)
+        // RFC 5424 には TAG が無いので、表示・フィルタ用に APP-NAME を TAG として扱う
+        header = parse_rfc5424_header(bytes, &mut cursor, &mut diags);
+        tag = header.app_name.clone();
+
+        // 3. STRUCTURED-DATA (SD) パース
+        if bytes[cursor..].starts_with(b"[") {
+            let sd_start = cursor;
+            if let Some((elements, sd_len)) = parse_structured_data(&bytes[cursor..]) {
+                // charset="xxx" 宣言を探す(どの SD-ELEMENT にあってもよい)
+                if let Some((_, charset_name)) = elements
+                    .iter()
+                    .flat_map(|e| &e.params)
+                    .find(|(name, _)| name == "charset")
+                {
+                    // 特殊対応: "MSG-UTF8" というラベルを UTF-8 として扱う
+                    if charset_name.to_uppercase() == "MSG-UTF8" {
+                        detected_encoding = Some(UTF_8);
+                    } else {
+                        detected_encoding = Encoding::for_label(charset_name.as_bytes());
+                        if detected_encoding.is_none() {
+                            diags.push(Diagnostic::new(
+                                DiagnosticKind::UnknownCharset,
+                                sd_start,
+                                charset_name.clone(),
+                            ));
+                        }
+                    }
+                }
+                structured_data = elements;
+                cursor += sd_len;
+                // SDの後のスペースをスキップ
+                if cursor < bytes.len() && bytes[cursor] == b' ' {
+                    cursor += 1;
+                }
+            } else {
+                // 閉じていない SD は MSG の一部として残す
+                diags.push(Diagnostic::new(DiagnosticKind::MalformedSd, sd_start, ""));
+            }
+        } else if bytes[cursor..].starts_with(b"- ") || &bytes[cursor..] == b"-" {
+            // NILVALUE(SD 無し)
+            cursor = (cursor + 2).min(bytes.len());
+        } else if cursor < bytes.len() {
+            diags.push(Diagnostic::new(
+                DiagnosticKind::MalformedSd,
+                cursor,
+                "expected '-' or '['",
+            ));
+        } else if !diags
+            .iter()
+            .any(|d| d.kind == DiagnosticKind::TruncatedHeader)
+        {
+            diags.push(Diagnostic::new(
+                DiagnosticKind::TruncatedHeader,
+                cursor,
+                "STRUCTURED-DATA",
+            ));
+        }
+    }
+
+    // 4. MSG デコード
+    let msg_bytes = &bytes[cursor..];
+    let has_bom = msg_bytes.starts_with(&[0xEF, 0xBB, 0xBF]);
+    if has_bom || (is_rfc5424 && detected_encoding == Some(UTF_8)) {
+        let payload_start = if has_bom { 3 } else { 0 };
+        if let Err(e) = std::str::from_utf8(&msg_bytes[payload_start..]) {
+            let offset = cursor + payload_start + e.valid_up_to();
+            diags.push(Diagnostic::new(DiagnosticKind::InvalidUtf8, offset, ""));
+        }
+    } else if is_rfc5424 && opts.strict && detected_encoding.is_none() && !msg_bytes.is_ascii() {
+        diags.push(Diagnostic::new(DiagnosticKind::MissingBom, cursor, ""));
+    }
+
+    // 送信元ごとのエンコーディングルール。メッセージ自身が charset を宣言していれば使わない。
+    let rule = if opts.encoding_rules.is_empty() || detected_encoding.is_some() {
+        None
+    } else {
+        let (hostname, rule_tag) = match &bsd_header {
+            Some(bsd) => (bsd.hostname.clone(), bsd.tag.clone()),
+            None if is_rfc5424 => (header.hostname.clone(), header.app_name.clone()),
+            None => (None, guess_ascii_tag(msg_bytes)),
+        };
+        opts.encoding_rules
+            .find(opts.source, hostname.as_deref(), rule_tag.as_deref())
+    };
+
+    let (content, encoding_name) = if !is_rfc5424 {
+        // RFC 3164 等は従来通りのスマート判定
+        match rule {
+            Some(rule) if !msg_bytes.starts_with(&[0xEF, 0xBB, 0xBF]) => {
+                decode_by_rule(msg_bytes, rule.label, rule.decision)
+            }
+            _ => decode_smart(msg_bytes),
+        }
+    } else {
+        /*
+           RFC 5424 / 6.4. Message (現実の実装への配慮)
+           SD での宣言を最優先し、BOM は「あればスキップする」程度の寛容な扱いにします。
+           (規格上 BOM が必須とされるケースでも、それがない「雑な」メッセージを救済します)
+        */
+
+        if let Some(enc) = detected_encoding {
+            // A. ヘッダーに「意志」がある場合: それを全面的に信じる。
+            //    BOM は「UTF-8 であることを示すための付属物」として、あれば外す。
+            let is_bom = msg_bytes.starts_with(&[0xEF, 0xBB, 0xBF]);
+            let actual_payload = if is_bom { &msg_bytes[3..] } else { msg_bytes };
+            let (result, _, _) = enc.decode(actual_payload);
+
+            let label = if is_bom {
+                "BOM-Detected"
+            } else {
+                "BOM-Missing"
+            };
+            (
+                result.into_owned(),
+                format!("{} (MSG-SD/{})", enc.name(), label),
+            )
+        } else if msg_bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            // B. ヘッダーが沈黙しているが、BOM だけはある場合: 素直に UTF-8 として扱う。
+            let (result, _, _) = UTF_8.decode(&msg_bytes[3..]);
+            (result.into_owned(), "UTF-8 (MSG-UTF8/BOM)".to_string())
+        } else {
+            // C. メッセージにヒントが無い場合: 送信元のルールがあればそれに従い、
+            //    無ければ実態と推測に頼る。
+            if let Some(rule) = rule {
+                decode_by_rule(msg_bytes, rule.label, rule.decision)
+            } else if std::str::from_utf8(msg_bytes).is_ok() {
+                (
+                    String::from_utf8_lossy(msg_bytes).into_owned(),
+                    "UTF-8 (Implicit)".to_string(),
+                )
+            } else {
+                let (text, enc_name) = decode_smart(msg_bytes);
+                (text, format!("{} (Guess)", enc_name))
+            }
+        }
+    };
+
+    let body = content.clone();
+
+    // レガシーな TAG パース (RFC 3164 的なやつ)
+    let final_content = if !is_rfc5424 && bsd_header.is_none() {
+        if let Some(colon_pos) = content.find(':') {
+            let potential_tag = content[..colon_pos].trim();
+            if !potential_tag.contains(' ') && !potential_tag.is_empty() {
+                tag = Some(potential_tag.to_string());
+                content[colon_pos + 1..].trim().to_string()
+            } else {
+                content
+            }
+        } else {
+            content
+        }
+    } else {
+        content
+    };
+
+    let mut pid = None;
+    if let Some(bsd) = bsd_header {
+        header.timestamp = bsd.timestamp.as_deref().and_then(|t| {
+            let parsed = parse_bsd_timestamp(t, received_at);
+            if parsed.is_none() {
+                diags.push(Diagnostic::new(
+                    DiagnosticKind::InvalidTimestamp,
+                    header_start,
+                    t,
+                ));
+            }
+            parsed
+        });
+        header.hostname = bsd.hostname;
+        tag = bsd.tag;
+        pid = bsd.pid;
+    }
+
+    let msg = SyslogMessage {
+        severity,
+        facility,
+        invalid_pri,
+        received_at,
+        device_timestamp: header.timestamp,
+        hostname: header.hostname,
+        app_name: header.app_name,
+        procid: header.procid,
+        msgid: header.msgid,
+        structured_data,
+        tag,
+        pid,
+        content: final_content,
+        dialect: None,
+        fields: BTreeMap::new(),
+        raw: bytes.to_vec(),
+        encoding: encoding_name,
+        nonconforming: opts.strict && !diags.is_empty(),
+        diagnostics: diags,
+        tls_subject: None,
+        listener: None,
+        peer_cred: None,
+        truncated: false,
+        source_addr: None,
+        source_name: None,
+    };
+    (msg, body)
+}
+
+/// 先頭の "<PRIVAL>" を読む。戻り値は (PRI 部の長さ, PRIVAL)。
+/// PRI の形(< + 数字 + >)をしているが値が規格外(192 以上・4 桁以上)なら PRIVAL を None にする。
+fn parse_pri(bytes: &[u8]) -> Option<(usize, Option<u8>)> {
+    if !bytes.starts_with(b"<") {
+        return None;
+    }
+    let close = bytes.iter().take(6).position(|&b| b == b'>')?;
+    let digits = &bytes[1..close];
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let value = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|s| s.parse::<u8>().ok())
+        .filter(|&v| digits.len() <= 3 && v <= 191);
+    Some((close + 1, value))
+}
+
+/// RFC 5424 HEADER のうち VERSION より後ろの 5 フィールド。
+/// NILVALUE("-")や規格外(長さ超過・PRINTUSASCII 以外)の値は None にする。
+#[derive(Default)]
+struct Rfc5424Header {
+    timestamp: Option<DateTime<FixedOffset>>,
+    hostname: Option<String>,
+    app_name: Option<String>,
+    procid: Option<String>,
+    msgid: Option<String>,
+}
+
+/// RFC 5424 6. の各フィールド長の上限。
+const HOSTNAME_MAX: usize = 255;
+const APP_NAME_MAX: usize = 48;
+const PROCID_MAX: usize = 128;
+const MSGID_MAX: usize = 32;
+
+/// VERSION 直後(cursor)から TIMESTAMP / HOSTNAME / APP-NAME / PROCID / MSGID を読む。
+/// cursor は STRUCTURED-DATA の先頭へ進める。読めなかったフィールドは diags に残す。
+fn parse_rfc5424_header(
+    bytes: &[u8],
+    cursor: &mut usize,
+    diags: &mut Vec<Diagnostic>,
+) -> Rfc5424Header {
+    let start = *cursor;
+    let timestamp = header_field(bytes, cursor, "TIMESTAMP", diags).and_then(|f| {
+        let parsed = parse_rfc5424_timestamp(f);
+        if parsed.is_none() && f != b"-" {
+            let text = String::from_utf8_lossy(f);
+            diags.push(Diagnostic::new(
+                DiagnosticKind::InvalidTimestamp,
+                start,
+                text,
+            ));
+        }
+        parsed
+    });
+    let hostname = checked_header_value(bytes, cursor, "HOSTNAME", HOSTNAME_MAX, diags);
+    let app_name = checked_header_value(bytes, cursor, "APP-NAME", APP_NAME_MAX, diags);
+    let procid = checked_header_value(bytes, cursor, "PROCID", PROCID_MAX, diags);
+    let msgid = checked_header_value(bytes, cursor, "MSGID", MSGID_MAX, diags);
+    Rfc5424Header {
+        timestamp,
+        hostname,
+        app_name,
+        procid,
+        msgid,
+    }
+}
+
+/// cursor 位置から SP までを 1 フィールドとして切り出し、cursor を SP の次へ進める。
+fn next_header_field<'a>(bytes: &'a [u8], cursor: &mut usize) -> Option<&'a [u8]> {
+    let rest = bytes.get(*cursor..).filter(|r| !r.is_empty())?;
+    let end = rest.iter().position(|&b| b == b' ').unwrap_or(rest.len());
+    *cursor += (end + 1).min(rest.len());
+    Some(&rest[..end])
+}
+
+/// [`next_header_field`] の、HEADER が途中で終わっていたら(最初の 1 回だけ)診断を残す版。
+fn header_field<'a>(
+    bytes: &'a [u8],
+    cursor: &mut usize,
+    name: &str,
+    diags: &mut Vec<Diagnostic>,
+) -> Option<&'a [u8]> {
+    let start = *cursor;
+    let field = next_header_field(bytes, cursor);
+    if field.is_none()
+        && !diags
+            .iter()
+            .any(|d| d.kind == DiagnosticKind::TruncatedHeader)
+    {
+        diags.push(Diagnostic::new(
+            DiagnosticKind::TruncatedHeader,
+            start,
+            name,
+        ));
+    }
+    field
+}
+
+/// HEADER のフィールドを 1 つ読んで検証する。NILVALUE 以外で規格外なら診断を残して None。
+fn checked_header_value(
+    bytes: &[u8],
+    cursor: &mut usize,
+    name: &str,
+    max_len: usize,
+    diags: &mut Vec<Diagnostic>,
+) -> Option<String> {
+    let start = *cursor;
+    let field = header_field(bytes, cursor, name, diags)?;
+    let value = header_value(field, max_len);
+    if value.is_none() && field != b"-" {
+        diags.push(Diagnostic::new(
+            DiagnosticKind::InvalidHeaderField,
+            start,
+            name,
+        ));
+    }
+    value
+}
+
+/// HOSTNAME / APP-NAME / PROCID / MSGID 共通の検証(1*N PRINTUSASCII か NILVALUE)。
+fn header_value(field: &[u8], max_len: usize) -> Option<String> {
+    if field == b"-" || field.is_empty() || field.len() > max_len {
+        return None;
+    }
+    if !field.iter().all(|b| (33..=126).contains(b)) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(field).into_owned())
+}
+
+/// RFC 5424 TIMESTAMP(RFC 3339 のうち "T" 区切り・秒の小数 6 桁まで)を検証する。
+fn parse_rfc5424_timestamp(field: &[u8]) -> Option<DateTime<FixedOffset>> {
+    let s = std::str::from_utf8(field).ok()?;
+    if s == "-" || s.as_bytes().get(10) != Some(&b'T') {
+        return None;
+    }
+    let parsed = DateTime::parse_from_rfc3339(s).ok()?;
+    if let Some(frac) = s.get(19..).and_then(|t| t.strip_prefix('.')) {
+        let digits = frac.bytes().take_while(u8::is_ascii_digit).count();
+        if digits > 6 {
+            return None;
+        }
+    }
+    Some(parsed)
+}
+
+/// RFC 3164 (BSD syslog) の HEADER と TAG。
+/// "Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG" のうち MSG より前の部分。
+#[derive(Default)]
+struct Rfc3164Header {
+    /// 送信元の時刻表記そのまま。年とタイムゾーンは受信時刻から補って解釈する。
+    timestamp: Option<String>,
+    hostname: Option<String>,
+    tag: Option<String>,
+    pid: Option<String>,
+}
+
+const BSD_MONTHS: [&[u8]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// PRI 直後(cursor)から RFC 3164 の TIMESTAMP / HOSTNAME / TAG[PID]: を読む。
+/// TIMESTAMP が無ければヘッダー無しとみなして None を返し、cursor は動かさない。
+/// 成功時は cursor を MSG の先頭へ進める。
+fn parse_rfc3164_header(bytes: &[u8], cursor: &mut usize) -> Option<Rfc3164Header> {
+    let rest = bytes.get(*cursor..)?;
+    let ts_len = bsd_timestamp_len(rest)?;
+    if rest.get(ts_len) != Some(&b' ') {
+        return None;
+    }
+    let mut header = Rfc3164Header {
+        timestamp: Some(String::from_utf8_lossy(&rest[..ts_len]).into_owned()),
+        ..Default::default()
+    };
+    let mut pos = ts_len + 1;
+
+    // HOSTNAME。省略する送信元もあるので、次の語が "tag:" / "tag[pid]:" の形ならホスト名無しと判断する。
+    let word_end = rest[pos..]
+        .iter()
+        .position(|&b| b == b' ')
+        .map_or(rest.len(), |p| pos + p);
+    let word = &rest[pos..word_end];
+    let looks_like_tag = word.ends_with(b":") || word.contains(&b'[');
+    if !word.is_empty() && !looks_like_tag && word.iter().all(|b| (33..=126).contains(b)) {
+        header.hostname = Some(String::from_utf8_lossy(word).into_owned());
+        pos = (word_end + 1).min(rest.len());
+    }
+
+    // TAG[PID]: 。":" で終わらなければ TAG 無しとみなし、ここから先を MSG とする。
+    let tag_end = rest[pos..]
+        .iter()
+        .position(|&b| matches!(b, b'[' | b':' | b' '))
+        .map(|p| pos + p);
+    if let Some(tag_end) = tag_end
+        && tag_end > pos
+        && rest[pos..tag_end].iter().all(|b| (33..=126).contains(b))
+    {
+        let mut after = tag_end;
+        let mut pid = None;
+        if rest[tag_end] == b'['
+            && let Some(close) = rest[tag_end..].iter().position(|&b| b == b']')
+        {
+            pid = Some(String::from_utf8_lossy(&rest[tag_end + 1..tag_end + close]).into_owned());
+            after = tag_end + close + 1;
+        }
+        if rest.get(after) == Some(&b':') {
+            header.tag = Some(String::from_utf8_lossy(&rest[pos..tag_end]).into_owned());
+            header.pid = pid.filter(|p| !p.is_empty());
+            pos = after + 1;
+            if rest.get(pos) == Some(&b' ') {
+                pos += 1;
+            }
+        }
+    }
+
+    *cursor += pos;
+    Some(header)
+}
+
+/// BSD 形式のタイムスタンプの長さを返す。受け付ける形:
+///   - "Oct 18 12:34:56"(RFC 3164 の標準形。年を持たない)
+///   - "Oct  8 12:34:56" / "Oct 8 12:34:56"(1 桁の日。空白詰めと詰め無しの両方)
+///   - "Oct 18 2026 12:34:56"(年付き。Cisco 等)
+///   - 秒の後ろの小数 "12:34:56.123"
+fn bsd_timestamp_len(rest: &[u8]) -> Option<usize> {
+    if rest.len() < 14 || !BSD_MONTHS.contains(&&rest[..3]) || rest[3] != b' ' {
+        return None;
+    }
+    let mut i = 4;
+    if rest[i] == b' ' {
+        i += 1;
+    }
+    let day_digits = count_digits(&rest[i..]);
+    if !(1..=2).contains(&day_digits) {
+        return None;
+    }
+    i += day_digits;
+    if rest.get(i) != Some(&b' ') {
+        return None;
+    }
+    i += 1;
+    if count_digits(&rest[i..]) == 4 && rest.get(i + 4) == Some(&b' ') {
+        i += 5;
+    }
+    let time = rest.get(i..i + 8)?;
+    let is_time = time
+        .iter()
+        .enumerate()
+        .all(|(j, b)| if j == 2 || j == 5 { *b == b':' } else { b.is_ascii_digit() });
+    if !is_time {
+        return None;
+    }
+    i += 8;
+    if rest.get(i) == Some(&b'.') {
+        let frac = count_digits(&rest[i + 1..]);
+        if frac > 0 {
+            i += 1 + frac;
+        }
+    }
+    Some(i)
+}
+
+/// RFC 3164 の時刻表記("Mmm dd [yyyy ]hh:mm:ss[.fff]")を解釈する。
+///
+/// タイムゾーンは書かれていないので受信側のローカル時刻とみなす。
+/// 年も無ければ前年・同年・翌年のうち `received_at` に最も近いものを選ぶ
+/// (年末に送られて年明けに受信したメッセージを 1 年先にしないため)。
+fn parse_bsd_timestamp(text: &str, received_at: DateTime<Utc>) -> Option<DateTime<FixedOffset>> {
+    let mut parts = text.split_ascii_whitespace();
+    let month = parts.next()?.as_bytes();
+    let month = BSD_MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?;
+    let mut year = None;
+    if !time.contains(':') {
+        year = Some(time.parse::<i32>().ok()?);
+        time = parts.next()?;
+    }
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f").ok()?;
+
+    let this_year = received_at.with_timezone(&Local).year();
+    let years = match year {
+        Some(y) => y..=y,
+        None => this_year - 1..=this_year + 1,
+    };
+    years
+        .filter_map(|y| {
+            let naive = NaiveDate::from_ymd_opt(y, month, day)?.and_time(time);
+            Local.from_local_datetime(&naive).earliest()
+        })
+        .min_by_key(|t| (t.with_timezone(&Utc) - received_at).num_seconds().abs())
+        .map(|t| t.fixed_offset())
+}
+
+fn count_digits(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+/// 先頭から STRUCTURED-DATA(1 個以上の SD-ELEMENT)を読む。
+/// 戻り値は (SD-ELEMENT の列, SD 部の長さ)。閉じていない・形が崩れている場合は None。
+/// PARAM-VALUE 内の "]" や "\"" はクォート/エスケープを考慮して数える。
+pub(crate) fn parse_structured_data(bytes: &[u8]) -> Option<(Vec<SdElement>, usize)> {
+    let mut elements = Vec::new();
+    let mut i = 0;
+    while bytes.get(i) == Some(&b'[') {
+        i += 1;
+        let id = sd_name(bytes, &mut i)?;
+        let mut params = Vec::new();
+        loop {
+            match bytes.get(i)? {
+                b']' => {
+                    i += 1;
+                    break;
+                }
+                b' ' => {
+                    i += 1;
+                    let name = sd_name(bytes, &mut i)?;
+                    if bytes.get(i) != Some(&b'=') || bytes.get(i + 1) != Some(&b'"') {
+                        return None;
+                    }
+                    i += 2;
+                    let value = sd_param_value(bytes, &mut i)?;
+                    params.push((name, value));
+                }
+                _ => return None,
+            }
+        }
+        elements.push(SdElement { id, params });
+    }
+    if elements.is_empty() {
+        None
+    } else {
+        Some((elements, i))
+    }
+}
+
+/// SD-NAME(SD-ID / PARAM-NAME)。PRINTUSASCII のうち '=' SP ']' '"' を除く 1〜32 文字。
+fn sd_name(bytes: &[u8], i: &mut usize) -> Option<String> {
+    let start = *i;
+    while let Some(&b) = bytes.get(*i) {
+        if !(33..=126).contains(&b) || matches!(b, b'=' | b']' | b'"') {
+            break;
+        }
+        *i += 1;
+    }
+    let name = &bytes[start..*i];
+    if name.is_empty() || name.len() > 32 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+/// 開きクォートの直後から閉じクォートまでの PARAM-VALUE を読み、エスケープを戻す。
+/// RFC 5424 6.3.3: エスケープ対象は '"' '\' ']' のみ。それ以外の直前の '\' はそのまま残す。
+fn sd_param_value(bytes: &[u8], i: &mut usize) -> Option<String> {
+    let mut value = Vec::new();
+    loop {
+        let b = *bytes.get(*i)?;
+        *i += 1;
+        match b {
+            b'"' => break,
+            b'\\' => match bytes.get(*i) {
+                Some(&next @ (b'"' | b'\\' | b']')) => {
+                    value.push(next);
+                    *i += 1;
+                }
+                _ => value.push(b),
+            },
+            _ => value.push(b),
+        }
+    }
+    Some(String::from_utf8_lossy(&value).into_owned())
+}
+
+/// エンコーディングルールに従ってデコードする。ラベルは "Shift_JIS (Rule:legacy/Forced)" の形。
+/// ヒントは推測の材料なので、正しい UTF-8 ならそのまま UTF-8 とする。
+fn decode_by_rule(bytes: &[u8], label: &str, decision: Decision) -> (String, String) {
+    match decision {
+        Decision::Force(enc) => {
+            let (res, _) = enc.decode_without_bom_handling(bytes);
+            (
+                res.into_owned(),
+                format!("{} (Rule:{label}/Forced)", enc.name()),
+            )
+        }
+        Decision::Hint(_) if std::str::from_utf8(bytes).is_ok() => (
+            String::from_utf8_lossy(bytes).into_owned(),
+            format!("UTF-8 (Rule:{label})"),
+        ),
+        Decision::Hint(tld) => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(bytes, true);
+            let enc = detector.guess(Some(tld), true);
+            let (res, _) = enc.decode_without_bom_handling(bytes);
+            (
+                res.into_owned(),
+                format!(
+                    "{} (Rule:{label}/Hint:{})",
+                    enc.name(),
+                    String::from_utf8_lossy(tld)
+                ),
+            )
+        }
+    }
+}
+
+/// ヘッダーの無いメッセージの先頭 "tag:" を、デコード前にルール照合用として推測する。
+/// 後段の TAG 推測と同じく、":" までが空白を含まない語のときだけ。
+fn guess_ascii_tag(bytes: &[u8]) -> Option<String> {
+    let colon = bytes.iter().position(|&b| b == b':')?;
+    let word = bytes[..colon].trim_ascii();
+    (!word.is_empty() && word.iter().all(|b| (33..=126).contains(b)))
+        .then(|| String::from_utf8_lossy(word).into_owned())
+}
+
+fn decode_smart(bytes: &[u8]) -> (String, String) {
+    if bytes.is_empty() {
+        return (String::new(), "Empty".to_string());
+    }
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        let (res, _, _) = UTF_8.decode(&bytes[3..]);
+        return (res.into_owned(), "UTF-8 (BOM)".to_string());
+    }
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return (s.to_string(), "UTF-8".to_string());
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let enc = detector.guess(None, true);
+    let (res, _, _) = enc.decode(bytes);
+    (res.into_owned(), enc.name().to_string())
+}
+
@@ -0,0 +1,243 @@
+//! HAProxy の PROXY プロトコル(v1 / v2)のヘッダ。
+//!
+//! ロードバランサの後ろで TCP / TLS を受けると、接続の相手はロードバランサになり本来の送信元が分からない。
+//! PROXY プロトコルを有効にしたロードバランサは、接続の先頭に本来の送信元・宛先を書いたヘッダを 1 つ送る。
+//!
+//! - v1: `PROXY TCP4 192.0.2.7 198.51.100.1 40000 514\r\n` のテキスト 1 行(107 バイトまで)
+//! - v2: 12 バイトのシグネチャで始まるバイナリ
+//!
+//! TLS ではヘッダの直後に ClientHello が続くので、ヘッダより先を読んではいけない。
+//! [`parse`] は読んだところまでを渡すと、あと何バイト読めばよいか([`Parse::Need`])を返すので、
+//! 受け側はその数だけ読み足していけばヘッダの終わりちょうどで止まれる。
+//!
+//! ```
+//! use vlt_syslog_core::proxy::{Parse, parse};
+//!
+//! let header = b"PROXY TCP4 192.0.2.7 198.51.100.1 40000 514\r\n";
+//! let mut buf = Vec::new();
+//! let parsed = loop {
+//!     match parse(&buf).unwrap() {
+//!         Parse::Need(n) => buf.extend_from_slice(&header[buf.len()..buf.len() + n]),
+//!         Parse::Done(parsed) => break parsed,
+//!     }
+//! };
+//! assert_eq!(parsed.source, Some("192.0.2.7:40000".parse().unwrap()));
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use ipnet::IpNet;
+
+/// v2 のシグネチャ。
+pub const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// v1 のヘッダ(CRLF を含む)の最大長。
+pub const V1_MAX_LEN: usize = 107;
+/// 最も短いヘッダ(`PROXY UNKNOWN\r\n`)の長さ。最初はこれだけ読めば読み過ぎない。
+const MIN_LEN: usize = 15;
+/// v2 の固定部(シグネチャ・版とコマンド・アドレスファミリ・長さ)。
+const V2_FIXED_LEN: usize = 16;
+
+/// ヘッダに書かれていた接続。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// 本来の送信元。ロードバランサ自身のヘルスチェック(v2 の LOCAL、v1 の UNKNOWN)や
+    /// IP 以外のアドレスファミリでは None で、そのときは接続の相手をそのまま送信元とする。
+    pub source: Option<SocketAddr>,
+    /// 本来の宛先(ロードバランサが受けたアドレス)。
+    pub destination: Option<SocketAddr>,
+}
+
+/// [`parse`] の結果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parse {
+    /// ちょうどこのバイト数を読み足してから、もう一度呼ぶ。
+    Need(usize),
+    /// 渡したバイト列がちょうど 1 つのヘッダだった。
+    Done(ProxyHeader),
+}
+
+/// ヘッダを読めなかった。受け側は接続を閉じる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyError {
+    /// PROXY プロトコルのヘッダで始まっていない(ロードバランサ側の設定漏れなど)。
+    Missing,
+    /// ヘッダが規格どおりでない。値は壊れていた箇所。
+    Malformed(&'static str),
+}
+
+impl std::fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::Missing => f.write_str("connection did not start with a PROXY header"),
+            ProxyError::Malformed(what) => write!(f, "malformed PROXY header ({what})"),
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+/// 接続の先頭から読んだバイト列 `buf` を解釈する。`buf` はヘッダの終わりを越えてはいけない
+/// (直前の [`Parse::Need`] の数だけ読み足したもの)。
+pub fn parse(buf: &[u8]) -> Result<Parse, ProxyError> {
+    if buf.len() < MIN_LEN {
+        let head = &buf[..buf.len().min(6)];
+        if !b"PROXY "[..head.len()].eq(head) && !V2_SIGNATURE[..head.len()].eq(head) {
+            return Err(ProxyError::Missing);
+        }
+        return Ok(Parse::Need(MIN_LEN - buf.len()));
+    }
+    if buf.starts_with(b"PROXY ") {
+        return parse_v1(buf);
+    }
+    if !buf.starts_with(&V2_SIGNATURE) {
+        return Err(ProxyError::Missing);
+    }
+    if buf.len() < V2_FIXED_LEN {
+        return Ok(Parse::Need(V2_FIXED_LEN - buf.len()));
+    }
+    let total = V2_FIXED_LEN + usize::from(u16::from_be_bytes([buf[14], buf[15]]));
+    if buf.len() < total {
+        return Ok(Parse::Need(total - buf.len()));
+    }
+    parse_v2(&buf[..total]).map(Parse::Done)
+}
+
+/// v1。CRLF が来るまで 1 バイトずつ読ませる。
+fn parse_v1(buf: &[u8]) -> Result<Parse, ProxyError> {
+    if !buf.ends_with(b"\r\n") {
+        if buf.len() >= V1_MAX_LEN {
+            return Err(ProxyError::Malformed("v1 header too long"));
+        }
+        if buf.ends_with(b"\n") {
+            return Err(ProxyError::Malformed("v1 line ending"));
+        }
+        return Ok(Parse::Need(1));
+    }
+    let line = std::str::from_utf8(&buf[..buf.len() - 2])
+        .map_err(|_| ProxyError::Malformed("v1 encoding"))?;
+    let words: Vec<&str> = line.split(' ').collect();
+    let (source, destination) = match words.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => (None, None),
+        ["PROXY", family @ ("TCP4" | "TCP6"), src, dst, sport, dport] => {
+            let v4 = *family == "TCP4";
+            let ip = |s: &str| {
+                s.parse::<IpAddr>()
+                    .ok()
+                    .filter(|ip| ip.is_ipv4() == v4)
+                    .ok_or(ProxyError::Malformed("v1 address"))
+            };
+            // 先頭の 0 や "+" は規格外なので数字だけを受け付ける
+            let port = |s: &str| {
+                let digits = s.bytes().all(|b| b.is_ascii_digit());
+                s.parse::<u16>()
+                    .ok()
+                    .filter(|_| digits && (s == "0" || !s.starts_with('0')))
+                    .ok_or(ProxyError::Malformed("v1 port"))
+            };
+            (
+                Some(SocketAddr::new(ip(src)?, port(sport)?)),
+                Some(SocketAddr::new(ip(dst)?, port(dport)?)),
+            )
+        }
+        _ => return Err(ProxyError::Malformed("v1 fields")),
+    };
+    Ok(Parse::Done(ProxyHeader {
+        source,
+        destination,
+    }))
+}
+
+/// v2。`buf` は固定部と長さぶんの本体がちょうど揃ったもの。本体の後ろの TLV は読み飛ばす。
+fn parse_v2(buf: &[u8]) -> Result<ProxyHeader, ProxyError> {
+    if buf[..12] != V2_SIGNATURE {
+        return Err(ProxyError::Missing);
+    }
+    if buf[12] >> 4 != 2 {
+        return Err(ProxyError::Malformed("v2 version"));
+    }
+    match buf[12] & 0x0f {
+        // LOCAL はロードバランサ自身の接続(ヘルスチェックなど)。アドレスは無視する
+        0 => {
+            return Ok(ProxyHeader {
+                source: None,
+                destination: None,
+            });
+        }
+        1 => {}
+        _ => return Err(ProxyError::Malformed("v2 command")),
+    }
+    let body = &buf[V2_FIXED_LEN..];
+    let (source, destination) = match buf[13] >> 4 {
+        // AF_INET
+        1 => {
+            let addr = body
+                .get(..12)
+                .ok_or(ProxyError::Malformed("v2 address length"))?;
+            let ip = |at: usize| {
+                IpAddr::V4(Ipv4Addr::new(
+                    addr[at],
+                    addr[at + 1],
+                    addr[at + 2],
+                    addr[at + 3],
+                ))
+            };
+            let port = |at: usize| u16::from_be_bytes([addr[at], addr[at + 1]]);
+            (
+                SocketAddr::new(ip(0), port(8)),
+                SocketAddr::new(ip(4), port(10)),
+            )
+        }
+        // AF_INET6
+        2 => {
+            let addr = body
+                .get(..36)
+                .ok_or(ProxyError::Malformed("v2 address length"))?;
+            let ip = |at: usize| {
+                let octets: [u8; 16] = addr[at..at + 16].try_into().unwrap_or_default();
+                IpAddr::V6(Ipv6Addr::from(octets))
+            };
+            let port = |at: usize| u16::from_be_bytes([addr[at], addr[at + 1]]);
+            (
+                SocketAddr::new(ip(0), port(32)),
+                SocketAddr::new(ip(16), port(34)),
+            )
+        }
+        // AF_UNSPEC / AF_UNIX: IP の送信元は無い
+        0 | 3 => {
+            return Ok(ProxyHeader {
+                source: None,
+                destination: None,
+            });
+        }
+        _ => return Err(ProxyError::Malformed("v2 address family")),
+    };
+    Ok(ProxyHeader {
+        source: Some(source),
+        destination: Some(destination),
+    })
+}
+
+/// ヘッダを送ってよいロードバランサのアドレス(`trusted_proxies`)。
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// "192.0.2.10" / "10.0.0.0/8" のような IP アドレスか CIDR の並び。読めなければそれを添えて Err。
+    pub fn compile(sources: &[String]) -> Result<Self, String> {
+        let nets = sources
+            .iter()
+            .map(|s| {
+                crate::acl::parse_source(s).ok_or_else(|| format!("invalid trusted proxy {s:?}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { nets })
+    }
+
+    /// `ip` からの接続の先頭に PROXY ヘッダを期待するか。
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+}
@@ -0,0 +1,184 @@
+//! RELP(Reliable Event Logging Protocol。rsyslog の `omrelp` / `imrelp`)のフレーム。
+//!
+//! フレームは `TXNR SP COMMAND SP DATALEN [SP DATA] LF`。送信側は `syslog` コマンドの
+//! 応答(`rsp` の `200 OK`)を受け取るまでメッセージを手元に残し、接続が切れたら再送する。
+//! したがって受け側は、メッセージを保存し終えてから応答を返す必要がある。
+//!
+//! [`RelpDecoder`] は [`crate::framing::FrameDecoder`] と同じく、ソケットを持たずに
+//! 読んだバイト列からフレームを切り出すだけ。応答は [`encode_rsp`] で作る。
+//!
+//! ```
+//! use vlt_syslog_core::relp::{RelpDecoder, encode_rsp};
+//!
+//! let mut decoder = RelpDecoder::new(8192);
+//! decoder.extend(b"2 syslog 11 <13>app: hi\n");
+//! let frame = decoder.next_frame().unwrap().unwrap();
+//! assert_eq!((frame.txnr, frame.command.as_str()), (2, "syslog"));
+//! assert_eq!(frame.data, b"<13>app: hi");
+//! assert_eq!(encode_rsp(frame.txnr, "200 OK"), b"2 rsp 6 200 OK\n");
+//! ```
+
+/// RELP のフレーム 1 件。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelpFrame {
+    /// トランザクション番号。応答には同じ番号を付ける。
+    pub txnr: u64,
+    /// `open` / `syslog` / `close` など。
+    pub command: String,
+    pub data: Vec<u8>,
+}
+
+/// フレームを読めなかった。どちらの場合もこの接続の続きは読めないので、受け側は接続を閉じる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelpError {
+    /// DATALEN が上限を超えている。
+    TooLarge { len: usize, max: usize },
+    /// ヘッダ(TXNR / COMMAND / DATALEN)か末尾の LF が規格どおりでない。値は壊れていた箇所。
+    Malformed(&'static str),
+}
+
+impl std::fmt::Display for RelpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelpError::TooLarge { len, max } => {
+                write!(
+                    f,
+                    "RELP frame of {len} bytes exceeds the limit of {max} bytes"
+                )
+            }
+            RelpError::Malformed(what) => write!(f, "malformed RELP frame ({what})"),
+        }
+    }
+}
+
+impl std::error::Error for RelpError {}
+
+/// TXNR / DATALEN の桁数の上限(RFC 相当の仕様では 9 桁)。
+const MAX_NUMBER_DIGITS: usize = 9;
+/// COMMAND の長さの上限。
+const MAX_COMMAND_LEN: usize = 32;
+
+/// 1 本の接続ぶんの受信バッファ。
+pub struct RelpDecoder {
+    buf: Vec<u8>,
+    max_len: usize,
+}
+
+impl RelpDecoder {
+    /// `max_len` は 1 フレームの DATA の上限バイト数。
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max_len,
+        }
+    }
+
+    /// ソケットから読んだバイト列を足す。
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// 揃っているフレームを 1 件取り出す。まだ揃っていなければ Ok(None)。
+    pub fn next_frame(&mut self) -> Result<Option<RelpFrame>, RelpError> {
+        let mut pos = 0;
+        let Some(txnr) = self.number(&mut pos, "TXNR")? else {
+            return Ok(None);
+        };
+        let command_len = self.buf[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic())
+            .count();
+        if command_len > MAX_COMMAND_LEN {
+            return Err(RelpError::Malformed("COMMAND"));
+        }
+        match self.buf.get(pos + command_len) {
+            None => return Ok(None),
+            Some(b' ') if command_len > 0 => {}
+            Some(_) => return Err(RelpError::Malformed("COMMAND")),
+        }
+        let command = String::from_utf8_lossy(&self.buf[pos..pos + command_len]).into_owned();
+        pos += command_len + 1;
+
+        // DATALEN の後ろは、DATA があれば SP、無ければそのまま LF
+        let digits = self.buf[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits > MAX_NUMBER_DIGITS {
+            return Err(RelpError::Malformed("DATALEN"));
+        }
+        let Some(&sep) = self.buf.get(pos + digits) else {
+            return Ok(None);
+        };
+        if digits == 0 {
+            return Err(RelpError::Malformed("DATALEN"));
+        }
+        let len: usize = parse_digits(&self.buf[pos..pos + digits]);
+        pos += digits;
+        if len > self.max_len {
+            return Err(RelpError::TooLarge {
+                len,
+                max: self.max_len,
+            });
+        }
+        let data_start = match (len, sep) {
+            (0, b'\n') => pos,
+            (_, b' ') => pos + 1,
+            _ => return Err(RelpError::Malformed("DATALEN")),
+        };
+        let trailer = data_start + len;
+        let Some(&end) = self.buf.get(trailer) else {
+            return Ok(None);
+        };
+        if end != b'\n' {
+            return Err(RelpError::Malformed("TRAILER"));
+        }
+        let data = self.buf[data_start..trailer].to_vec();
+        self.buf.drain(..=trailer);
+        Ok(Some(RelpFrame {
+            txnr,
+            command,
+            data,
+        }))
+    }
+
+    /// `pos` から数字と続く SP を読む。まだ揃っていなければ Ok(None)。
+    fn number(&self, pos: &mut usize, what: &'static str) -> Result<Option<u64>, RelpError> {
+        let digits = self.buf[*pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits > MAX_NUMBER_DIGITS {
+            return Err(RelpError::Malformed(what));
+        }
+        match self.buf.get(*pos + digits) {
+            None => return Ok(None),
+            Some(b' ') if digits > 0 => {}
+            Some(_) => return Err(RelpError::Malformed(what)),
+        }
+        let n = parse_digits(&self.buf[*pos..*pos + digits]) as u64;
+        *pos += digits + 1;
+        Ok(Some(n))
+    }
+}
+
+/// 9 桁までの ASCII 数字列を数値にする(桁数・文字種は呼び出し側で確認済み)。
+fn parse_digits(digits: &[u8]) -> usize {
+    digits.iter().fold(0, |n, d| n * 10 + usize::from(d - b'0'))
+}
+
+/// フレームを組み立てる。
+pub fn encode_frame(txnr: u64, command: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = format!("{txnr} {command} {}", data.len()).into_bytes();
+    if !data.is_empty() {
+        out.push(b' ');
+        out.extend_from_slice(data);
+    }
+    out.push(b'\n');
+    out
+}
+
+/// `txnr` への応答(`rsp`)を組み立てる。`status` は "200 OK" のように 3 桁の番号と説明。
+pub fn encode_rsp(txnr: u64, status: &str) -> Vec<u8> {
+    encode_frame(txnr, "rsp", status.as_bytes())
+}
@@ -0,0 +1,46 @@
+//! Server → GUI の配信ストリーム(JSON Lines)と制御ポートの既定値。
+//!
+//! 配信ストリームは「1 メッセージ = [`SyslogMessage`] の JSON 1 行 + 改行」。
+//! 送る側(Server)と受ける側(Console や社内ツール)は必ずこのモジュールの関数を通し、
+//! 行の形式を片側だけで変えてしまわないようにする。
+//!
+//! 生バイト列は `raw_b64`(base64)で送る。Server の設定で省いた行には `raw_b64` が無い。
+//! 旧バージョンの Server は `raw`(16 進)で送っていたので、読むときはそちらも受け付ける。
+
+use serde::Deserialize;
+
+use crate::message::SyslogMessage;
+
+/// 配信ストリームの既定アドレス。ループバック限定で外部には公開しない。
+pub const DEFAULT_STREAM_ADDR: &str = "127.0.0.1:5141";
+
+/// 制御ポート(設定の取得/変更)の既定アドレス。ループバック限定で外部には公開しない。
+pub const DEFAULT_CONTROL_ADDR: &str = "127.0.0.1:5142";
+
+/// メッセージを配信用の 1 行(末尾の改行は含まない)にする。
+pub fn encode_line(msg: &SyslogMessage) -> serde_json::Result<String> {
+    serde_json::to_string(msg)
+}
+
+/// 配信ストリームの 1 行をメッセージに戻す。前後の空白・改行は無視する。
+pub fn decode_line(line: &str) -> serde_json::Result<SyslogMessage> {
+    let line = line.trim();
+    let mut msg: SyslogMessage = serde_json::from_str(line)?;
+    if msg.raw.is_empty() && line.contains("\"raw\"") {
+        msg.raw = legacy_raw(line);
+    }
+    Ok(msg)
+}
+
+/// 旧バージョンの `raw`(16 進)。読めなければ空。
+fn legacy_raw(line: &str) -> Vec<u8> {
+    #[derive(Deserialize)]
+    struct Legacy {
+        #[serde(default)]
+        raw: String,
+    }
+    serde_json::from_str::<Legacy>(line)
+        .ok()
+        .and_then(|l| hex::decode(l.raw).ok())
+        .unwrap_or_default()
+}
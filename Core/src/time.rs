@@ -0,0 +1,63 @@
+//! 時刻の表示タイムゾーン。
+//!
+//! メッセージの時刻は UTC(`received_at`)またはオフセット付き(`device_timestamp`)で持ち、
+//! 画面やファイルに出すときだけ利用者が選んだタイムゾーンに直す。
+//! 設定ファイルには文字列("Local" / "UTC" / "Asia/Tokyo" などの IANA 名)で保存する。
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+
+/// 表示に使うタイムゾーン。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayZone {
+    /// この PC のローカル時刻(既定)。
+    #[default]
+    Local,
+    /// UTC。
+    Utc,
+    /// IANA タイムゾーン名で指定したもの(例 "Asia/Tokyo")。
+    Named(chrono_tz::Tz),
+}
+
+impl DisplayZone {
+    /// 画面の時刻列に出す形式。日付・ミリ秒・UTC からのオフセットまで書き、曖昧さを残さない。
+    pub fn format<Tz: TimeZone>(&self, t: &DateTime<Tz>) -> String {
+        const FMT: &str = "%Y-%m-%d %H:%M:%S%.3f %:z";
+        match self {
+            DisplayZone::Local => t.with_timezone(&Local).format(FMT).to_string(),
+            DisplayZone::Utc => t.with_timezone(&Utc).format(FMT).to_string(),
+            DisplayZone::Named(tz) => t.with_timezone(tz).format(FMT).to_string(),
+        }
+    }
+}
+
+impl FromStr for DisplayZone {
+    type Err = String;
+
+    /// "Local" / "UTC"(大文字小文字は問わない)か IANA 名。空文字は Local。
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("local") {
+            return Ok(DisplayZone::Local);
+        }
+        if s.eq_ignore_ascii_case("utc") {
+            return Ok(DisplayZone::Utc);
+        }
+        s.parse::<chrono_tz::Tz>()
+            .map(DisplayZone::Named)
+            .map_err(|_| format!("unknown time zone: {s}"))
+    }
+}
+
+impl fmt::Display for DisplayZone {
+    /// 設定ファイルに書く表記。[`FromStr`] でそのまま読み戻せる。
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayZone::Local => write!(f, "Local"),
+            DisplayZone::Utc => write!(f, "UTC"),
+            DisplayZone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! 送信元のアクセス制御の結合テスト。
+
+use vlt_syslog_core::acl::{AccessList, AclAction, AclRule};
+
+fn rule(action: AclAction, source: &str, listener: Option<&str>) -> AclRule {
+    AclRule {
+        action,
+        source: source.into(),
+        listener: listener.map(Into::into),
+    }
+}
+
+fn allowed(acl: &AccessList, source: &str, listener: &str) -> bool {
+    acl.is_allowed(source.parse().unwrap(), listener)
+}
+
+#[test]
+fn empty_list_allows_everyone() {
+    let acl = AccessList::empty();
+    assert!(allowed(&acl, "203.0.113.9", "udp://[::]:514"));
+}
+
+#[test]
+fn deny_rules_only_reject_their_sources() {
+    let acl = AccessList::compile(&[rule(AclAction::Deny, "203.0.113.0/24", None)]).unwrap();
+    assert!(!allowed(&acl, "203.0.113.9", "udp://[::]:514"));
+    // デュアルスタックで受けた IPv4 も同じ扱い
+    assert!(!allowed(&acl, "::ffff:203.0.113.9", "udp://[::]:514"));
+    assert!(allowed(&acl, "198.51.100.1", "udp://[::]:514"));
+}
+
+#[test]
+fn first_matching_rule_wins_and_allow_rules_reject_the_rest() {
+    let acl = AccessList::compile(&[
+        rule(AclAction::Deny, "192.0.2.66", None),
+        rule(AclAction::Allow, "192.0.2.0/24", None),
+    ])
+    .unwrap();
+    assert!(!allowed(&acl, "192.0.2.66", "lan"));
+    assert!(allowed(&acl, "192.0.2.10", "lan"));
+    assert!(!allowed(&acl, "198.51.100.1", "lan"));
+}
+
+#[test]
+fn listener_rules_apply_only_to_that_listener() {
+    let acl = AccessList::compile(&[rule(AclAction::Allow, "10.0.0.0/8", Some("dmz"))]).unwrap();
+    assert!(allowed(&acl, "10.1.2.3", "dmz"));
+    assert!(!allowed(&acl, "192.0.2.10", "dmz"));
+    // 他の待ち受け口には allow のルールが無いので全部受ける
+    assert!(allowed(&acl, "192.0.2.10", "lan"));
+}
+
+#[test]
+fn invalid_source_is_reported_with_rule_number() {
+    let err = AccessList::compile(&[
+        rule(AclAction::Allow, "192.0.2.0/24", None),
+        rule(AclAction::Deny, "not-an-ip", None),
+    ])
+    .err()
+    .unwrap();
+    assert!(err.contains("#2"), "{err}");
+}
@@ -0,0 +1,295 @@
+//! パケットキャプチャからの取り出しの結合テスト。キャプチャはテストの中で組み立てる。
+
+use chrono::{DateTime, Utc};
+use vlt_syslog_core::capture::{self, CaptureError, DEFAULT_PORTS, Transport, read_messages};
+
+fn udp(sport: u16, dport: u16, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend_from_slice(&sport.to_be_bytes());
+    segment.extend_from_slice(&dport.to_be_bytes());
+    segment.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    segment.extend_from_slice(&[0, 0]);
+    segment.extend_from_slice(payload);
+    segment
+}
+
+fn tcp(sport: u16, dport: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend_from_slice(&sport.to_be_bytes());
+    segment.extend_from_slice(&dport.to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&[0; 4]);
+    segment.extend_from_slice(&[5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    segment.extend_from_slice(payload);
+    segment
+}
+
+fn ipv4(src: [u8; 4], dst: [u8; 4], protocol: u8, flags_offset: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x45, 0];
+    packet.extend_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(&flags_offset.to_be_bytes());
+    packet.extend_from_slice(&[64, protocol, 0, 0]);
+    packet.extend_from_slice(&src);
+    packet.extend_from_slice(&dst);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn ipv6(src: &str, dst: &str, protocol: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x60, 0, 0, 0];
+    packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[protocol, 64]);
+    packet.extend_from_slice(&src.parse::<std::net::Ipv6Addr>().unwrap().octets());
+    packet.extend_from_slice(&dst.parse::<std::net::Ipv6Addr>().unwrap().octets());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// VLAN タグ付きの Ethernet フレーム。
+fn ethernet_vlan(ip: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; 12];
+    frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a, 0x08, 0x00]);
+    frame.extend_from_slice(ip);
+    frame
+}
+
+/// リトルエンディアン・マイクロ秒の pcap(LINKTYPE_ETHERNET)。
+fn pcap(records: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut file = Vec::new();
+    file.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&4u16.to_le_bytes());
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&65535u32.to_le_bytes());
+    file.extend_from_slice(&1u32.to_le_bytes());
+    for (secs, micros, frame) in records {
+        file.extend_from_slice(&secs.to_le_bytes());
+        file.extend_from_slice(&micros.to_le_bytes());
+        file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        file.extend_from_slice(frame);
+    }
+    file
+}
+
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let mut body = body.to_vec();
+    body.resize(body.len().div_ceil(4) * 4, 0);
+    let len = 12 + body.len() as u32;
+    let mut out = Vec::new();
+    out.extend_from_slice(&block_type.to_be_bytes());
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&body);
+    out.extend_from_slice(&len.to_be_bytes());
+    out
+}
+
+/// ビッグエンディアンの pcapng。LINKTYPE_RAW・ナノ秒(if_tsresol = 9)のインターフェース 1 つ。
+fn pcapng(packets: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    pcapng_with_offset(0, packets)
+}
+
+/// [`pcapng`] の、インターフェースに if_tsoffset(秒)を付ける版。
+fn pcapng_with_offset(offset: i64, packets: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&0x1a2b_3c4du32.to_be_bytes());
+    shb.extend_from_slice(&[0, 1, 0, 0]);
+    shb.extend_from_slice(&u64::MAX.to_be_bytes());
+    let mut file = block(0x0a0d_0d0a, &shb);
+
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&101u16.to_be_bytes());
+    idb.extend_from_slice(&[0, 0]);
+    idb.extend_from_slice(&0u32.to_be_bytes());
+    idb.extend_from_slice(&[0, 9, 0, 1, 9, 0, 0, 0]); // if_tsresol = 10^-9
+    if offset != 0 {
+        idb.extend_from_slice(&[0, 14, 0, 8]); // if_tsoffset
+        idb.extend_from_slice(&offset.to_be_bytes());
+    }
+    idb.extend_from_slice(&[0, 0, 0, 0]);
+    file.extend(block(1, &idb));
+
+    for (nanos, packet) in packets {
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_be_bytes());
+        epb.extend_from_slice(&((nanos >> 32) as u32).to_be_bytes());
+        epb.extend_from_slice(&(*nanos as u32).to_be_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        epb.extend_from_slice(packet);
+        file.extend(block(6, &epb));
+    }
+    file
+}
+
+fn time(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+#[test]
+fn pcap_udp_datagrams_keep_their_time_and_source() {
+    let src = [192, 0, 2, 7];
+    let dst = [198, 51, 100, 1];
+    let file = pcap(&[
+        (
+            1_760_788_800,
+            250_000,
+            ethernet_vlan(&ipv4(src, dst, 17, 0, &udp(40000, 514, b"<13>app: second"))),
+        ),
+        // syslog 以外のポートは取り込まない
+        (
+            1_760_788_799,
+            0,
+            ethernet_vlan(&ipv4(src, dst, 17, 0, &udp(40000, 53, b"dns"))),
+        ),
+        (
+            1_760_788_799,
+            500_000,
+            ethernet_vlan(&ipv4(src, dst, 17, 0, &udp(40001, 514, b"<13>app: first"))),
+        ),
+        // フラグメントは組み立てずに捨てる
+        (
+            1_760_788_801,
+            0,
+            ethernet_vlan(&ipv4(src, dst, 17, 0x2000, &udp(40002, 514, b"<13>frag"))),
+        ),
+    ]);
+    let messages = read_messages(&file, &DEFAULT_PORTS).unwrap();
+    assert_eq!(messages.len(), 2);
+    // 時刻順に並ぶ
+    assert_eq!(messages[0].raw, b"<13>app: first");
+    assert_eq!(messages[0].timestamp, time("2025-10-18T11:59:59.5Z"));
+    assert_eq!(messages[0].source, "192.0.2.7:40001".parse().unwrap());
+    assert_eq!(messages[0].destination, "198.51.100.1:514".parse().unwrap());
+    assert_eq!(messages[0].transport, Transport::Udp);
+    assert_eq!(messages[1].timestamp, time("2025-10-18T12:00:00.25Z"));
+
+    // ポートを指定しなければすべての UDP を取り込む
+    assert_eq!(read_messages(&file, &[]).unwrap().len(), 3);
+}
+
+#[test]
+fn pcapng_tcp_streams_are_reassembled_and_framed() {
+    let (src, dst) = ("2001:db8::7", "2001:db8::1");
+    let t0 = 1_760_788_800_000_000_000u64;
+    let packet = |sport: u16, seq: u32, flags: u8, payload: &[u8]| {
+        ipv6(src, dst, 6, &tcp(sport, 601, seq, flags, payload))
+    };
+    let file = pcapng(&[
+        (t0, packet(40000, 999, 0x02, b"")),
+        (t0 + 1, packet(40000, 1000, 0x18, b"11 <13>app: hi12 <13>a")),
+        // 同じセグメントの再送は二重に数えない
+        (t0 + 2, packet(40000, 1000, 0x18, b"11 <13>app: hi12 <13>a")),
+        (t0 + 3, packet(40000, 1022, 0x18, b"pp: bye")),
+        // LF 区切りの接続の、最後の LF の無いメッセージはキャプチャの終わりで取り出す
+        (
+            t0 + 4,
+            packet(40001, 5000, 0x18, b"<13>app: one\n<13>app: tail"),
+        ),
+    ]);
+    let messages = read_messages(&file, &DEFAULT_PORTS).unwrap();
+    let raws: Vec<&[u8]> = messages.iter().map(|m| m.raw.as_slice()).collect();
+    assert_eq!(
+        raws,
+        [
+            &b"<13>app: hi"[..],
+            b"<13>app: bye",
+            b"<13>app: one",
+            b"<13>app: tail"
+        ]
+    );
+    assert_eq!(messages[0].transport, Transport::Tcp);
+    assert_eq!(messages[0].source, "[2001:db8::7]:40000".parse().unwrap());
+    // メッセージの時刻は最後のセグメントの時刻(ナノ秒まで)
+    assert_eq!(
+        messages[1].timestamp,
+        time("2025-10-18T12:00:00.000000003Z")
+    );
+}
+
+#[test]
+fn short_retransmissions_do_not_rewind_the_stream() {
+    let (src, dst) = ("2001:db8::7", "2001:db8::1");
+    let t0 = 1_760_788_800_000_000_000u64;
+    let packet = |sport: u16, seq: u32, payload: &[u8]| {
+        ipv6(src, dst, 6, &tcp(sport, 601, seq, 0x18, payload))
+    };
+    // 2 つ目の接続はシーケンス番号が途中で 0 に戻る
+    let wrap = u32::MAX - 10;
+    let file = pcapng(&[
+        (t0, packet(40000, 1000, b"11 <13>app: hi12 <13>a")),
+        // 読み終えたセグメントの先頭だけの再送
+        (t0 + 1, packet(40000, 1000, b"11 <13>")),
+        (t0 + 2, packet(40000, 1022, b"pp: bye")),
+        (t0 + 3, packet(40001, wrap, b"11 <13>app: hi12 <13>a")),
+        (t0 + 4, packet(40001, wrap.wrapping_add(3), b"<13>app")),
+        (t0 + 5, packet(40001, wrap.wrapping_add(22), b"pp: bye")),
+    ]);
+    let messages = read_messages(&file, &DEFAULT_PORTS).unwrap();
+    let raws: Vec<&[u8]> = messages.iter().map(|m| m.raw.as_slice()).collect();
+    assert_eq!(
+        raws,
+        [
+            &b"<13>app: hi"[..],
+            b"<13>app: bye",
+            b"<13>app: hi",
+            b"<13>app: bye"
+        ]
+    );
+}
+
+#[test]
+fn timestamp_offsets_are_applied_unless_they_overflow() {
+    let packet = ipv4(
+        [192, 0, 2, 7],
+        [198, 51, 100, 1],
+        17,
+        0,
+        &udp(40000, 514, b"<13>app: hi"),
+    );
+    let t0 = 1_760_788_800_000_000_000u64;
+    let file = pcapng_with_offset(60, &[(t0, packet.clone())]);
+    let messages = read_messages(&file, &DEFAULT_PORTS).unwrap();
+    assert_eq!(messages[0].timestamp, time("2025-10-18T12:01:00Z"));
+
+    // 壊れた・細工された if_tsoffset で溢れるパケットは読み飛ばす
+    let file = pcapng_with_offset(i64::MAX, &[(t0, packet)]);
+    assert!(read_messages(&file, &DEFAULT_PORTS).unwrap().is_empty());
+}
+
+#[test]
+fn files_over_the_size_limit_are_not_read() {
+    let dir = std::env::temp_dir().join(format!("vlt-capture-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let small = dir.join("small.pcap");
+    std::fs::write(&small, pcap(&[])).unwrap();
+    assert_eq!(capture::read_file(&small).unwrap(), pcap(&[]));
+
+    // 中身は読まないので、大きさだけ上限を超えた疎なファイルで足りる
+    let large = dir.join("large.pcap");
+    std::fs::File::create(&large)
+        .unwrap()
+        .set_len(capture::MAX_FILE_SIZE + 1)
+        .unwrap();
+    let err = capture::read_file(&large).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("too large"), "{err}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn truncated_and_foreign_files() {
+    let src = [192, 0, 2, 7];
+    let dst = [198, 51, 100, 1];
+    let frame = ethernet_vlan(&ipv4(src, dst, 17, 0, &udp(40000, 514, b"<13>app: hi")));
+    let mut file = pcap(&[(1, 0, frame.clone()), (2, 0, frame)]);
+    // 書き込み途中で切れたファイルは読めたところまで
+    file.truncate(file.len() - 5);
+    assert_eq!(read_messages(&file, &DEFAULT_PORTS).unwrap().len(), 1);
+
+    assert_eq!(
+        read_messages(b"<13>app: not a capture", &DEFAULT_PORTS).unwrap_err(),
+        CaptureError::UnknownFormat
+    );
+}
@@ -0,0 +1,148 @@
+//! パース診断の結合テスト。壊れた入力でもメッセージは返り、救済した箇所が
+//! 種類とバイト位置つきで残ることを確かめる。
+
+use vlt_syslog_core::{
+    Diagnostic, DiagnosticKind, ParseOptions, Severity, SyslogMessage, parse_syslog,
+    parse_syslog_with,
+};
+
+fn kinds(diags: &[Diagnostic]) -> Vec<(DiagnosticKind, usize)> {
+    diags.iter().map(|d| (d.kind, d.offset)).collect()
+}
+
+fn strict(bytes: &[u8]) -> SyslogMessage {
+    parse_syslog_with(
+        bytes,
+        &ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        },
+    )
+}
+
+#[test]
+fn well_formed_messages_have_no_diagnostics() {
+    let msg = parse_syslog(b"<34>1 2026-10-18T12:00:00Z host app 123 ID47 [a b=\"c\"] hello");
+    assert!(msg.diagnostics.is_empty(), "{:?}", msg.diagnostics);
+    let msg = strict(b"<34>Oct 18 12:00:00 host su[230]: hello");
+    assert!(msg.diagnostics.is_empty(), "{:?}", msg.diagnostics);
+    assert!(!msg.nonconforming);
+}
+
+#[test]
+fn pri_problems_are_reported_at_offset_zero() {
+    let msg = parse_syslog(b"<999>Oct 18 12:00:00 host app: x");
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::InvalidPri, 0)]);
+    assert_eq!(msg.diagnostics[0].detail, "PRIVAL 999");
+    assert_eq!(msg.diagnostics[0].to_string(), "invalid_pri@0 (PRIVAL 999)");
+
+    let msg = parse_syslog(b"<1x>hello");
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::InvalidPri, 0)]);
+
+    let msg = parse_syslog(b"Oct 18 12:00:00 host app: x");
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::MissingPri, 0)]);
+}
+
+#[test]
+fn version_and_header_problems_carry_offsets() {
+    // "<13>" の直後(4)の VERSION "01"
+    let msg = parse_syslog(b"<13>01 2026-10-18T12:00:00Z host app - - - x");
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::InvalidVersion, 4)]
+    );
+
+    let msg = parse_syslog(b"<13>2 2026-10-18T12:00:00Z host app - - - x");
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::InvalidVersion, 4)]
+    );
+
+    // TIMESTAMP は 6 バイト目から
+    let msg = parse_syslog(b"<13>1 yesterday host app - - - x");
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::InvalidTimestamp, 6)]
+    );
+    assert_eq!(msg.diagnostics[0].detail, "yesterday");
+
+    // APP-NAME は 48 文字まで
+    let long_app = "a".repeat(49);
+    let raw = format!("<13>1 - host {long_app} - - - x");
+    let msg = parse_syslog(raw.as_bytes());
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::InvalidHeaderField, 13)]
+    );
+    assert_eq!(msg.diagnostics[0].detail, "APP-NAME");
+
+    let msg = parse_syslog(b"<13>1 - host app");
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::TruncatedHeader, 16)]
+    );
+    assert_eq!(msg.diagnostics[0].detail, "PROCID");
+}
+
+#[test]
+fn sd_problems_are_reported_and_msg_is_kept() {
+    let raw = b"<13>1 - host app - - [meta charset=\"UTF-8\" x=\"unterminated] hello";
+    let msg = parse_syslog(raw);
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::MalformedSd, 21)]);
+    assert!(msg.content.contains("hello"));
+    assert!(matches!(msg.severity, Severity::Notice));
+
+    let msg = parse_syslog(b"<13>1 - host app - - hello");
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::MalformedSd, 21)]);
+
+    let msg = parse_syslog(b"<13>1 - host app - - [meta charset=\"x-klingon\"] hello");
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::UnknownCharset, 21)]
+    );
+    assert_eq!(msg.diagnostics[0].detail, "x-klingon");
+}
+
+#[test]
+fn invalid_utf8_after_declaration_points_at_the_bad_byte() {
+    let mut raw = b"<13>1 - host app - - - \xEF\xBB\xBFok".to_vec();
+    raw.push(0xFF);
+    let msg = parse_syslog(&raw);
+    // MSG は 23 バイト目から。BOM(3)と "ok"(2)の後ろ
+    assert_eq!(kinds(&msg.diagnostics), [(DiagnosticKind::InvalidUtf8, 28)]);
+}
+
+#[test]
+fn strict_mode_adds_conformance_checks_and_marks_messages() {
+    // ヘッダー無しの RFC 3164 は通常は黙って救済する
+    let raw = b"<13>app: hello";
+    assert!(parse_syslog(raw).diagnostics.is_empty());
+    let msg = strict(raw);
+    assert_eq!(
+        kinds(&msg.diagnostics),
+        [(DiagnosticKind::MissingHeader, 4)]
+    );
+    assert!(msg.nonconforming);
+
+    // BOM も charset も無い非 ASCII の RFC 5424 MSG
+    let raw = "<13>1 - host app - - - こんにちは".as_bytes();
+    assert!(parse_syslog(raw).diagnostics.is_empty());
+    assert_eq!(
+        kinds(&strict(raw).diagnostics),
+        [(DiagnosticKind::MissingBom, 23)]
+    );
+
+    // strict でなければ診断があっても nonconforming は付けない
+    let msg = parse_syslog(b"<999>hello");
+    assert!(!msg.diagnostics.is_empty());
+    assert!(!msg.nonconforming);
+}
+
+#[test]
+fn diagnostics_round_trip_through_json() {
+    let msg = parse_syslog(b"<999>hello");
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.contains("\"kind\":\"invalid_pri\""), "{json}");
+    let back: SyslogMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.diagnostics, msg.diagnostics);
+}
@@ -0,0 +1,314 @@
+//! ベンダー方言の結合テスト。`tests/corpus/<vendor>.log` の各行(実機の出力に近い形)を
+//! そのベンダーとして解釈できることと、代表的な行の取り出し結果を確かめる。
+
+use vlt_syslog_core::dialect::{Dialect, DialectSet};
+use vlt_syslog_core::{
+    ParseOptions, SyslogMessage, parse_syslog, parse_syslog_at, parse_syslog_with,
+};
+
+/// コーパスの全行を読む(空行は飛ばす)。
+fn corpus(name: &str) -> Vec<SyslogMessage> {
+    let path = format!("{}/tests/corpus/{name}", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{path}: {e}"))
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| parse_syslog(l.as_bytes()))
+        .collect()
+}
+
+fn field<'a>(msg: &'a SyslogMessage, key: &str) -> Option<&'a str> {
+    msg.fields.get(key).map(String::as_str)
+}
+
+#[test]
+fn every_corpus_line_is_recognised_by_its_vendor() {
+    for (file, dialect) in [
+        ("cisco.log", "cisco"),
+        ("juniper.log", "juniper"),
+        ("fortigate.log", "fortigate"),
+        ("paloalto.log", "paloalto"),
+        ("cef.log", "cef"),
+        ("leef.log", "leef"),
+        ("json.log", "json"),
+        ("logfmt.log", "logfmt"),
+    ] {
+        for msg in corpus(file) {
+            assert_eq!(msg.dialect.as_deref(), Some(dialect), "{file}: {}", msg.content);
+            assert!(!msg.fields.is_empty(), "{file}: {}", msg.content);
+        }
+    }
+}
+
+#[test]
+fn plain_messages_are_left_alone() {
+    for msg in corpus("plain.log") {
+        assert_eq!(msg.dialect, None, "{}", msg.content);
+        assert!(msg.fields.is_empty());
+    }
+}
+
+#[test]
+fn cisco_ios_mnemonic_and_prefix() {
+    let msgs = corpus("cisco.log");
+
+    let m = &msgs[0];
+    assert_eq!(field(m, "seq"), Some("000123"));
+    assert_eq!(field(m, "device_time"), Some("*Mar  1 00:01:02.123"));
+    assert_eq!(field(m, "facility_code"), Some("LINK"));
+    assert_eq!(field(m, "vendor_severity"), Some("3"));
+    assert_eq!(field(m, "mnemonic"), Some("UPDOWN"));
+    assert_eq!(m.tag.as_deref(), Some("LINK"));
+    assert_eq!(m.content, "Interface GigabitEthernet0/1, changed state to down");
+
+    let m = &msgs[1];
+    assert_eq!(m.hostname.as_deref(), Some("core-sw1"));
+    assert_eq!(field(m, "mnemonic"), Some("CONFIG_I"));
+
+    // BSD ヘッダー付き: 通し番号は TAG の位置に来る
+    let m = &msgs[2];
+    assert_eq!(m.hostname.as_deref(), Some("core-sw1"));
+    assert_eq!(field(m, "seq"), Some("125"));
+    assert_eq!(field(m, "facility_code"), Some("LINEPROTO"));
+}
+
+#[test]
+fn cisco_nxos_and_iosxr_forms() {
+    let msgs = corpus("cisco.log");
+
+    let m = &msgs[3];
+    assert_eq!(m.hostname.as_deref(), Some("nxos1"));
+    assert_eq!(field(m, "device_time"), Some("2026 Oct 18 12:00:00"));
+    assert_eq!(field(m, "facility_code"), Some("ETHPORT"));
+    assert_eq!(field(m, "mnemonic"), Some("IF_UP"));
+
+    let m = &msgs[4];
+    assert_eq!(field(m, "facility_code"), Some("PKT_INFRA-LINK"));
+    assert_eq!(field(m, "vendor_severity"), Some("3"));
+    assert!(m.content.starts_with("Interface GigabitEthernet0/0/0/0"));
+}
+
+#[test]
+fn juniper_structured_and_bsd_forms() {
+    let msgs = corpus("juniper.log");
+
+    let m = &msgs[0];
+    assert_eq!(field(m, "mnemonic"), Some("UI_COMMIT"));
+    assert_eq!(field(m, "username"), Some("admin"));
+
+    let m = &msgs[1];
+    assert_eq!(m.tag.as_deref(), Some("mib2d"));
+    assert_eq!(field(m, "mnemonic"), Some("SNMP_TRAP_LINK_DOWN"));
+    assert!(m.content.starts_with("ifIndex 526"));
+
+    let m = &msgs[2];
+    assert_eq!(field(m, "mnemonic"), Some("RT_FLOW_SESSION_CREATE"));
+    assert_eq!(field(m, "destination-port"), Some("443"));
+    assert!(m.content.starts_with("session created"));
+}
+
+#[test]
+fn fortigate_key_values() {
+    let msgs = corpus("fortigate.log");
+
+    let m = &msgs[0];
+    assert_eq!(m.hostname.as_deref(), Some("FGT60F-HQ"));
+    assert_eq!(m.tag.as_deref(), Some("traffic"));
+    assert_eq!(field(m, "mnemonic"), Some("0000000013"));
+    assert_eq!(field(m, "vendor_severity"), Some("notice"));
+    assert_eq!(field(m, "srcip"), Some("192.0.2.10"));
+    assert_eq!(field(m, "dstintf"), Some("wan1"));
+    assert!(m.content.starts_with("date=2026-10-18 time=12:00:00"));
+
+    let m = &msgs[1];
+    assert_eq!(
+        m.content,
+        "Administrator admin login failed from https(10.0.0.5) because of invalid password"
+    );
+
+    let m = &msgs[2];
+    assert_eq!(m.hostname.as_deref(), Some("FGT-ブランチ"));
+    assert_eq!(field(m, "msg"), Some("web_misc: HTTP.URI.SQL.Injection, \"quoted\""));
+}
+
+#[test]
+fn paloalto_csv_columns() {
+    let msgs = corpus("paloalto.log");
+
+    let m = &msgs[0];
+    assert_eq!(m.hostname.as_deref(), Some("PA-VM"));
+    assert_eq!(m.tag.as_deref(), Some("TRAFFIC"));
+    assert_eq!(field(m, "mnemonic"), Some("TRAFFIC/end"));
+    assert_eq!(field(m, "src"), Some("192.0.2.10"));
+    assert_eq!(field(m, "rule"), Some("allow-web"));
+    assert_eq!(field(m, "dport"), Some("443"));
+    assert_eq!(field(m, "action"), Some("allow"));
+    assert_eq!(field(m, "col32"), Some("6600"));
+
+    let m = &msgs[1];
+    assert_eq!(field(m, "vendor_severity"), Some("high"));
+    assert_eq!(
+        m.content,
+        "User admin failed login. Reason: Invalid username or password, From: 10.0.0.5"
+    );
+
+    let m = &msgs[2];
+    assert_eq!(field(m, "cmd"), Some("set"));
+    assert_eq!(field(m, "admin"), Some("admin"));
+}
+
+#[test]
+fn cef_header_and_extension() {
+    let msgs = corpus("cef.log");
+
+    let m = &msgs[0];
+    assert_eq!(field(m, "device_vendor"), Some("Trend Micro"));
+    assert_eq!(field(m, "device_product"), Some("Deep Security Agent"));
+    assert_eq!(field(m, "signature_id"), Some("4000000"));
+    assert_eq!(field(m, "mnemonic"), Some("4000000"));
+    assert_eq!(field(m, "vendor_severity"), Some("6"));
+    // 値の中の空白はそのまま、"\\" は "\" に戻る
+    assert_eq!(field(m, "cn1Label"), Some("Host ID"));
+    assert_eq!(field(m, "filePath"), Some(r"C:\Users\admin\Downloads\eicar.com"));
+    assert_eq!(m.tag.as_deref(), Some("Deep Security Agent"));
+    assert_eq!(m.content, "Eicar_test_file");
+
+    // BSD ヘッダー付き("CEF:" が TAG に取られる形)、ヘッダー内の "\|" と値の "\=" "\n"
+    let m = &msgs[1];
+    assert_eq!(m.hostname.as_deref(), Some("ids01"));
+    assert_eq!(field(m, "name"), Some("detected a | in message"));
+    assert_eq!(field(m, "act"), Some("blocked a ="));
+    assert_eq!(field(m, "dst"), Some("1.1.1.1"));
+    assert_eq!(field(m, "cs1"), Some("line1\nline2"));
+
+    // RFC 5424 の MSG に入った CEF
+    let m = &msgs[2];
+    assert_eq!(m.hostname.as_deref(), Some("waf01"));
+    assert_eq!(field(m, "vendor_severity"), Some("High"));
+    assert_eq!(field(m, "requestMethod"), Some("PROPFIND"));
+}
+
+#[test]
+fn leef_versions_and_delimiters() {
+    let msgs = corpus("leef.log");
+
+    // 1.0: タブ区切り
+    let m = &msgs[0];
+    assert_eq!(field(m, "leef_version"), Some("1.0"));
+    assert_eq!(field(m, "device_product"), Some("MSExchange"));
+    assert_eq!(field(m, "mnemonic"), Some("15345"));
+    assert_eq!(field(m, "dst"), Some("2.10.20.20"));
+    assert_eq!(field(m, "vendor_severity"), Some("5"));
+    assert_eq!(m.content, "Delivery failed");
+
+    // 2.0: 区切り文字 "^"。値の中の "=" はそのまま
+    let m = &msgs[1];
+    assert_eq!(m.hostname.as_deref(), Some("qradar-src"));
+    assert_eq!(field(m, "srcPort"), Some("81"));
+    assert_eq!(field(m, "msg"), Some("a=b c"));
+
+    // 2.0: 16 進表記の区切り文字(0x7c = "|")
+    let m = &msgs[2];
+    assert_eq!(field(m, "usrName"), Some("bob"));
+    assert_eq!(field(m, "cat"), Some("login"));
+
+    // 2.0: 区切り文字が空ならタブ
+    let m = &msgs[3];
+    assert_eq!(field(m, "src"), Some("192.0.2.1"));
+    assert_eq!(field(m, "dst"), Some("192.0.2.2"));
+}
+
+/// 利用側で足した方言は組み込みより先に(または代わりに)使える。
+#[test]
+fn custom_dialect_can_be_plugged_in() {
+    struct Hello;
+    impl Dialect for Hello {
+        fn name(&self) -> &'static str {
+            "hello"
+        }
+        fn apply(&self, body: &str, msg: &mut SyslogMessage) -> bool {
+            let Some(who) = body.strip_prefix("hello ") else {
+                return false;
+            };
+            msg.fields.insert("who".to_string(), who.to_string());
+            true
+        }
+    }
+
+    let mut set = DialectSet::empty();
+    set.push(Box::new(Hello));
+    let now = chrono::Utc::now();
+    let opts = ParseOptions {
+        received_at: now,
+        dialects: &set,
+        ..ParseOptions::default()
+    };
+    let msg = parse_syslog_with(b"<13>hello world", &opts);
+    assert_eq!(msg.dialect.as_deref(), Some("hello"));
+    assert_eq!(msg.fields.get("who").map(String::as_str), Some("world"));
+
+    // 空の一覧なら本文は解釈しない
+    let raw = b"<187>%LINK-3-UPDOWN: Interface Gi0/1, changed state to down";
+    let empty = DialectSet::empty();
+    let opts = ParseOptions {
+        received_at: now,
+        dialects: &empty,
+        ..ParseOptions::default()
+    };
+    assert!(parse_syslog_with(raw, &opts).fields.is_empty());
+    assert_eq!(parse_syslog_at(raw, now).dialect.as_deref(), Some("cisco"));
+}
+
+#[test]
+fn json_bodies_are_flattened() {
+    let msgs = corpus("json.log");
+
+    let m = &msgs[0];
+    assert_eq!(m.tag.as_deref(), Some("api"));
+    assert_eq!(field(m, "user"), Some("alice"));
+    assert_eq!(field(m, "client.ip"), Some("192.0.2.7"));
+    assert_eq!(field(m, "client.port"), Some("51000"));
+    assert_eq!(field(m, "tags.1"), Some("web"));
+    assert_eq!(field(m, "vendor_severity"), Some("warn"));
+    assert_eq!(m.content, "login failed");
+
+    let m = &msgs[1];
+    assert_eq!(field(m, "order_id"), Some("1234"));
+    assert_eq!(field(m, "paid"), Some("true"));
+    assert_eq!(field(m, "coupon"), Some(""));
+    assert_eq!(m.content, "order created");
+
+    // 本文にできるキーが無ければ、JSON の文字列ではなく項目を並べたものが本文になる
+    let m = &msgs[2];
+    assert_eq!(m.tag.as_deref(), Some("worker"));
+    assert_eq!(m.content, "duration_ms=42 job=resize");
+
+    let m = &msgs[3];
+    assert_eq!(field(m, "user"), Some("山田"));
+}
+
+#[test]
+fn logfmt_bodies_are_split() {
+    let msgs = corpus("logfmt.log");
+
+    let m = &msgs[0];
+    assert_eq!(m.tag.as_deref(), Some("app"));
+    assert_eq!(field(m, "level"), Some("info"));
+    assert_eq!(m.content, "hello");
+
+    let m = &msgs[1];
+    assert_eq!(field(m, "upstream"), Some("10.0.0.9:8080"));
+    assert_eq!(field(m, "vendor_severity"), Some("error"));
+    assert_eq!(m.content, "upstream timed out");
+
+    let m = &msgs[2];
+    assert_eq!(m.tag.as_deref(), Some("svc"));
+    assert_eq!(field(m, "status"), Some("200"));
+    assert_eq!(m.fields_logfmt(), "method=GET path=/health status=200");
+}
+
+#[test]
+fn fields_logfmt_quotes_values_that_need_it() {
+    let m = parse_syslog(br#"<13>{"msg":"a \"b\"","empty":"","eq":"x=y"}"#);
+    assert_eq!(m.fields_logfmt(), r#"empty="" eq="x=y" msg="a \"b\"""#);
+}
@@ -0,0 +1,149 @@
+//! 送信元ごとのエンコーディングルールの結合テスト。
+
+use std::net::IpAddr;
+
+use vlt_syslog_core::encoding::{EncodingRule, EncodingRules};
+use vlt_syslog_core::{ParseOptions, parse_syslog, parse_syslog_with};
+
+fn rules(rules: &[EncodingRule]) -> EncodingRules {
+    EncodingRules::compile(rules).expect("rules compile")
+}
+
+fn opts<'a>(rules: &'a EncodingRules, source: &str) -> ParseOptions<'a> {
+    ParseOptions {
+        source: Some(source.parse::<IpAddr>().unwrap()),
+        encoding_rules: rules,
+        ..ParseOptions::default()
+    }
+}
+
+#[test]
+fn source_cidr_forces_encoding() {
+    // "テスト" の Shift_JIS。短すぎて推測だけでは当たらない
+    let raw = b"<13>Oct 18 12:00:00 sw01 sys: \x83\x65\x83\x58\x83\x67";
+    let rules = rules(&[EncodingRule {
+        name: Some("legacy".into()),
+        source: Some("192.0.2.0/24".into()),
+        encoding: Some("Shift_JIS".into()),
+        ..Default::default()
+    }]);
+
+    let msg = parse_syslog_with(raw, &opts(&rules, "192.0.2.10"));
+    assert_eq!(msg.content, "テスト");
+    assert_eq!(msg.encoding, "Shift_JIS (Rule:legacy/Forced)");
+
+    // デュアルスタックのソケットで受けた IPv4 も同じ扱い
+    let msg = parse_syslog_with(raw, &opts(&rules, "::ffff:192.0.2.10"));
+    assert_eq!(msg.encoding, "Shift_JIS (Rule:legacy/Forced)");
+
+    // 範囲外の送信元は従来どおり推測
+    let msg = parse_syslog_with(raw, &opts(&rules, "198.51.100.1"));
+    assert!(!msg.encoding.contains("Rule:"));
+}
+
+#[test]
+fn message_declaration_wins_over_rule() {
+    let rules = rules(&[EncodingRule {
+        source: Some("192.0.2.10".into()),
+        encoding: Some("Shift_JIS".into()),
+        ..Default::default()
+    }]);
+
+    let raw = "<13>1 2026-10-18T12:00:00Z host app - - [origin charset=\"UTF-8\"] こんにちは";
+    let msg = parse_syslog_with(raw.as_bytes(), &opts(&rules, "192.0.2.10"));
+    assert_eq!(msg.content, "こんにちは");
+    assert!(msg.encoding.contains("MSG-SD"));
+
+    let raw = "<13>1 2026-10-18T12:00:00Z host app - - \u{FEFF}こんにちは";
+    let msg = parse_syslog_with(raw.as_bytes(), &opts(&rules, "192.0.2.10"));
+    assert_eq!(msg.content, "こんにちは");
+    assert_eq!(msg.encoding, "UTF-8 (MSG-UTF8/BOM)");
+
+    // 宣言の無い RFC 5424 にはルールが効く("テスト" の Shift_JIS)
+    let raw = b"<13>1 2026-10-18T12:00:00Z host app - - \x83\x65\x83\x58\x83\x67";
+    let msg = parse_syslog_with(raw, &opts(&rules, "192.0.2.10"));
+    assert_eq!(msg.content, "テスト");
+    assert_eq!(msg.encoding, "Shift_JIS (Rule:#1/Forced)");
+}
+
+#[test]
+fn hostname_and_tag_rules_give_detector_hints() {
+    // "中文日志" の GBK
+    let raw = b"<13>Oct 18 12:00:00 cn-fw01 kernel: \xd6\xd0\xce\xc4\xc8\xd5\xd6\xbe";
+    let rules = rules(&[
+        EncodingRule {
+            hostname: Some("CN-FW01".into()),
+            tag: Some("kernel".into()),
+            tld_hint: Some(".cn".into()),
+            ..Default::default()
+        },
+        EncodingRule {
+            tag: Some("kernel".into()),
+            encoding: Some("EUC-JP".into()),
+            ..Default::default()
+        },
+    ]);
+
+    let msg = parse_syslog_with(
+        raw,
+        &ParseOptions {
+            encoding_rules: &rules,
+            ..ParseOptions::default()
+        },
+    );
+    assert_eq!(msg.content, "中文日志");
+    assert_eq!(msg.encoding, "GBK (Rule:#1/Hint:cn)");
+
+    // ホスト名が違えば 2 件目(タグだけの条件)に当たる
+    let raw = b"<13>Oct 18 12:00:00 jp-sw01 kernel: \xa5\xc6\xa5\xb9\xa5\xc8";
+    let msg = parse_syslog_with(
+        raw,
+        &ParseOptions {
+            encoding_rules: &rules,
+            ..ParseOptions::default()
+        },
+    );
+    assert_eq!(msg.content, "テスト");
+    assert_eq!(msg.encoding, "EUC-JP (Rule:#2/Forced)");
+
+    // 正しい UTF-8 はヒントがあってもそのまま
+    let msg = parse_syslog_with(
+        "<13>Oct 18 12:00:00 cn-fw01 kernel: 正常".as_bytes(),
+        &ParseOptions {
+            encoding_rules: &rules,
+            ..ParseOptions::default()
+        },
+    );
+    assert_eq!(msg.content, "正常");
+    assert_eq!(msg.encoding, "UTF-8 (Rule:#1)");
+
+    // ルールを渡さなければ従来どおり
+    assert!(!parse_syslog(raw).encoding.contains("Rule:"));
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let bad_source = EncodingRule {
+        source: Some("192.0.2.0/99".into()),
+        encoding: Some("Shift_JIS".into()),
+        ..Default::default()
+    };
+    let err = EncodingRules::compile(&[bad_source]).err().unwrap();
+    assert!(err.contains("#1") && err.contains("source"), "{err}");
+
+    let bad_encoding = EncodingRule {
+        name: Some("x".into()),
+        encoding: Some("no-such-charset".into()),
+        ..Default::default()
+    };
+    assert!(EncodingRules::compile(&[bad_encoding]).is_err());
+
+    let bad_tld = EncodingRule {
+        tld_hint: Some("zz".into()),
+        ..Default::default()
+    };
+    assert!(EncodingRules::compile(&[bad_tld]).is_err());
+
+    assert!(EncodingRules::compile(&[EncodingRule::default()]).is_err());
+    assert!(EncodingRules::compile(&[]).unwrap().is_empty());
+}
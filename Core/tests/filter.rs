@@ -0,0 +1,57 @@
+//! 絞り込み欄の書式の結合テスト。
+
+use vlt_syslog_core::filter::MessageFilter;
+use vlt_syslog_core::parse_syslog;
+
+#[test]
+fn text_and_field_conditions_combine() {
+    let msg = parse_syslog(b"<13>Oct 18 12:00:00 web01 api: user=alice action=login result=failed");
+
+    assert!(MessageFilter::parse("").is_empty());
+    assert!(MessageFilter::parse("").matches(&msg));
+    assert!(MessageFilter::parse("web01").matches(&msg));
+    assert!(MessageFilter::parse("fields.user=alice").matches(&msg));
+    assert!(MessageFilter::parse("fields.user=alice fields.result=FAILED").matches(&msg));
+    assert!(MessageFilter::parse("API fields.action=login").matches(&msg));
+
+    assert!(!MessageFilter::parse("fields.user=bob").matches(&msg));
+    assert!(!MessageFilter::parse("fields.missing=x").matches(&msg));
+    assert!(!MessageFilter::parse("db01 fields.user=alice").matches(&msg));
+}
+
+#[test]
+fn words_that_are_not_field_conditions_stay_text() {
+    // "fields." の後ろに "=" が無い語はただの文字列
+    let msg = parse_syslog(b"<13>app: copy fields.txt to backup");
+    assert!(MessageFilter::parse("fields.txt").matches(&msg));
+    assert!(MessageFilter::parse("copy fields.txt").matches(&msg));
+}
+
+#[test]
+fn listener_condition_matches_the_receiving_listener() {
+    let mut msg = parse_syslog(b"<13>app: hello");
+    assert!(!MessageFilter::parse("listener=dmz").matches(&msg));
+    msg.listener = Some("DMZ".to_string());
+    assert!(!MessageFilter::parse("listener=dmz").is_empty());
+    assert!(MessageFilter::parse("listener=dmz hello").matches(&msg));
+    assert!(!MessageFilter::parse("listener=lan").matches(&msg));
+}
+
+#[test]
+fn source_condition_matches_address_cidr_or_name() {
+    let mut msg = parse_syslog(b"<13>app: hello");
+    assert!(!MessageFilter::parse("src=192.0.2.7").matches(&msg));
+    msg.source_addr = Some("192.0.2.7".parse().unwrap());
+    msg.source_name = Some("fw01.example.net".to_string());
+    assert!(MessageFilter::parse("src=192.0.2.7").matches(&msg));
+    assert!(MessageFilter::parse("src=192.0.2.0/24 hello").matches(&msg));
+    assert!(MessageFilter::parse("src=FW01").matches(&msg));
+    assert!(!MessageFilter::parse("src=198.51.100.0/24").matches(&msg));
+    assert!(!MessageFilter::parse("src=sw01").matches(&msg));
+    // 送信元の名前は自由入力の文字列でも当たる
+    assert!(MessageFilter::parse("example.net").matches(&msg));
+
+    // IPv4 射影の IPv6 アドレス(デュアルスタックの受信)も IPv4 の CIDR で当たる
+    msg.source_addr = Some("::ffff:192.0.2.7".parse().unwrap());
+    assert!(MessageFilter::parse("src=192.0.2.0/24").matches(&msg));
+}
@@ -0,0 +1,83 @@
+//! RFC 6587 の区切りの結合テスト。
+
+use vlt_syslog_core::framing::{FrameDecoder, FrameError, Framing};
+
+fn drain(decoder: &mut FrameDecoder) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.next_frame().unwrap() {
+        frames.push(frame);
+    }
+    frames
+}
+
+#[test]
+fn octet_counting_is_detected_and_split_across_reads() {
+    let stream = b"26 <13>1 - host app - - - a b\n28 <13>1 - host app - - - line2";
+    let mut decoder = FrameDecoder::new(1024);
+    let mut frames = Vec::new();
+    // 1 バイトずつ届いても同じように切り出せる
+    for b in stream {
+        decoder.extend(&[*b]);
+        frames.extend(drain(&mut decoder));
+    }
+    assert_eq!(decoder.framing(), Some(Framing::OctetCounting));
+    assert_eq!(
+        frames,
+        [
+            b"<13>1 - host app - - - a b".to_vec(),
+            b"<13>1 - host app - - - line2".to_vec()
+        ]
+    );
+    // 長さで区切るので、本文中の LF はそのまま残る
+    let mut decoder = FrameDecoder::new(1024);
+    decoder.extend(b"9 <13>a\nb:c");
+    assert_eq!(drain(&mut decoder), [b"<13>a\nb:c".to_vec()]);
+}
+
+#[test]
+fn lf_framing_handles_crlf_blank_lines_and_eof() {
+    let mut decoder = FrameDecoder::new(1024);
+    decoder.extend(b"<13>app: one\r\n\n<13>app: two\n<13>app: th");
+    assert_eq!(
+        drain(&mut decoder),
+        [b"<13>app: one".to_vec(), b"<13>app: two".to_vec()]
+    );
+    assert_eq!(decoder.framing(), Some(Framing::NonTransparent));
+    decoder.extend(b"ree");
+    assert_eq!(decoder.finish(), Some(b"<13>app: three".to_vec()));
+
+    // NUL 区切り(glibc の syslog(3) が Unix ストリームソケットへ送る形)
+    let mut decoder = FrameDecoder::new(1024);
+    decoder.extend(b"<13>app: one\0<13>app: two\0");
+    assert_eq!(
+        drain(&mut decoder),
+        [b"<13>app: one".to_vec(), b"<13>app: two".to_vec()]
+    );
+}
+
+#[test]
+fn limits_and_bad_lengths_are_errors() {
+    let mut decoder = FrameDecoder::new(16);
+    decoder.extend(b"100 <13>");
+    assert_eq!(
+        decoder.next_frame(),
+        Err(FrameError::TooLarge { len: 100, max: 16 })
+    );
+
+    let mut decoder = FrameDecoder::new(16);
+    decoder.extend(b"<13>app: this line never ends");
+    assert!(matches!(
+        decoder.next_frame(),
+        Err(FrameError::TooLarge { max: 16, .. })
+    ));
+
+    let mut decoder = FrameDecoder::new(16);
+    decoder.extend(b"12x <13>");
+    assert_eq!(decoder.next_frame(), Err(FrameError::BadLength));
+
+    // Octet Counting の途中で切れた分は捨てる
+    let mut decoder = FrameDecoder::new(64);
+    decoder.extend(b"20 <13>app: cut");
+    assert_eq!(decoder.next_frame(), Ok(None));
+    assert_eq!(decoder.finish(), None);
+}
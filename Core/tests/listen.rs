@@ -0,0 +1,59 @@
+//! 受信ソケット作成の結合テスト。
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::Duration;
+
+use vlt_syslog_core::listen;
+
+#[test]
+fn unspecified_ipv6_also_receives_ipv4() {
+    // IPv6 の無い環境では 0.0.0.0 に読み替えられるので、どちらでも IPv4 から届く
+    let socket = listen::udp_socket("[::]:0").unwrap();
+    socket.set_nonblocking(false).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    sender
+        .send_to(b"<13>app: hi", (Ipv4Addr::LOCALHOST, port))
+        .unwrap();
+    let mut buf = [0u8; 64];
+    let (n, src) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"<13>app: hi");
+    // デュアルスタックでは IPv4 の送信元は ::ffff:127.0.0.1 として見える
+    assert_eq!(src.ip().to_canonical(), Ipv4Addr::LOCALHOST);
+}
+
+#[test]
+fn tcp_listener_accepts_connections() {
+    let listener = listen::tcp_listener("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _client = std::net::TcpStream::connect(addr).unwrap();
+    listener.set_nonblocking(false).unwrap();
+    let (_, peer) = listener.accept().unwrap();
+    assert_eq!(peer.ip(), Ipv4Addr::LOCALHOST);
+}
+
+// Windows では収まらないデータグラムの recv がエラーになる(`listen::is_oversized_datagram`)
+#[cfg(not(windows))]
+#[test]
+fn oversized_datagrams_are_truncated_and_flagged() {
+    let socket = listen::udp_socket("127.0.0.1:0").unwrap();
+    socket.set_nonblocking(false).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let addr = socket.local_addr().unwrap();
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let mut buf = listen::DatagramBuf::new(16);
+
+    sender.send_to(&[b'a'; 16], addr).unwrap();
+    let (n, _) = socket.recv_from(buf.as_mut_slice()).unwrap();
+    assert_eq!(buf.received(n), (&[b'a'; 16][..], false));
+
+    sender.send_to(&[b'b'; 40000], addr).unwrap();
+    let (n, _) = socket.recv_from(buf.as_mut_slice()).unwrap();
+    assert_eq!(buf.received(n), (&[b'b'; 16][..], true));
+}
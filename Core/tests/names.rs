@@ -0,0 +1,65 @@
+//! 送信元の名前解決の結合テスト。
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use vlt_syslog_core::names::{ResolveConfig, Resolver, parse_hosts};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn hosts_file_format_is_parsed() {
+    let hosts = parse_hosts(
+        "# comment\n\
+         192.0.2.7   fw01.example.net fw01   # edge\n\
+         \n\
+         2001:db8::1 core01\n\
+         192.0.2.7   duplicate\n\
+         not-an-ip   ignored\n\
+         198.51.100.1\n",
+    );
+    assert_eq!(hosts.len(), 2);
+    assert_eq!(hosts[&ip("192.0.2.7")], "fw01.example.net");
+    assert_eq!(hosts[&ip("2001:db8::1")], "core01");
+}
+
+#[test]
+fn hosts_entries_answer_without_dns() {
+    let hosts = parse_hosts("192.0.2.7 fw01\n");
+    let resolver = Resolver::new(hosts, false, Duration::from_secs(60));
+    assert_eq!(resolver.name(ip("192.0.2.7")).as_deref(), Some("fw01"));
+    // デュアルスタックで受けた IPv4 射影アドレスも同じ送信元
+    assert_eq!(
+        resolver.name(ip("::ffff:192.0.2.7")).as_deref(),
+        Some("fw01")
+    );
+    // 逆引きが無効なら hosts に無い送信元は名前無し
+    assert_eq!(resolver.name(ip("192.0.2.8")), None);
+}
+
+#[test]
+fn reverse_lookup_result_is_cached_in_the_background() {
+    let resolver = Resolver::new(HashMap::new(), true, Duration::from_secs(60));
+    // 初回は逆引きを頼むだけで待たない
+    let _ = resolver.name(ip("127.0.0.1"));
+    // 結果(引けなかったことも含む)がキャッシュに入れば、以降は同じ答えを返し続ける
+    std::thread::sleep(Duration::from_secs(2));
+    let first = resolver.name(ip("127.0.0.1"));
+    assert_eq!(resolver.name(ip("127.0.0.1")), first);
+}
+
+#[test]
+fn missing_hosts_file_is_reported() {
+    let config = ResolveConfig {
+        hosts_file: Some("/nonexistent/vlt-syslogd-hosts".into()),
+        ..ResolveConfig::default()
+    };
+    let err = Resolver::from_config(&config).err().unwrap();
+    assert!(
+        err.starts_with("hosts_file /nonexistent/vlt-syslogd-hosts"),
+        "{err}"
+    );
+}
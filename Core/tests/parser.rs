@@ -0,0 +1,287 @@
+//! パーサの結合テスト。公開 API(`parse_syslog` と `SyslogMessage`)だけを使う。
+
+use chrono::{DateTime, Datelike, Utc};
+use vlt_syslog_core::{Facility, SdElement, Severity, parse_syslog, parse_syslog_at};
+
+#[test]
+fn test_rfc5424_with_charset() {
+    // <13>1 - - - - - [meta charset="Shift_JIS"] (SjisBytes)
+    let header = b"<13>1 - - - - - [meta charset=\"Shift_JIS\"] ";
+    let sjis_body = [0x82, 0xB1, 0x82, 0xF1, 0x82, 0xC9, 0x82, 0xBF, 0x82, 0xCD];
+    let mut full = header.to_vec();
+    full.extend_from_slice(&sjis_body);
+
+    let msg = parse_syslog(&full);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "Shift_JIS (MSG-SD/BOM-Missing)");
+}
+
+#[test]
+fn test_rfc5424_msg_utf8_bom() {
+    let header = b"<13>1 - - - - - - ";
+    let utf8_body_with_bom = [
+        0xEF, 0xBB, 0xBF, 0xE3, 0x81, 0x93, 0xE3, 0x82, 0x93, 0xE3, 0x81, 0xAB, 0xE3, 0x81,
+        0xA1, 0xE3, 0x81, 0xAF,
+    ];
+    let mut full = header.to_vec();
+    full.extend_from_slice(&utf8_body_with_bom);
+
+    let msg = parse_syslog(&full);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "UTF-8 (MSG-UTF8/BOM)");
+}
+
+#[test]
+fn test_rfc5424_with_sd_utf8_label() {
+    let header = b"<13>1 - - - - - [meta charset=\"UTF-8\"] ";
+    let body = "こんにちは".as_bytes();
+    let mut full = header.to_vec();
+    full.extend_from_slice(body);
+
+    let msg = parse_syslog(&full);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "UTF-8 (MSG-SD/BOM-Missing)");
+}
+
+#[test]
+fn test_rfc5424_with_sd_utf8_and_bom() {
+    // SD宣言があり、かつBOMもあるケース
+    let header = b"<13>1 - - - - - [meta charset=\"UTF-8\"] ";
+    let body = [
+        0xEF, 0xBB, 0xBF, 0xE3, 0x81, 0x93, 0xE3, 0x82, 0x93, 0xE3, 0x81, 0xAB, 0xE3, 0x81,
+        0xA1, 0xE3, 0x81, 0xAF,
+    ];
+    let mut full = header.to_vec();
+    full.extend_from_slice(&body);
+
+    let msg = parse_syslog(&full);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "UTF-8 (MSG-SD/BOM-Detected)");
+}
+
+#[test]
+fn test_rfc5424_with_msg_utf8_label() {
+    let header = b"<13>1 - - - - - [meta charset=\"MSG-UTF8\"] ";
+    let body = "こんにちは".as_bytes();
+    let mut full = header.to_vec();
+    full.extend_from_slice(body);
+
+    let msg = parse_syslog(&full);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "UTF-8 (MSG-SD/BOM-Missing)");
+}
+
+#[test]
+fn test_rfc5424_header_fields() {
+    let msg = parse_syslog(
+        b"<165>1 2026-10-18T12:34:56.123456+09:00 router01 sshd 4242 ID47 - login ok",
+    );
+    assert_eq!(
+        msg.device_timestamp.map(|t| t.to_rfc3339()),
+        Some("2026-10-18T12:34:56.123456+09:00".to_string())
+    );
+    assert_eq!(msg.hostname.as_deref(), Some("router01"));
+    assert_eq!(msg.app_name.as_deref(), Some("sshd"));
+    assert_eq!(msg.procid.as_deref(), Some("4242"));
+    assert_eq!(msg.msgid.as_deref(), Some("ID47"));
+    assert_eq!(msg.tag.as_deref(), Some("sshd"));
+    assert_eq!(msg.content, "login ok");
+}
+
+#[test]
+fn test_rfc5424_nilvalue_and_invalid_header_fields() {
+    // NILVALUE は None、秒の小数 7 桁や長すぎる MSGID は規格外として None
+    let msg = parse_syslog(
+        b"<13>1 2026-10-18T12:34:56.1234567Z - app - ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 - body",
+    );
+    assert_eq!(msg.device_timestamp, None);
+    assert_eq!(msg.hostname, None);
+    assert_eq!(msg.app_name.as_deref(), Some("app"));
+    assert_eq!(msg.procid, None);
+    assert_eq!(msg.msgid, None);
+    assert_eq!(msg.content, "body");
+}
+
+#[test]
+fn test_rfc3164_full_header() {
+    let msg = parse_syslog(b"<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick");
+    let device = msg.device_timestamp.unwrap();
+    assert_eq!(device.format("%m-%d %H:%M:%S").to_string(), "10-11 22:14:15");
+    assert_eq!(msg.hostname.as_deref(), Some("mymachine"));
+    assert_eq!(msg.tag.as_deref(), Some("su"));
+    assert_eq!(msg.pid.as_deref(), Some("230"));
+    assert_eq!(msg.content, "'su root' failed for lonvick");
+}
+
+#[test]
+fn test_rfc3164_single_digit_day_forms() {
+    for raw in [
+        &b"<13>Oct  8 01:02:03 host1 app: padded"[..],
+        &b"<13>Oct 8 01:02:03 host1 app: unpadded"[..],
+    ] {
+        let msg = parse_syslog(raw);
+        assert_eq!(msg.hostname.as_deref(), Some("host1"));
+        assert_eq!(msg.tag.as_deref(), Some("app"));
+        let device = msg.device_timestamp.unwrap();
+        assert_eq!(device.format("%d %H:%M:%S").to_string(), "08 01:02:03");
+    }
+}
+
+#[test]
+fn test_rfc3164_year_and_missing_hostname() {
+    // 年付き・小数秒付きの時刻(Cisco 等)
+    let msg = parse_syslog(b"<189>Oct 18 2026 12:00:00.123 core-sw1 LINK: Interface up");
+    let device = msg.device_timestamp.unwrap();
+    assert_eq!(
+        device.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        "2026-10-18 12:00:00.123"
+    );
+    assert_eq!(msg.hostname.as_deref(), Some("core-sw1"));
+    assert_eq!(msg.tag.as_deref(), Some("LINK"));
+
+    // ホスト名を省略して TAG から始まる送信元
+    let msg = parse_syslog(b"<13>Oct 18 12:00:00 sshd[99]: Accepted publickey");
+    assert_eq!(msg.hostname, None);
+    assert_eq!(msg.tag.as_deref(), Some("sshd"));
+    assert_eq!(msg.pid.as_deref(), Some("99"));
+    assert_eq!(msg.content, "Accepted publickey");
+}
+
+#[test]
+fn test_rfc3164_year_is_taken_from_receive_time() {
+    // 年末に送られ年明けに届いたメッセージは前年とみなす
+    let received_at: DateTime<Utc> = "2026-01-01T12:00:00Z".parse().unwrap();
+    let msg = parse_syslog_at(b"<13>Dec 31 23:59:00 host app: late", received_at);
+    assert_eq!(msg.received_at, received_at);
+    assert_eq!(msg.device_timestamp.unwrap().year(), 2025);
+
+    let msg = parse_syslog_at(b"<13>Jan  1 00:01:00 host app: on time", received_at);
+    assert_eq!(msg.device_timestamp.unwrap().year(), 2026);
+}
+
+#[test]
+fn test_received_at_is_utc_rfc3339() {
+    let msg = parse_syslog(b"<13>1 2026-10-18T12:34:56+09:00 - - - - - x");
+    let json = serde_json::to_value(&msg).unwrap();
+    assert!(json["received_at"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(json["device_timestamp"], "2026-10-18T12:34:56+09:00");
+}
+
+#[test]
+fn test_digit_led_bodies_are_not_rfc5424() {
+    // VERSION は 1〜3 桁(先頭 0 不可)+ SP のときだけ RFC 5424 とみなす
+    let msg = parse_syslog(b"<187>000123: *Mar  1 00:01:02: text");
+    assert_eq!(msg.hostname, None);
+    assert_eq!(msg.app_name, None);
+
+    let msg = parse_syslog(b"<14>1,2026/10/18 12:00:00,0128,SYSTEM");
+    assert_eq!(msg.device_timestamp, None);
+    assert_eq!(msg.content, "1,2026/10/18 12:00:00,0128,SYSTEM");
+}
+
+#[test]
+fn test_rfc3164_without_header_uses_tag_heuristic() {
+    let msg = parse_syslog(b"<13>myapp: hello world");
+    assert_eq!(msg.device_timestamp, None);
+    assert_eq!(msg.hostname, None);
+    assert_eq!(msg.tag.as_deref(), Some("myapp"));
+    assert_eq!(msg.content, "hello world");
+}
+
+#[test]
+fn test_facility_from_pri() {
+    let msg = parse_syslog(b"<0>kernel panic");
+    assert_eq!(msg.facility, Some(Facility::Kern));
+    assert!(matches!(msg.severity, Severity::Emergency));
+
+    let msg = parse_syslog(b"<86>Oct 18 12:00:00 host sudo: session opened");
+    assert_eq!(msg.facility, Some(Facility::Authpriv));
+    assert!(matches!(msg.severity, Severity::Informational));
+
+    let msg = parse_syslog(b"<191>1 - - - - - - debug");
+    assert_eq!(msg.facility, Some(Facility::Local7));
+    assert!(matches!(msg.severity, Severity::Debug));
+    assert!(!msg.invalid_pri);
+}
+
+#[test]
+fn test_out_of_range_pri_is_flagged() {
+    // u8 には収まるが facility 24 以上になる 192〜255 も、4 桁も規格外
+    for raw in [&b"<192>hello"[..], &b"<255>hello"[..], &b"<1000>hello"[..]] {
+        let msg = parse_syslog(raw);
+        assert_eq!(msg.facility, None);
+        assert!(msg.invalid_pri);
+        assert!(matches!(msg.severity, Severity::Informational));
+        assert_eq!(msg.content, "hello");
+    }
+    // PRI 無しは規格外扱いしない
+    let msg = parse_syslog(b"plain text");
+    assert_eq!(msg.facility, None);
+    assert!(!msg.invalid_pri);
+}
+
+#[test]
+fn test_structured_data_elements_and_escapes() {
+    let msg = parse_syslog(
+        br#"<165>1 - host app - - [exampleSDID@32473 iut="3" eventSource="App\"lication" eventID="1011"][origin ip="192.0.2.1" note="a\]b\\c"] body"#,
+    );
+    assert_eq!(
+        msg.structured_data,
+        vec![
+            SdElement {
+                id: "exampleSDID@32473".to_string(),
+                params: vec![
+                    ("iut".to_string(), "3".to_string()),
+                    ("eventSource".to_string(), "App\"lication".to_string()),
+                    ("eventID".to_string(), "1011".to_string()),
+                ],
+            },
+            SdElement {
+                id: "origin".to_string(),
+                params: vec![
+                    ("ip".to_string(), "192.0.2.1".to_string()),
+                    ("note".to_string(), "a]b\\c".to_string()),
+                ],
+            },
+        ]
+    );
+    assert_eq!(msg.content, "body");
+    // 表示用の書き戻しはエスケープを付け直す
+    assert_eq!(
+        msg.structured_data[1].to_string(),
+        r#"[origin ip="192.0.2.1" note="a\]b\\c"]"#
+    );
+}
+
+#[test]
+fn test_structured_data_quoted_bracket_is_not_sd_end() {
+    // 旧実装は値の中の "]" で SD が終わったと誤認していた
+    let msg = parse_syslog(br#"<13>1 - - - - - [meta note="x]y" sequenceId="7"] after"#);
+    assert_eq!(msg.structured_data.len(), 1);
+    assert_eq!(msg.structured_data[0].params[0].1, "x]y");
+    assert_eq!(msg.structured_data[0].params[1].1, "7");
+    assert_eq!(msg.content, "after");
+}
+
+#[test]
+fn test_structured_data_nilvalue() {
+    let msg = parse_syslog(b"<13>1 - - - - - - body");
+    assert!(msg.structured_data.is_empty());
+    assert_eq!(msg.content, "body");
+}
+
+#[test]
+fn test_rfc5424_implicit_utf8_label() {
+    // Server / Portable で別々だったラベルは "UTF-8 (Implicit)" にそろえた
+    let msg = parse_syslog("<13>1 - - - - - - こんにちは".as_bytes());
+    assert_eq!(msg.content, "こんにちは");
+    assert_eq!(msg.encoding, "UTF-8 (Implicit)");
+}
+
+#[test]
+fn test_rfc3164_sjis_fallback() {
+    let sjis_bytes = [0x82, 0xB1, 0x82, 0xF1, 0x82, 0xC9, 0x82, 0xBF, 0x82, 0xCD];
+    let msg = parse_syslog(&sjis_bytes);
+    assert!(msg.content.contains("こんにちは"));
+    assert_eq!(msg.encoding, "Shift_JIS");
+}
@@ -0,0 +1,142 @@
+//! PROXY プロトコルのヘッダの結合テスト。
+
+use std::net::SocketAddr;
+
+use vlt_syslog_core::proxy::{Parse, ProxyError, ProxyHeader, TrustedProxies, parse};
+
+/// `stream` の先頭から、parse が求める数だけ読み足していく。読んだバイト数も返す。
+fn read_header(stream: &[u8]) -> Result<(ProxyHeader, usize), ProxyError> {
+    let mut len = 0;
+    loop {
+        match parse(&stream[..len])? {
+            Parse::Need(n) => {
+                assert!(len + n <= stream.len(), "asked to read past the stream");
+                len += n;
+            }
+            Parse::Done(header) => return Ok((header, len)),
+        }
+    }
+}
+
+fn addr(s: &str) -> Option<SocketAddr> {
+    Some(s.parse().unwrap())
+}
+
+fn v2(command: u8, family: u8, body: &[u8]) -> Vec<u8> {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.push(0x20 | command);
+    header.push(family);
+    header.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    header.extend_from_slice(body);
+    header
+}
+
+#[test]
+fn v1_headers_stop_exactly_before_the_payload() {
+    let stream = b"PROXY TCP4 192.0.2.7 198.51.100.1 40000 514\r\n<13>app: hi\n";
+    let (header, len) = read_header(stream).unwrap();
+    assert_eq!(header.source, addr("192.0.2.7:40000"));
+    assert_eq!(header.destination, addr("198.51.100.1:514"));
+    assert_eq!(&stream[len..], b"<13>app: hi\n");
+
+    let (header, _) = read_header(b"PROXY TCP6 2001:db8::7 2001:db8::1 40000 6514\r\n").unwrap();
+    assert_eq!(header.source, addr("[2001:db8::7]:40000"));
+
+    // 最も短いヘッダでも読み過ぎない
+    let (header, len) = read_header(b"PROXY UNKNOWN\r\nX").unwrap();
+    assert_eq!((header.source, len), (None, 15));
+}
+
+#[test]
+fn v1_malformed_headers_are_rejected() {
+    for bad in [
+        &b"PROXY TCP4 2001:db8::7 192.0.2.1 1 2\r\n"[..],
+        b"PROXY TCP4 192.0.2.7 192.0.2.1 01 2\r\n",
+        b"PROXY TCP4 192.0.2.7 192.0.2.1 70000 2\r\n",
+        b"PROXY TCP4 192.0.2.7 192.0.2.1 1\r\n",
+        b"PROXY TCP4 192.0.2.7 192.0.2.1 1 2\n",
+    ] {
+        assert!(
+            matches!(read_header(bad), Err(ProxyError::Malformed(_))),
+            "{}",
+            String::from_utf8_lossy(bad)
+        );
+    }
+    let long = [b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat();
+    assert_eq!(
+        read_header(&long).unwrap_err(),
+        ProxyError::Malformed("v1 header too long")
+    );
+}
+
+#[test]
+fn v2_inet_inet6_and_local_headers() {
+    let mut body = vec![192, 0, 2, 7, 198, 51, 100, 1];
+    body.extend_from_slice(&40000u16.to_be_bytes());
+    body.extend_from_slice(&514u16.to_be_bytes());
+    // 後ろの TLV は読み飛ばす
+    body.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]);
+    let mut stream = v2(1, 0x11, &body);
+    let header_len = stream.len();
+    stream.extend_from_slice(b"\x16\x03\x01");
+    let (header, len) = read_header(&stream).unwrap();
+    assert_eq!(len, header_len);
+    assert_eq!(header.source, addr("192.0.2.7:40000"));
+    assert_eq!(header.destination, addr("198.51.100.1:514"));
+
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        &"2001:db8::7"
+            .parse::<std::net::Ipv6Addr>()
+            .unwrap()
+            .octets(),
+    );
+    body.extend_from_slice(
+        &"2001:db8::1"
+            .parse::<std::net::Ipv6Addr>()
+            .unwrap()
+            .octets(),
+    );
+    body.extend_from_slice(&40000u16.to_be_bytes());
+    body.extend_from_slice(&6514u16.to_be_bytes());
+    let (header, _) = read_header(&v2(1, 0x21, &body)).unwrap();
+    assert_eq!(header.source, addr("[2001:db8::7]:40000"));
+
+    // LOCAL(ヘルスチェック)はアドレスを持たない
+    let (header, len) = read_header(&v2(0, 0x00, &[])).unwrap();
+    assert_eq!((header.source, len), (None, 16));
+}
+
+#[test]
+fn v2_malformed_headers_are_rejected() {
+    assert_eq!(
+        read_header(&v2(1, 0x11, &[192, 0, 2, 7])).unwrap_err(),
+        ProxyError::Malformed("v2 address length")
+    );
+    let mut wrong_version = v2(1, 0x11, &[0; 12]);
+    wrong_version[12] = 0x11;
+    assert_eq!(
+        read_header(&wrong_version).unwrap_err(),
+        ProxyError::Malformed("v2 version")
+    );
+}
+
+#[test]
+fn connections_without_a_header_are_detected_early() {
+    assert_eq!(parse(b"<13>").unwrap_err(), ProxyError::Missing);
+    assert_eq!(parse(b"\x16\x03\x01").unwrap_err(), ProxyError::Missing);
+    assert_eq!(
+        parse(b"<13>app: hello world").unwrap_err(),
+        ProxyError::Missing
+    );
+}
+
+#[test]
+fn trusted_proxies_accept_addresses_and_cidrs() {
+    let trusted =
+        TrustedProxies::compile(&["10.0.0.0/8".to_string(), "192.0.2.10".to_string()]).unwrap();
+    assert!(trusted.contains("10.1.2.3".parse().unwrap()));
+    assert!(trusted.contains("::ffff:192.0.2.10".parse().unwrap()));
+    assert!(!trusted.contains("192.0.2.11".parse().unwrap()));
+    assert!(TrustedProxies::compile(&["10.0.0.0/33".to_string()]).is_err());
+}
@@ -0,0 +1,64 @@
+//! RELP のフレームの結合テスト。
+
+use vlt_syslog_core::relp::{RelpDecoder, RelpError, RelpFrame, encode_frame, encode_rsp};
+
+#[test]
+fn session_frames_are_split_across_reads() {
+    let offer = b"relp_version=0\nrelp_software=librelp,1.10.0\ncommands=syslog";
+    let mut stream = encode_frame(1, "open", offer);
+    stream.extend_from_slice(b"2 syslog 26 <13>1 - host app - - - a\nb\n");
+    stream.extend_from_slice(b"3 close 0\n");
+
+    let mut decoder = RelpDecoder::new(1024);
+    let mut frames = Vec::new();
+    // 1 バイトずつ届いても同じように切り出せる
+    for b in &stream {
+        decoder.extend(&[*b]);
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            frames.push(frame);
+        }
+    }
+    assert_eq!(
+        frames,
+        [
+            RelpFrame {
+                txnr: 1,
+                command: "open".into(),
+                data: offer.to_vec(),
+            },
+            RelpFrame {
+                txnr: 2,
+                command: "syslog".into(),
+                data: b"<13>1 - host app - - - a\nb".to_vec(),
+            },
+            RelpFrame {
+                txnr: 3,
+                command: "close".into(),
+                data: Vec::new(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn responses_are_encoded() {
+    assert_eq!(encode_rsp(7, "200 OK"), b"7 rsp 6 200 OK\n");
+    assert_eq!(encode_frame(0, "serverclose", b""), b"0 serverclose 0\n");
+}
+
+#[test]
+fn malformed_and_oversized_frames_are_errors() {
+    let error = |input: &[u8]| {
+        let mut decoder = RelpDecoder::new(16);
+        decoder.extend(input);
+        decoder.next_frame().unwrap_err()
+    };
+    assert_eq!(error(b"x syslog 1 a\n"), RelpError::Malformed("TXNR"));
+    assert_eq!(error(b"1 sys-log 1 a\n"), RelpError::Malformed("COMMAND"));
+    assert_eq!(error(b"1 syslog a\n"), RelpError::Malformed("DATALEN"));
+    assert_eq!(error(b"1 syslog 1 ab\n"), RelpError::Malformed("TRAILER"));
+    assert_eq!(
+        error(b"1 syslog 100 "),
+        RelpError::TooLarge { len: 100, max: 16 }
+    );
+}
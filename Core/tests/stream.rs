@@ -0,0 +1,64 @@
+//! 配信ストリーム(JSON Lines)の結合テスト。
+
+use vlt_syslog_core::stream::{decode_line, encode_line};
+use vlt_syslog_core::{Facility, Severity, parse_syslog};
+
+#[test]
+fn round_trips_parsed_message() {
+    let msg = parse_syslog(br#"<165>1 2026-10-18T12:00:00Z host app 1 ID [origin ip="192.0.2.1"] hi"#);
+    let line = encode_line(&msg).unwrap();
+    assert!(!line.contains('\n'));
+
+    let back = decode_line(&line).unwrap();
+    assert_eq!(back.facility, Some(Facility::Local4));
+    assert!(matches!(back.severity, Severity::Notice));
+    assert_eq!(back.hostname.as_deref(), Some("host"));
+    assert_eq!(back.structured_data, msg.structured_data);
+    assert_eq!(back.received_at, msg.received_at);
+    assert_eq!(back.device_timestamp, msg.device_timestamp);
+    assert_eq!(back.content, "hi");
+}
+
+#[test]
+fn decodes_lines_from_older_servers() {
+    // facility や RFC 5424 ヘッダー項目を送らない旧バージョンの行も読めること
+    let line = r#"{"severity":"Error","timestamp":"2026-06-29T00:00:00.000","hostname":null,"tag":"myapp","content":"こんにちは syslog","raw":"00","encoding":"UTF-8"}"#;
+    let msg = decode_line(&format!("{line}\r\n")).unwrap();
+    assert_eq!(msg.content, "こんにちは syslog");
+    assert_eq!(msg.facility, None);
+    assert!(msg.structured_data.is_empty());
+    assert_eq!(msg.raw, [0x00]);
+}
+
+#[test]
+fn raw_bytes_travel_as_base64() {
+    let raw: &[u8] = b"<13>app: \x83\x65\x83\x58\x83\x67";
+    let msg = parse_syslog(raw);
+    assert_eq!(msg.raw, raw);
+    assert_eq!(msg.raw_hex(), "3c31333e6170703a20836583588367");
+
+    let line = encode_line(&msg).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["raw_b64"], "PDEzPmFwcDogg2WDWINn");
+    assert!(json.get("raw").is_none());
+    assert_eq!(decode_line(&line).unwrap().raw, raw);
+
+    // 生バイト列を省いた行も読める
+    let mut without = msg.clone();
+    without.raw.clear();
+    let line = encode_line(&without).unwrap();
+    assert!(!line.contains("raw_b64"));
+    assert!(decode_line(&line).unwrap().raw.is_empty());
+}
+
+#[test]
+fn dialect_fields_are_carried_in_the_stream() {
+    let msg = parse_syslog(b"<134>CEF:0|Vendor|Product|1.0|100|Port scan|7|src=10.0.0.1 dpt=22");
+    let json: serde_json::Value = serde_json::from_str(&encode_line(&msg).unwrap()).unwrap();
+    assert_eq!(json["dialect"], "cef");
+    assert_eq!(json["fields"]["src"], "10.0.0.1");
+    assert_eq!(json["fields"]["signature_id"], "100");
+
+    let back = decode_line(&encode_line(&msg).unwrap()).unwrap();
+    assert_eq!(back.fields, msg.fields);
+}
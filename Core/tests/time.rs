@@ -0,0 +1,24 @@
+//! 表示タイムゾーンの結合テスト。
+
+use chrono::{DateTime, Utc};
+use vlt_syslog_core::time::DisplayZone;
+
+#[test]
+fn parses_and_prints_zone_names() {
+    for name in ["Local", "UTC", "Asia/Tokyo", "America/New_York"] {
+        let zone: DisplayZone = name.parse().unwrap();
+        assert_eq!(zone.to_string(), name);
+    }
+    assert_eq!("".parse::<DisplayZone>(), Ok(DisplayZone::Local));
+    assert_eq!("utc".parse::<DisplayZone>(), Ok(DisplayZone::Utc));
+    assert!("Mars/Olympus_Mons".parse::<DisplayZone>().is_err());
+}
+
+#[test]
+fn formats_with_offset_in_the_chosen_zone() {
+    let t: DateTime<Utc> = "2026-10-18T03:04:05.678Z".parse().unwrap();
+    assert_eq!(DisplayZone::Utc.format(&t), "2026-10-18 03:04:05.678 +00:00");
+
+    let tokyo: DisplayZone = "Asia/Tokyo".parse().unwrap();
+    assert_eq!(tokyo.format(&t), "2026-10-18 12:04:05.678 +09:00");
+}
@@ -45,8 +45,10 @@ fn main() {
     let mut last_bar: Option<egui::Rect> = None;
     let mut last_text: Option<(egui::Rect, f32)> = None;
     for _ in 0..4 {
-        let mut input = egui::RawInput::default();
-        input.screen_rect = Some(egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(800.0, 600.0)));
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(800.0, 600.0))),
+            ..Default::default()
+        };
         let output = ctx.run(input, |ctx| {
             egui::Window::new(egui::RichText::new("環境設定").size(11.0).strong())
                 .collapsible(false)
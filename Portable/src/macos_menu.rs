@@ -7,7 +7,7 @@
 //!
 //! ## アクションの橋渡し
 //!
-//! 「環境設定」「ログフォルダを開く」「コピー/カット/ペースト/全選択/取り消す/やり直す」は
+//! 「環境設定」「ログフォルダを開く」「キャプチャを開く」「コピー/カット/ペースト/全選択/取り消す/やり直す」は
 //! egui 側の状態やテキスト編集に作用させる必要がある。AppKit のメニュー項目は別スレッド
 //! 文脈ではなくメインスレッドのアクションとして発火するので、ここでは「フラグを立てるだけ」に
 //! 留め、egui の update ループ側が [`drain_requests`] でフラグを回収して実処理を行う。
@@ -41,6 +41,10 @@ pub enum MenuRequest {
     Preferences,
     /// ログ保存フォルダを Finder で開く。
     OpenLogs,
+    /// キャプチャ(pcap / pcapng)を選んで取り込む。
+    OpenCapture,
+    /// キャプチャを選んで元の間隔で再生する。
+    ReplayCapture,
     Copy,
     Cut,
     Paste,
@@ -79,6 +83,12 @@ extern "C" fn act_preferences(_: &Object, _: Sel, _: id) {
 extern "C" fn act_open_logs(_: &Object, _: Sel, _: id) {
     push_request(MenuRequest::OpenLogs);
 }
+extern "C" fn act_open_capture(_: &Object, _: Sel, _: id) {
+    push_request(MenuRequest::OpenCapture);
+}
+extern "C" fn act_replay_capture(_: &Object, _: Sel, _: id) {
+    push_request(MenuRequest::ReplayCapture);
+}
 extern "C" fn act_copy(_: &Object, _: Sel, _: id) {
     push_request(MenuRequest::Copy);
 }
@@ -115,6 +125,14 @@ fn shared_target() -> id {
                 sel!(vltOpenLogs:),
                 act_open_logs as extern "C" fn(&Object, Sel, id),
             );
+            decl.add_method(
+                sel!(vltOpenCapture:),
+                act_open_capture as extern "C" fn(&Object, Sel, id),
+            );
+            decl.add_method(
+                sel!(vltReplayCapture:),
+                act_replay_capture as extern "C" fn(&Object, Sel, id),
+            );
             decl.add_method(sel!(vltCopy:), act_copy as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(vltCut:), act_cut as extern "C" fn(&Object, Sel, id));
             decl.add_method(sel!(vltPaste:), act_paste as extern "C" fn(&Object, Sel, id));
@@ -229,6 +247,23 @@ pub fn install() {
             nil,
         );
 
+        // --- ファイルメニュー ---
+        let file_menu = add_submenu(main_menu, "ファイル");
+        add_item(
+            file_menu,
+            "キャプチャを開く…",
+            sel!(vltOpenCapture:),
+            "o",
+            target,
+        );
+        add_item(
+            file_menu,
+            "キャプチャを元の間隔で再生…",
+            sel!(vltReplayCapture:),
+            "",
+            target,
+        );
+
         // --- 編集メニュー ---
         let edit_menu = add_submenu(main_menu, "編集");
         add_item(edit_menu, "取り消す", sel!(vltUndo:), "z", target);
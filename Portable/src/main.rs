@@ -3,7 +3,6 @@
     windows_subsystem = "windows"
 )]
 
-mod parser;
 mod platform;
 mod settings;
 
@@ -11,7 +10,14 @@ mod settings;
 mod macos_menu;
 
 use eframe::egui;
-use parser::{Severity, SyslogMessage};
+use vlt_syslog_core::acl::AccessList;
+use vlt_syslog_core::capture::{self, DEFAULT_PORTS};
+use vlt_syslog_core::filter::MessageFilter;
+use vlt_syslog_core::names::Resolver;
+use vlt_syslog_core::time::DisplayZone;
+use vlt_syslog_core::encoding::EncodingRules;
+use vlt_syslog_core::listen::{self, DatagramBuf};
+use vlt_syslog_core::{Facility, ParseOptions, Severity, SyslogMessage, parse_syslog_with};
 use std::io::Write;
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc;
@@ -60,8 +66,12 @@ fn key_event(key: egui::Key, shift: bool) -> egui::Event {
 struct SyslogApp {
     logs: Vec<SyslogMessage>,
     receiver: mpsc::Receiver<SyslogMessage>,
+    // キャプチャの取り込みを受信と同じ経路(表示・ログファイル)へ流すための送り口
+    sender: mpsc::Sender<SyslogMessage>,
     auto_scroll: bool,
     filter: String,
+    // facility での絞り込み(None = すべて)
+    facility_filter: Option<Facility>,
     log_file: Option<std::fs::File>,
     // bind 状態と、GUI からポートを選び直すための経路
     bind_state: BindState,
@@ -73,9 +83,12 @@ struct SyslogApp {
     show_preferences: bool,
     pref_port: String,
     pref_log_dir: String,
+    pref_display_tz: String,
     pref_error: Option<String>,
     pref_saved: bool,
     effective_log_dir: std::path::PathBuf,
+    // 時刻列の表示タイムゾーン(環境設定で変更)
+    display_zone: DisplayZone,
     // メニュー(ネイティブ/アプリ内とも)から積まれた、次の描画で egui 入力へ注入する編集イベント
     pending_events: Vec<egui::Event>,
     // 直近でフォーカスされていたテキスト欄の id。
@@ -91,6 +104,7 @@ impl SyslogApp {
     fn new(
         cc: &eframe::CreationContext<'_>,
         receiver: mpsc::Receiver<SyslogMessage>,
+        sender: mpsc::Sender<SyslogMessage>,
         bind_status_rx: mpsc::Receiver<BindState>,
         bind_tx: mpsc::Sender<u16>,
     ) -> Self {
@@ -157,8 +171,10 @@ impl SyslogApp {
         Self {
             logs: Vec::new(),
             receiver,
+            sender,
             auto_scroll: true,
             filter: String::new(),
+            facility_filter: None,
             log_file,
             bind_state: BindState::Connecting,
             bind_status_rx,
@@ -169,15 +185,30 @@ impl SyslogApp {
             show_preferences: false,
             pref_port: cfg.bind_port.to_string(),
             pref_log_dir: log_dir.display().to_string(),
+            pref_display_tz: cfg.display_tz.clone(),
             pref_error: None,
             pref_saved: false,
             effective_log_dir: log_dir,
+            display_zone: settings::display_zone(&cfg),
             pending_events: Vec::new(),
             last_text_focus: None,
             show_about: false,
         }
     }
 
+    /// pcap / pcapng を選ばせ、取り出した syslog を受信したものと同じように流す。
+    /// `pace` ならキャプチャの時刻の間隔どおりに流す。
+    /// [`capture::MAX_FILE_SIZE`] を超えるファイルは読まずにエラーを出す。
+    fn open_capture(&self, pace: bool) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("pcap / pcapng", &["pcap", "pcapng", "cap"])
+            .pick_file()
+        else {
+            return;
+        };
+        tokio::spawn(import_capture(path, pace, self.sender.clone()));
+    }
+
     /// 編集メニューの操作を「次フレームで egui 入力へ注入するイベント」として積む。
     /// 注入は [`eframe::App::update`] の冒頭で行い、同フレーム内のフォーカス中ウィジェットに効かせる。
     fn queue_edit(&mut self, action: EditAction) {
@@ -216,6 +247,15 @@ impl SyslogApp {
                         ui.close_menu();
                     }
                     ui.separator();
+                    if ui.button("キャプチャを開く…").clicked() {
+                        ui.close_menu();
+                        self.open_capture(false);
+                    }
+                    if ui.button("キャプチャを元の間隔で再生…").clicked() {
+                        ui.close_menu();
+                        self.open_capture(true);
+                    }
+                    ui.separator();
                     if ui.button("終了").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
@@ -297,9 +337,24 @@ impl SyslogApp {
             }
         };
 
+        let display_zone = match self.pref_display_tz.parse::<DisplayZone>() {
+            Ok(z) => z,
+            Err(_) => {
+                self.pref_error = Some(
+                    "タイムゾーンは Local / UTC / Asia/Tokyo のような IANA 名で指定してください"
+                        .to_string(),
+                );
+                self.pref_saved = false;
+                return;
+            }
+        };
+
         let cfg = settings::Settings {
             bind_port: port,
             log_dir: self.pref_log_dir.trim().to_string(),
+            display_tz: display_zone.to_string(),
+            // GUI で編集しない項目(エンコーディングルール)はファイルの内容を残す
+            ..settings::load()
         };
         if let Err(e) = settings::save(&cfg) {
             self.pref_error = Some(format!("設定の保存に失敗しました: {}", e));
@@ -308,6 +363,8 @@ impl SyslogApp {
         }
         self.pref_error = None;
         self.pref_saved = true;
+        self.pref_display_tz = cfg.display_tz.clone();
+        self.display_zone = display_zone;
 
         // ログ保存先を新しいディレクトリへ切り替え(GUI 側のログファイルを開き直す)
         let new_log_dir = settings::effective_log_dir(&cfg);
@@ -365,6 +422,14 @@ impl SyslogApp {
                             }
                         });
                         ui.end_row();
+
+                        ui.label("表示タイムゾーン:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.pref_display_tz)
+                                .hint_text("Local / UTC / Asia/Tokyo")
+                                .desired_width(180.0),
+                        );
+                        ui.end_row();
                     });
 
                 ui.add_space(6.0);
@@ -418,7 +483,7 @@ impl SyslogApp {
                     ui.label(
                         "考えられる原因: 同じポートを別プロセスが使用中 / Linux で特権ポート(1024未満)に \
                          root 権限がない / 特定アドレスへの特権ポート bind に権限がない、など。\
-                         (macOS では 0.0.0.0 への特権ポート bind は root 不要です。)\
+                         (macOS では全アドレス([::] / 0.0.0.0)への特権ポート bind は root 不要です。)\
                          下の欄で別のポートを指定して再試行できますが、送信側(機器・ルータ等)も\
                          そのポートに向ける必要があります。常駐で確実に動かしたい場合は Server 版もご利用ください。",
                     );
@@ -458,9 +523,10 @@ impl eframe::App for SyslogApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         while let Ok(log) = self.receiver.try_recv() {
             if let Some(ref mut file) = self.log_file {
+                // ファイルには表示設定に関係なく UTC の RFC 3339 で書く(後から突き合わせやすい)
                 let log_line = format!(
                     "[{}] [{:?}] [{}] {}\n",
-                    log.timestamp,
+                    log.received_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
                     log.severity,
                     log.tag.as_deref().unwrap_or("-"),
                     log.content
@@ -498,6 +564,8 @@ impl eframe::App for SyslogApp {
                     macos_menu::MenuRequest::OpenLogs => {
                         let _ = platform::open_in_file_manager(&self.effective_log_dir);
                     }
+                    macos_menu::MenuRequest::OpenCapture => self.open_capture(false),
+                    macos_menu::MenuRequest::ReplayCapture => self.open_capture(true),
                     macos_menu::MenuRequest::Copy => self.queue_edit(EditAction::Copy),
                     macos_menu::MenuRequest::Cut => self.queue_edit(EditAction::Cut),
                     macos_menu::MenuRequest::Paste => self.queue_edit(EditAction::Paste),
@@ -545,27 +613,35 @@ impl eframe::App for SyslogApp {
             ui.add_space(5.0);
             ui.horizontal(|ui| {
                 ui.label("Filter:");
-                ui.text_edit_singleline(&mut self.filter);
+                ui.text_edit_singleline(&mut self.filter).on_hover_text(
+                    "本文・タグ・ホスト名・送信元の名前を検索します。fields.user=alice のように書くと項目の値、listener=名前 で受けた待ち受け口、src=IP / CIDR / 名前 で送信元で絞り込めます",
+                );
                 if ui.button("x").clicked() {
                     self.filter.clear();
                 }
+                ui.label("Facility:");
+                egui::ComboBox::from_id_source("facility_filter")
+                    .selected_text(self.facility_filter.map_or("All", |f| f.name()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.facility_filter, None, "All");
+                        for f in Facility::ALL {
+                            ui.selectable_value(&mut self.facility_filter, Some(f), f.name());
+                        }
+                    });
             });
 
             ui.add_space(5.0);
             ui.separator();
             ui.add_space(5.0);
 
-            let filter = self.filter.to_lowercase();
+            // "fields.user=alice" のような項目指定も書ける(書式は vlt_syslog_core::filter)
+            let filter = MessageFilter::parse(&self.filter);
+            let facility_filter = self.facility_filter;
             let filtered_logs: Vec<_> = self
                 .logs
                 .iter()
-                .filter(|l| {
-                    filter.is_empty()
-                        || l.content.to_lowercase().contains(&filter)
-                        || l.tag
-                            .as_ref()
-                            .map_or(false, |t| t.to_lowercase().contains(&filter))
-                })
+                .filter(|l| facility_filter.is_none() || l.facility == facility_filter)
+                .filter(|l| filter.matches(l))
                 .collect();
 
             let row_height = 24.0;
@@ -573,13 +649,16 @@ impl eframe::App for SyslogApp {
                 .auto_shrink([false; 2])
                 .stick_to_bottom(self.auto_scroll)
                 .show_rows(ui, row_height, filtered_logs.len(), |ui, row_range| {
-                    egui::Grid::new("log_grid_v4")
+                    egui::Grid::new("log_grid_v5")
                         .striped(true)
-                        .num_columns(5)
+                        .num_columns(8)
                         .spacing([15.0, 8.0])
                         .show(ui, |ui| {
                             ui.strong("Time");
+                            ui.strong("Host");
+                            ui.strong("Source");
                             ui.strong("Tag");
+                            ui.strong("Facility");
                             ui.strong("Severity");
                             ui.strong("Enc");
                             ui.strong("Message");
@@ -590,8 +669,34 @@ impl eframe::App for SyslogApp {
                                 let (r, g, b) = log.severity.color();
                                 let color = egui::Color32::from_rgb(r, g, b);
 
-                                ui.label(&log.timestamp);
+                                let time_label =
+                                    ui.label(self.display_zone.format(&log.received_at));
+                                // 送信元機器の時刻はホバーで(受信時刻とずれていれば機器の時計が怪しい)
+                                if let Some(device) = &log.device_timestamp {
+                                    time_label.on_hover_text(format!(
+                                        "機器時刻: {}",
+                                        self.display_zone.format(device)
+                                    ));
+                                }
+                                ui.label(log.hostname.as_deref().unwrap_or("-"));
+                                // 送信元は名前が引けていれば名前、無ければ IP。名前のときは IP をホバーで
+                                let source_label = ui.label(
+                                    log.source_display().unwrap_or_else(|| "-".to_string()),
+                                );
+                                if log.source_name.is_some()
+                                    && let Some(addr) = log.source_addr
+                                {
+                                    source_label.on_hover_text(addr.to_string());
+                                }
                                 ui.label(log.tag.as_deref().unwrap_or("-"));
+                                if log.invalid_pri {
+                                    ui.label(
+                                        egui::RichText::new("invalid PRI")
+                                            .color(egui::Color32::from_rgb(240, 90, 90)),
+                                    );
+                                } else {
+                                    ui.label(log.facility.map_or("-", |f| f.name()));
+                                }
                                 ui.label(
                                     egui::RichText::new(format!("{:?}", log.severity))
                                         .color(color)
@@ -599,8 +704,58 @@ impl eframe::App for SyslogApp {
                                 );
                                 ui.label(egui::RichText::new(&log.encoding).weak());
 
-                                let message_label =
+                                let mut message_label =
                                     ui.label(egui::RichText::new(&log.content).color(color));
+                                // STRUCTURED-DATA はホバーで RFC 5424 の表記のまま見せる。
+                                // ベンダー方言で取り出した項目・パース診断・受信経路があれば、その下に 1 行ずつ並べる。
+                                let sd_text: String =
+                                    log.structured_data.iter().map(|e| e.to_string()).collect();
+                                let mut hover = sd_text.clone();
+                                if let Some(dialect) = &log.dialect {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str(&format!("[{dialect}]"));
+                                    for (key, value) in &log.fields {
+                                        hover.push_str(&format!("\n{key} = {value}"));
+                                    }
+                                }
+                                // パース時に救済した規格外の箇所(種類@バイト位置)
+                                if !log.diagnostics.is_empty() {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str("[diagnostics]");
+                                    for d in &log.diagnostics {
+                                        hover.push_str(&format!("\n{d}"));
+                                    }
+                                }
+                                // 受けた待ち受け口と、TLS のクライアント証明書の Subject・Unix ソケットの送信元プロセス・切り詰めの有無
+                                if log.listener.is_some()
+                                    || log.tls_subject.is_some()
+                                    || log.peer_cred.is_some()
+                                    || log.truncated
+                                {
+                                    if !hover.is_empty() {
+                                        hover.push('\n');
+                                    }
+                                    hover.push_str("[received]");
+                                    if let Some(listener) = &log.listener {
+                                        hover.push_str(&format!("\nlistener = {listener}"));
+                                    }
+                                    if let Some(subject) = &log.tls_subject {
+                                        hover.push_str(&format!("\ntls_subject = {subject}"));
+                                    }
+                                    if let Some(cred) = &log.peer_cred {
+                                        hover.push_str(&format!("\npeer_cred = {cred}"));
+                                    }
+                                    if log.truncated {
+                                        hover.push_str("\ntruncated = true(上限を超えたため切り詰め)");
+                                    }
+                                }
+                                if !hover.is_empty() {
+                                    message_label = message_label.on_hover_text(&hover);
+                                }
 
                                 // コンテキストメニュー（右クリック）
                                 message_label.context_menu(|ui| {
@@ -608,8 +763,15 @@ impl eframe::App for SyslogApp {
                                         ui.output_mut(|o| o.copied_text = log.content.clone());
                                         ui.close_menu();
                                     }
-                                    if ui.button("Copy as Hex").clicked() {
-                                        ui.output_mut(|o| o.copied_text = log.raw.clone());
+                                    if !sd_text.is_empty()
+                                        && ui.button("Copy Structured Data").clicked()
+                                    {
+                                        ui.output_mut(|o| o.copied_text = sd_text.clone());
+                                        ui.close_menu();
+                                    }
+                                    // 生バイト列を省いて配信された行(や内部メッセージ)には出さない
+                                    if !log.raw.is_empty() && ui.button("Copy as Hex").clicked() {
+                                        ui.output_mut(|o| o.copied_text = log.raw_hex());
                                         ui.close_menu();
                                     }
                                 });
@@ -683,17 +845,7 @@ fn load_cjk_font(fonts: &mut egui::FontDefinitions) -> bool {
 
 /// GUI に表示する内部ステータス（起動・bind 失敗など）用のメッセージを生成する。
 fn system_message(content: String, severity: Severity) -> SyslogMessage {
-    SyslogMessage {
-        severity,
-        timestamp: chrono::Local::now()
-            .format("%Y-%m-%dT%H:%M:%S%.3fZ")
-            .to_string(),
-        hostname: None,
-        tag: Some("vlt-syslogd".to_string()),
-        content,
-        raw: String::new(),
-        encoding: "system".to_string(),
-    }
+    SyslogMessage::internal(severity, "vlt-syslogd", content)
 }
 
 #[tokio::main]
@@ -719,6 +871,8 @@ async fn main() -> eframe::Result<()> {
     let (status_tx, status_rx) = mpsc::channel::<BindState>(8);
     let (bind_tx, bind_rx) = mpsc::channel::<u16>(8);
 
+    let import_tx = tx.clone();
+    tokio::spawn(run_extra_listeners(tx.clone()));
     tokio::spawn(run_socket_manager(tx, bind_rx, status_tx));
 
     let mut viewport = egui::ViewportBuilder::default()
@@ -745,13 +899,13 @@ async fn main() -> eframe::Result<()> {
     eframe::run_native(
         "vlt-syslogd",
         native_options,
-        Box::new(|cc| Box::new(SyslogApp::new(cc, rx, status_rx, bind_tx))),
+        Box::new(|cc| Box::new(SyslogApp::new(cc, rx, import_tx, status_rx, bind_tx))),
     )
 }
 
 /// UDP の待ち受けを管理する。bind に失敗したら GUI からの新ポートを待って再試行する。
 ///
-/// - 最初の試行先は環境変数 `VLT_SYSLOGD_BIND`(無ければ標準 514)。
+/// - 最初の試行先は環境変数 `VLT_SYSLOGD_BIND`(無ければ `[::]` の標準 514。IPv4 も受ける)。
 /// - 成功したら状態を Bound にし、受信ループへ移る(以後は戻らない)。
 /// - 失敗したら状態を Failed にし、GUI が選んだポートを受け取って再 bind する。
 async fn run_socket_manager(
@@ -761,10 +915,10 @@ async fn run_socket_manager(
 ) {
     // 初期ポートは 環境変数 VLT_SYSLOGD_BIND > 設定ファイルの bind_port(既定 514)
     let mut addr = std::env::var("VLT_SYSLOGD_BIND")
-        .unwrap_or_else(|_| format!("0.0.0.0:{}", settings::load().bind_port));
+        .unwrap_or_else(|_| format!("[::]:{}", settings::load().bind_port));
 
     loop {
-        match UdpSocket::bind(&addr).await {
+        match bind_udp(&addr) {
             Ok(socket) => {
                 let _ = status_tx.send(BindState::Bound).await;
                 let _ = tx
@@ -776,10 +930,10 @@ async fn run_socket_manager(
                 // 受信ループを回しつつ、環境設定からの再 bind 要求も待つ。
                 // 要求が来たら recv_loop は drop され(ソケットを閉じ)、新アドレスで張り直す。
                 tokio::select! {
-                    _ = recv_loop(socket, &tx) => return,
+                    _ = recv_loop(socket, format!("udp://{}", addr), &tx) => return,
                     maybe = bind_rx.recv() => match maybe {
                         Some(port) => {
-                            addr = format!("0.0.0.0:{}", port);
+                            addr = format!("[::]:{}", port);
                             continue;
                         }
                         None => return,
@@ -789,13 +943,13 @@ async fn run_socket_manager(
             Err(e) => {
                 // bind 失敗の主因は OS で異なる:
                 //   - Windows : ポート使用中 or ファイアウォール/権限
-                //   - macOS   : Mojave 以降 0.0.0.0 への特権ポート bind は root 不要。
+                //   - macOS   : Mojave 以降 全アドレス([::] / 0.0.0.0)への特権ポート bind は root 不要。
                 //               失敗するのは「使用中」か「特定アドレスへの特権ポート bind」
                 //   - Linux   : 1024 未満は root / CAP_NET_BIND_SERVICE が必要
                 let hint = if cfg!(target_os = "windows") {
                     "別プロセスが使用中か、ファイアウォール/権限の問題です"
                 } else if cfg!(target_os = "macos") {
-                    "ポートが使用中の可能性があります(macOS は 0.0.0.0 への特権ポート bind に root 不要)。GUI で別ポートを指定できます"
+                    "ポートが使用中の可能性があります(macOS は全アドレスへの特権ポート bind に root 不要)。GUI で別ポートを指定できます"
                 } else {
                     "ポート使用中、または特権ポート(1024未満)に root/CAP_NET_BIND_SERVICE が必要です。GUI で別ポート指定か sudo 起動を"
                 };
@@ -815,7 +969,7 @@ async fn run_socket_manager(
 
                 // GUI がポートを指定してくるまで待つ。GUI が閉じてチャネルが切れたら終了。
                 match bind_rx.recv().await {
-                    Some(port) => addr = format!("0.0.0.0:{}", port),
+                    Some(port) => addr = format!("[::]:{}", port),
                     None => return,
                 }
             }
@@ -823,10 +977,43 @@ async fn run_socket_manager(
     }
 }
 
+/// `[::]` はデュアルスタックで開く(`listen::udp_socket`)。
+fn bind_udp(addr: &str) -> std::io::Result<UdpSocket> {
+    UdpSocket::from_std(listen::udp_socket(addr)?)
+}
+
+/// 設定ファイルの追加の待ち受け(`[[listeners]]`)を開く。GUI からの再 bind はしない。
+async fn run_extra_listeners(tx: mpsc::Sender<SyslogMessage>) {
+    for listener in settings::load().listeners {
+        match bind_udp(&listener.addr) {
+            Ok(socket) => {
+                let _ = tx
+                    .send(system_message(
+                        format!("Listening on {} (UDP)", listener.addr),
+                        Severity::Notice,
+                    ))
+                    .await;
+                let tx = tx.clone();
+                tokio::spawn(async move { recv_loop(socket, listener.name(), &tx).await });
+            }
+            Err(e) => {
+                let _ = tx
+                    .send(system_message(
+                        format!("Failed to bind {}: {}", listener.addr, e),
+                        Severity::Error,
+                    ))
+                    .await;
+            }
+        }
+    }
+}
+
 /// 待ち受け成功後の受信ループ。受け取ったパケットを生ログに残し、パースして GUI へ送る。
-async fn recv_loop(socket: UdpSocket, tx: &mpsc::Sender<SyslogMessage>) {
+/// `listener` は受信したメッセージに付ける待ち受け口の名前。
+async fn recv_loop(socket: UdpSocket, listener: String, tx: &mpsc::Sender<SyslogMessage>) {
     // デバッグ用生データ保存ファイルの準備(保存先は設定の実効ログディレクトリに従う)
-    let log_dir = settings::effective_log_dir(&settings::load());
+    let cfg = settings::load();
+    let log_dir = settings::effective_log_dir(&cfg);
     let _ = std::fs::create_dir_all(&log_dir);
     let mut debug_file = std::fs::OpenOptions::new()
         .create(true)
@@ -834,21 +1021,143 @@ async fn recv_loop(socket: UdpSocket, tx: &mpsc::Sender<SyslogMessage>) {
         .open(log_dir.join("debug_raw.log"))
         .ok();
 
-    let mut buf = [0u8; 8192];
+    // 送信元ごとのエンコーディングルール。誤りがあれば知らせてルール無しで受信を続ける。
+    let encoding_rules = match EncodingRules::compile(&cfg.encoding_rules) {
+        Ok(rules) => rules,
+        Err(e) => {
+            let _ = tx
+                .send(system_message(
+                    format!("Ignoring encoding_rules: {}", e),
+                    Severity::Warning,
+                ))
+                .await;
+            EncodingRules::empty()
+        }
+    };
+
+    // 送信元のアクセス制御。誤りがあれば知らせて、ネットワークからの受信をすべて拒否する。
+    let acl = match AccessList::compile(&cfg.acl) {
+        Ok(acl) => Some(acl),
+        Err(e) => {
+            let _ = tx
+                .send(system_message(
+                    format!("Invalid acl; rejecting all sources: {}", e),
+                    Severity::Error,
+                ))
+                .await;
+            None
+        }
+    };
+
+    // 送信元の名前解決。hosts ファイルが読めなければ知らせて逆引きだけで動かす。
+    let resolver = match &cfg.resolve {
+        Some(resolve) => Some(match Resolver::from_config(resolve) {
+            Ok(resolver) => resolver,
+            Err(e) => {
+                let _ = tx
+                    .send(system_message(format!("Ignoring {}", e), Severity::Warning))
+                    .await;
+                Resolver::new(
+                    Default::default(),
+                    resolve.reverse_dns,
+                    std::time::Duration::from_secs(resolve.ttl_secs),
+                )
+            }
+        }),
+        None => None,
+    };
+
+    let mut buf = DatagramBuf::new(cfg.max_message_size);
     loop {
-        if let Ok((size, src)) = socket.recv_from(&mut buf).await {
-            let raw_msg = &buf[..size];
+        if let Ok((size, src)) = socket.recv_from(buf.as_mut_slice()).await {
+            // デュアルスタックでは IPv4 の送信元が ::ffff:a.b.c.d で届くので IPv4 に戻す
+            let src = std::net::SocketAddr::new(src.ip().to_canonical(), src.port());
+            // 拒否する送信元はパースも生データ保存もしない
+            if !acl.as_ref().is_some_and(|acl| acl.is_allowed(src.ip(), &listener)) {
+                continue;
+            }
+            let (raw_msg, truncated) = buf.received(size);
 
             // 生データのHEXダンプを保存
             if let Some(ref mut file) = debug_file {
-                let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+                let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f %:z");
                 let line = format!("[{}] [src:{}] raw:{}\n", timestamp, src, hex::encode(raw_msg));
                 let _ = file.write_all(line.as_bytes());
                 let _ = file.flush();
             }
 
-            let parsed = parser::parse_syslog(raw_msg);
+            let mut parsed = parse_syslog_with(
+                raw_msg,
+                &ParseOptions {
+                    source: Some(src.ip()),
+                    encoding_rules: &encoding_rules,
+                    ..ParseOptions::default()
+                },
+            );
+            parsed.listener = Some(listener.clone());
+            parsed.truncated = truncated;
+            parsed.source_addr = Some(src.ip());
+            parsed.source_name = resolver.as_ref().and_then(|r| r.name(src.ip()));
             let _ = tx.send(parsed).await;
         }
     }
 }
+
+/// キャプチャファイルから取り出した syslog を、元の受信時刻・送信元のまま GUI へ送る。
+/// 待ち受け口の名前は "import:ファイル名" にする。アクセス制御(acl)は通さない。
+async fn import_capture(path: std::path::PathBuf, pace: bool, tx: mpsc::Sender<SyslogMessage>) {
+    let file = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |f| f.to_string_lossy().into_owned(),
+    );
+    let read_path = path.clone();
+    let messages = match tokio::task::spawn_blocking(move || capture::read_file(&read_path)).await {
+        Ok(Ok(data)) => capture::read_messages(&data, &DEFAULT_PORTS).map_err(|e| e.to_string()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let messages = match messages {
+        Ok(messages) => messages,
+        Err(e) => {
+            let _ = tx
+                .send(system_message(
+                    format!("Failed to import {}: {}", file, e),
+                    Severity::Error,
+                ))
+                .await;
+            return;
+        }
+    };
+    let _ = tx
+        .send(system_message(
+            format!("Importing {} messages from {}", messages.len(), file),
+            Severity::Notice,
+        ))
+        .await;
+
+    let encoding_rules = EncodingRules::compile(&settings::load().encoding_rules)
+        .unwrap_or_else(|_| EncodingRules::empty());
+    let started = tokio::time::Instant::now();
+    let first = messages.first().map(|m| m.timestamp);
+    for message in messages {
+        if pace && let Some(first) = first {
+            let offset = (message.timestamp - first).to_std().unwrap_or_default();
+            tokio::time::sleep_until(started + offset).await;
+        }
+        let src = message.source.ip();
+        let mut parsed = parse_syslog_with(
+            &message.raw,
+            &ParseOptions {
+                received_at: message.timestamp,
+                source: Some(src),
+                encoding_rules: &encoding_rules,
+                ..ParseOptions::default()
+            },
+        );
+        parsed.listener = Some(format!("import:{file}"));
+        parsed.source_addr = Some(src);
+        if tx.send(parsed).await.is_err() {
+            return;
+        }
+    }
+}
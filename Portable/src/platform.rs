@@ -142,6 +142,8 @@ mod tests {
     /// 既定(feature なし = App 版)では OS 標準のユーザーデータ領域に落ちる。
     #[test]
     #[cfg(not(feature = "portable"))]
+    // OS ごとの期待値を並べて書くため、同じ中身の分岐をまとめない
+    #[allow(clippy::if_same_then_else)]
     fn app_build_uses_user_data_dir() {
         let _guard = ENV_LOCK.lock().unwrap();
         unsafe { std::env::remove_var("VLT_SYSLOGD_DATA_DIR") };
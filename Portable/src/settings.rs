@@ -1,25 +1,74 @@
-//! ユーザー設定(待ち受けポート / ログ保存先)の永続化。
+//! ユーザー設定(待ち受けポート / 追加の待ち受け / 受信上限 / アクセス制御 / ログ保存先 / 表示タイムゾーン / エンコーディングルール)の永続化。
 //!
 //! 保存先は `platform::config_path()`(= データディレクトリ内の config.toml)で、
 //! ログ本体や Server 版と置き場の思想を揃えている。TOML 形式。
 
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use vlt_syslog_core::acl::AclRule;
+use vlt_syslog_core::encoding::EncodingRule;
+use vlt_syslog_core::names::ResolveConfig;
+use vlt_syslog_core::time::DisplayZone;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
-    /// 待ち受けポート(既定 514)。
+    /// 待ち受けポート(既定 514)。`[::]` で IPv4 / IPv6 の両方を受ける。
     pub bind_port: u16,
+    /// 追加の待ち受け(`[[listeners]]`。UDP のみ)。GUI には出さず、config.toml を直接編集する。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub listeners: Vec<Listener>,
+    /// 1 メッセージ(データグラム)の上限バイト数(既定 64 KiB)。超えたものは切り詰めて受け、
+    /// truncated を付ける。GUI には出さず、config.toml を直接編集する。
+    pub max_message_size: usize,
     /// ログ保存先の上書き。空文字なら platform 既定(`platform::log_dir()`)を使う。
     pub log_dir: String,
+    /// 時刻列の表示タイムゾーン("Local" / "UTC" / IANA 名)。
+    /// 解釈できない値は Local として扱う(`display_zone`)。
+    pub display_tz: String,
+    /// 送信元の受け入れ・拒否(`[[acl]]`。書き方は Server の `[[server.acl]]` と同じ)。
+    /// 拒否したパケットはパースもデバッグ用の生データ保存もしない。GUI には出さず、config.toml を直接編集する。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<AclRule>,
+    /// 送信元 IP の名前解決(`[resolve]`。書き方は Server の `[server.resolve]` と同じ)。
+    /// 無ければ名前を引かない。GUI には出さず、config.toml を直接編集する。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolve: Option<ResolveConfig>,
+    /// 送信元ごとの文字コード指定(`[[encoding_rules]]`)。GUI には出さず、config.toml を直接編集する。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub encoding_rules: Vec<EncodingRule>,
+}
+
+/// 追加の待ち受け 1 つぶん。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listener {
+    /// 待ち受けアドレス(例 "192.0.2.10:1514"、"[::]:5514")。
+    pub addr: String,
+    /// 受信したメッセージに付ける名前。無ければ "udp://アドレス"。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Listener {
+    /// メッセージに付ける待ち受け口の名前。
+    pub fn name(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| format!("udp://{}", self.addr))
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             bind_port: 514,
+            listeners: Vec::new(),
+            max_message_size: 64 * 1024,
             log_dir: String::new(),
+            display_tz: DisplayZone::Local.to_string(),
+            acl: Vec::new(),
+            resolve: None,
+            encoding_rules: Vec::new(),
         }
     }
 }
@@ -53,6 +102,11 @@ pub fn effective_log_dir(s: &Settings) -> PathBuf {
     }
 }
 
+/// 設定の表示タイムゾーン。未知の名前なら Local に倒す。
+pub fn display_zone(s: &Settings) -> DisplayZone {
+    s.display_tz.parse().unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,7 +122,46 @@ mod tests {
         let s = Settings {
             bind_port: 5514,
             log_dir: "/tmp/my-logs".to_string(),
+            ..Settings::default()
         };
         assert_eq!(effective_log_dir(&s), PathBuf::from("/tmp/my-logs"));
     }
+
+    #[test]
+    fn old_config_without_display_tz_uses_local() {
+        let s: Settings = toml::from_str("bind_port = 514\nlog_dir = \"\"\n").unwrap();
+        assert_eq!(display_zone(&s), DisplayZone::Local);
+        assert_eq!(s.max_message_size, 64 * 1024);
+    }
+
+    #[test]
+    fn encoding_rules_are_read_from_config() {
+        let s: Settings = toml::from_str(
+            "bind_port = 514\n[[encoding_rules]]\nsource = \"192.0.2.0/24\"\nencoding = \"Shift_JIS\"\n",
+        )
+        .unwrap();
+        assert_eq!(s.encoding_rules.len(), 1);
+        assert_eq!(s.encoding_rules[0].encoding.as_deref(), Some("Shift_JIS"));
+        assert!(Settings::default().encoding_rules.is_empty());
+    }
+
+    #[test]
+    fn extra_listeners_are_read_from_config() {
+        let s: Settings = toml::from_str(
+            "bind_port = 514\n[[listeners]]\naddr = \"[::]:5514\"\n[[listeners]]\naddr = \"127.0.0.1:1514\"\nlabel = \"lab\"\n",
+        )
+        .unwrap();
+        let names: Vec<String> = s.listeners.iter().map(Listener::name).collect();
+        assert_eq!(names, ["udp://[::]:5514", "lab"]);
+    }
+
+    #[test]
+    fn acl_is_read_from_config() {
+        let s: Settings = toml::from_str(
+            "bind_port = 514\n[[acl]]\naction = \"deny\"\nsource = \"203.0.113.0/24\"\n",
+        )
+        .unwrap();
+        assert_eq!(s.acl.len(), 1);
+        assert_eq!(s.acl[0].action, vlt_syslog_core::acl::AclAction::Deny);
+    }
 }
@@ -0,0 +1,146 @@
+//! `vlt-syslogd-srv bench [COUNT] [WORKERS]`: UDP 受信の性能測定。
+//!
+//! ループバックに UDP の待ち受けを立て、複数の送信スレッドから全力で COUNT 件(既定 200000)送り、
+//! 受信できた件数・カーネルで捨てられた件数・毎秒の件数を表示する。
+//! ワーカー 1(1 本の受信ループ)と WORKERS(既定は CPU 数)を続けて測り、並べて比べられるようにする。
+//!
+//! サービスの設定・ロガーは使わない。測るのはパース・統計・配信までで、ファイルログへの書き込みは含まない。
+
+use std::error::Error;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+
+use crate::config::{Config, ListenerConfig, Protocol};
+use crate::pipeline::Pipeline;
+use crate::stats::Stats;
+use crate::udp;
+
+/// 送信スレッドの数。
+const SENDERS: usize = 4;
+/// 受信件数がこの時間増えなければ、残りは届かなかったとみなす。
+const SETTLE: Duration = Duration::from_millis(500);
+
+struct Report {
+    workers: usize,
+    sent: u64,
+    received: u64,
+    kernel_drops: Option<u64>,
+    elapsed: Duration,
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let count: u64 = match args.first() {
+        Some(n) => n.parse().map_err(|e| format!("invalid COUNT {n:?}: {e}"))?,
+        None => 200_000,
+    };
+    let workers: usize = match args.get(1) {
+        Some(n) => n
+            .parse()
+            .map_err(|e| format!("invalid WORKERS {n:?}: {e}"))?,
+        None => std::thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+
+    let rt = Runtime::new()?;
+    println!(
+        "{:>8} {:>10} {:>10} {:>12} {:>10} {:>12}",
+        "workers", "sent", "received", "kernel_drops", "seconds", "msgs/s"
+    );
+    let mut runs = vec![1];
+    if workers > 1 {
+        runs.push(workers);
+    }
+    for workers in runs {
+        let report = rt.block_on(measure(workers, count))?;
+        let seconds = report.elapsed.as_secs_f64();
+        println!(
+            "{:>8} {:>10} {:>10} {:>12} {:>10.2} {:>12.0}",
+            report.workers,
+            report.sent,
+            report.received,
+            report
+                .kernel_drops
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            seconds,
+            report.received as f64 / seconds.max(f64::EPSILON)
+        );
+    }
+    Ok(())
+}
+
+async fn measure(workers: usize, count: u64) -> Result<Report, Box<dyn Error>> {
+    let cfg = Arc::new(ListenerConfig {
+        workers,
+        ..ListenerConfig::new(Protocol::Udp, "127.0.0.1:0")
+    });
+    let stats = Arc::new(Stats::new());
+    let (stream_tx, _) = broadcast::channel(1024);
+    let pipeline = Arc::new(Pipeline::new(
+        &Config::default(),
+        Arc::clone(&stats),
+        stream_tx,
+    ));
+    let sockets = udp::bind(&cfg)?;
+    let addr = sockets[0].local_addr()?;
+    let server = tokio::spawn(async move {
+        let _ = udp::serve(sockets, cfg, pipeline).await;
+    });
+
+    let started = Instant::now();
+    let senders: Vec<_> = (0..SENDERS as u64)
+        .map(|i| {
+            let n = count / SENDERS as u64 + u64::from(i < count % SENDERS as u64);
+            std::thread::spawn(move || send(addr, i, n))
+        })
+        .collect();
+    let mut sent = 0;
+    for sender in senders {
+        sent += sender.join().map_err(|_| "sender thread panicked")??;
+    }
+
+    // 受信件数が増えなくなるまで待つ。最後に増えた時刻までを所要時間とする
+    let mut received = 0;
+    let mut last_progress = Instant::now();
+    let mut finished = started.elapsed();
+    while received < sent && last_progress.elapsed() < SETTLE {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let now = stats.snapshot().received;
+        if now > received {
+            received = now;
+            last_progress = Instant::now();
+            finished = started.elapsed();
+        }
+    }
+    let kernel_drops = stats
+        .snapshot()
+        .kernel_drops
+        .values()
+        .copied()
+        .reduce(|a, b| a + b);
+    server.abort();
+    Ok(Report {
+        workers,
+        sent,
+        received,
+        kernel_drops,
+        elapsed: finished,
+    })
+}
+
+/// 1 スレッドぶんの送信。送れた件数を返す。
+fn send(addr: SocketAddr, sender: u64, count: u64) -> std::io::Result<u64> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let mut sent = 0;
+    for seq in 0..count {
+        let msg = format!(
+            "<134>Oct 18 12:00:00 bench-{sender} app[{}]: bench message seq={seq} user=alice action=login",
+            std::process::id()
+        );
+        socket.send_to(msg.as_bytes(), addr)?;
+        sent += 1;
+    }
+    Ok(sent)
+}
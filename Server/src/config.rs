@@ -2,15 +2,28 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs;
 use std::path::PathBuf;
+use vlt_syslog_core::acl::AclRule;
+use vlt_syslog_core::encoding::EncodingRule;
+use vlt_syslog_core::names::ResolveConfig;
+use vlt_syslog_core::proxy::TrustedProxies;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub server: ServerConfig,
+    /// サービス自身の診断ログ(vlt-syslogd-srv.log)。受信したメッセージは書かない。
     pub logging: LoggingConfig,
+    /// 受信したメッセージの保存(`[messages]`)。無ければ既定の場所・形式で保存する。
+    #[serde(default)]
+    pub messages: MessageStoreConfig,
+    /// 送信元ごとの文字コード指定(`[[encoding_rules]]`)。上にあるものほど優先。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encoding_rules: Vec<EncodingRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ServerConfig {
+    /// 旧形式の UDP 受信アドレス(listeners が無かった頃の設定)。空でなければ UDP の待ち受けを 1 つ足す。
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub bind_addr: String,
     /// GUI フロントエンドへ受信ログを配信する TCP アドレス(JSON Lines)。
     /// 既定はループバック限定(127.0.0.1)で外部には一切公開しない。
@@ -22,16 +35,433 @@ pub struct ServerConfig {
     /// 既存 config.toml(control_addr 無し)との互換のため serde default で補う。
     #[serde(default = "default_control_addr")]
     pub control_addr: String,
+    /// 規格に沿っていないメッセージ(RFC 3164 の HEADER 欠落など)も診断し、
+    /// ログに nonconforming と書く。既定は false(壊れた箇所だけ診断する)。
+    #[serde(default)]
+    pub strict: bool,
+    /// 配信ストリームに受信した生バイト列(`raw_b64`)を載せるか。
+    /// false にすると行が短くなるが、Console の "Copy as Hex" は使えなくなる。
+    #[serde(default = "default_stream_raw")]
+    pub stream_raw: bool,
+    /// 待ち受け口(`[[server.listeners]]`)。UDP / TCP / TLS / RELP を何個でも並べられる。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listeners: Vec<ListenerConfig>,
+    /// 送信元の受け入れ・拒否(`[[server.acl]]`)。上にあるものほど優先。
+    /// パースの前に判定し、拒否した数は送信元ごとに get_stats で返す。unix / unix-stream には効かない。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<AclRule>,
+    /// 送信元ごとの流量制限(`[server.rate_limit]`)。無ければ制限しない。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// 送信元 IP の名前解決(`[server.resolve]`)。無ければ名前を引かない。
+    /// 引けた名前はログ行に [name:名前] と書き、配信するメッセージの source_name に入れる。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolve: Option<ResolveConfig>,
+    /// 旧形式の TCP 受信(`[server.tcp]`)。listeners に protocol = "tcp" を書くのと同じ。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<TcpConfig>,
+    /// 旧形式の TLS 受信(`[server.tls]`)。listeners に protocol = "tls" を書くのと同じ。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// 旧形式の RELP 受信(`[server.relp]`)。listeners に protocol = "relp" を書くのと同じ。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relp: Option<RelpConfig>,
+}
+
+/// 待ち受け口の種類。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Udp,
+    /// RFC 6587。Octet Counting / LF 区切りは接続ごとに自動判別。
+    Tcp,
+    /// RFC 5425。区切りは TCP と同じ。
+    Tls,
+    /// rsyslog の `omrelp`。保存し終えてから応答する。
+    Relp,
+    /// Unix ドメインソケット(データグラム。`/dev/log` と同じ)。addr はソケットのパス。Unix 系のみ。
+    Unix,
+    /// Unix ドメインソケット(ストリーム。LF / NUL 区切り)。addr はソケットのパス。Unix 系のみ。
+    #[serde(rename = "unix-stream")]
+    UnixStream,
+}
+
+impl Protocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Udp => "udp",
+            Protocol::Tcp => "tcp",
+            Protocol::Tls => "tls",
+            Protocol::Relp => "relp",
+            Protocol::Unix => "unix",
+            Protocol::UnixStream => "unix-stream",
+        }
+    }
+}
+
+/// 待ち受け口 1 つぶん(`[[server.listeners]]`)。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListenerConfig {
+    /// 待ち受けアドレス(例 "[::]:514")。`[::]` は IPv4 も受けるデュアルスタックで開く。
+    /// unix / unix-stream ではソケットファイルのパス(例 "/dev/log")。
+    pub addr: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// 受信したメッセージに付ける待ち受け口の名前。指定するとログ行にも [lsn:名前] と書く。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// 1 メッセージの上限バイト数。TCP / TLS / RELP / unix-stream では超えた接続を閉じ、
+    /// UDP / unix では上限までで切り詰めて受け、切り詰めたことを記録する(truncated)。
+    #[serde(default = "default_tcp_max_message_size")]
+    pub max_message_size: usize,
+    /// この秒数何も届かない接続は閉じる(TCP / TLS / RELP)。
+    #[serde(default = "default_tcp_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// 同時に受け付ける接続数の上限(TCP / TLS / RELP / unix-stream)。
+    /// 達したら接続が閉じるまで accept せず、OS の backlog で待たせる。
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// 接続の先頭で HAProxy の PROXY プロトコル(v1 / v2)のヘッダを受け取り、そこに書かれた
+    /// 送信元をメッセージの送信元とする(TCP / TLS のみ)。ヘッダを読むのは trusted_proxies からの
+    /// 接続だけで、それ以外からの接続はこれまでどおり相手のアドレスを送信元とする。
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// PROXY ヘッダを送ってよいロードバランサの IP アドレスまたは CIDR。proxy_protocol では必須。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<String>,
+    /// 受信ワーカー数(UDP のみ、既定 1)。2 以上なら SO_REUSEPORT で同じアドレスにワーカーごとの
+    /// ソケットを開き、カーネルに振り分けさせる(SO_REUSEPORT の無い Windows では 1 つのソケットを共有する)。
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// SO_RCVBUF のバイト数(UDP のみ)。バースト時にカーネルで取りこぼすなら大きくする。
+    /// 無指定なら OS の既定。Linux では `net.core.rmem_max` を超える値は切り詰められる。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recv_buffer_size: Option<usize>,
+    /// サーバ証明書(TLS のみ。[`TlsConfig::cert_path`] と同じ)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_path: Option<String>,
+    /// サーバ証明書の秘密鍵(TLS のみ)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<String>,
+    /// クライアント証明書を検証する CA 証明書(TLS のみ)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<String>,
+    /// ソケットファイルのパーミッション(unix / unix-stream のみ。8 進の文字列、既定 "0666")。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+impl ListenerConfig {
+    /// 既定値で `protocol` の待ち受け口を作る。
+    pub fn new(protocol: Protocol, addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            protocol,
+            label: None,
+            max_message_size: default_tcp_max_message_size(),
+            idle_timeout_secs: default_tcp_idle_timeout_secs(),
+            max_connections: default_max_connections(),
+            proxy_protocol: false,
+            trusted_proxies: Vec::new(),
+            workers: default_workers(),
+            recv_buffer_size: None,
+            cert_path: None,
+            key_path: None,
+            client_ca_path: None,
+            mode: None,
+        }
+    }
+
+    /// ソケットファイルのパーミッション。指定が無ければ誰でも書ける 0666(`/dev/log` と同じ)。
+    pub fn socket_mode(&self) -> Result<u32, String> {
+        match &self.mode {
+            None => Ok(0o666),
+            Some(mode) => u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|m| *m <= 0o7777)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid mode {:?} for {} (expected octal like \"0660\")",
+                        mode, self.addr
+                    )
+                }),
+        }
+    }
+
+    /// PROXY ヘッダを読む接続の相手。proxy_protocol でなければ None。
+    pub fn trusted_proxies(&self) -> Result<Option<TrustedProxies>, String> {
+        if !self.proxy_protocol {
+            return Ok(None);
+        }
+        TrustedProxies::compile(&self.trusted_proxies)
+            .map(Some)
+            .map_err(|e| format!("listener {}: {}", self.addr, e))
+    }
+
+    /// メッセージに付ける名前。label が無ければ "udp://[::]:514" の形。
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => format!("{}://{}", self.protocol.as_str(), self.addr),
+        }
+    }
+
+    /// 起動しても動かないことが分かっている設定を弾く。
+    pub fn check(&self) -> Result<(), String> {
+        if self.addr.trim().is_empty() {
+            return Err(format!("{} listener has no addr", self.protocol.as_str()));
+        }
+        if self.protocol == Protocol::Tls && (self.cert_path.is_none() || self.key_path.is_none()) {
+            return Err(format!(
+                "tls listener {} requires cert_path and key_path",
+                self.addr
+            ));
+        }
+        if self.workers == 0 {
+            return Err(format!("listener {} requires at least 1 worker", self.addr));
+        }
+        if self.max_connections == 0 {
+            return Err(format!(
+                "listener {} requires max_connections of at least 1",
+                self.addr
+            ));
+        }
+        if self.proxy_protocol {
+            if !matches!(self.protocol, Protocol::Tcp | Protocol::Tls) {
+                return Err(format!(
+                    "proxy_protocol is not supported for {} listener {}",
+                    self.protocol.as_str(),
+                    self.addr
+                ));
+            }
+            if self.trusted_proxies.is_empty() {
+                return Err(format!(
+                    "listener {} with proxy_protocol requires trusted_proxies",
+                    self.addr
+                ));
+            }
+        }
+        self.trusted_proxies()?;
+        if matches!(self.protocol, Protocol::Unix | Protocol::UnixStream) {
+            if cfg!(not(unix)) {
+                return Err(format!(
+                    "{} listener {} is not supported on this platform",
+                    self.protocol.as_str(),
+                    self.addr
+                ));
+            }
+            self.socket_mode()?;
+        }
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    /// 実際に開く待ち受け口。listeners に旧形式(bind_addr / [server.tcp] 等)の分を足したもの。
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        let mut listeners = self.listeners.clone();
+        if !self.bind_addr.is_empty() {
+            listeners.push(ListenerConfig::new(Protocol::Udp, &self.bind_addr));
+        }
+        if let Some(tcp) = &self.tcp {
+            listeners.push(ListenerConfig {
+                max_message_size: tcp.max_message_size,
+                idle_timeout_secs: tcp.idle_timeout_secs,
+                max_connections: tcp.max_connections,
+                ..ListenerConfig::new(Protocol::Tcp, &tcp.bind_addr)
+            });
+        }
+        if let Some(tls) = &self.tls {
+            listeners.push(ListenerConfig {
+                max_message_size: tls.max_message_size,
+                idle_timeout_secs: tls.idle_timeout_secs,
+                cert_path: Some(tls.cert_path.clone()),
+                key_path: Some(tls.key_path.clone()),
+                client_ca_path: tls.client_ca_path.clone(),
+                max_connections: tls.max_connections,
+                ..ListenerConfig::new(Protocol::Tls, &tls.bind_addr)
+            });
+        }
+        if let Some(relp) = &self.relp {
+            listeners.push(ListenerConfig {
+                max_message_size: relp.max_message_size,
+                idle_timeout_secs: relp.idle_timeout_secs,
+                max_connections: relp.max_connections,
+                ..ListenerConfig::new(Protocol::Relp, &relp.bind_addr)
+            });
+        }
+        listeners
+    }
+}
+
+/// 流量制限で数える単位。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RateKey {
+    /// 送信元 IP。
+    #[default]
+    Source,
+    /// HOSTNAME(無ければ送信元 IP)。
+    Host,
+    /// TAG / APP-NAME(無ければ送信元 IP)。
+    Tag,
+}
+
+/// 上限を超えたメッセージの扱い。どれも抑えた件数は "N messages suppressed from X" として
+/// `summary_interval_secs` ごとにログと配信に 1 件書く。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RatePolicy {
+    /// 捨てる。
+    #[default]
+    Drop,
+    /// `sample_every` 件に 1 件だけ通し、残りは捨てる。
+    Sample,
+    /// 捨てるが、まとめの 1 件に severity ごとの件数と最後に抑えた本文を載せる。
+    Summarise,
+}
+
+/// 流量制限(トークンバケツ)。`by` ごとに毎秒 `rate` 件ずつ補充され、最大 `burst` 件まで貯まる。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// 1 秒あたりに通すメッセージ数。
+    pub rate: f64,
+    /// まとめて通せる数(バケツの容量)。
+    pub burst: u32,
+    #[serde(default)]
+    pub by: RateKey,
+    #[serde(default)]
+    pub policy: RatePolicy,
+    /// sample のとき、超えた分の何件に 1 件を通すか。
+    #[serde(default = "default_sample_every")]
+    pub sample_every: u64,
+    /// 抑えた件数をまとめて書く間隔(秒)。
+    #[serde(default = "default_summary_interval_secs")]
+    pub summary_interval_secs: u64,
+}
+
+impl RateLimitConfig {
+    /// 動かない値を弾く。
+    pub fn check(&self) -> Result<(), String> {
+        if !(self.rate.is_finite() && self.rate > 0.0) {
+            return Err(format!(
+                "rate_limit.rate must be positive (got {})",
+                self.rate
+            ));
+        }
+        if self.burst == 0 {
+            return Err("rate_limit.burst must be at least 1".to_string());
+        }
+        if self.sample_every == 0 {
+            return Err("rate_limit.sample_every must be at least 1".to_string());
+        }
+        if self.summary_interval_secs == 0 {
+            return Err("rate_limit.summary_interval_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn default_sample_every() -> u64 {
+    100
+}
+
+fn default_summary_interval_secs() -> u64 {
+    10
+}
+
+/// TCP での syslog 受信(RFC 6587。Octet Counting / LF 区切りは接続ごとに自動判別)。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TcpConfig {
+    /// 待ち受けアドレス(例 0.0.0.0:514)。
+    pub bind_addr: String,
+    /// 1 メッセージの上限バイト数。超えた接続は閉じる。
+    #[serde(default = "default_tcp_max_message_size")]
+    pub max_message_size: usize,
+    /// この秒数何も届かない接続は閉じる。
+    #[serde(default = "default_tcp_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// 同時に受け付ける TCP 接続数の上限([`ListenerConfig::max_connections`] と同じ)。
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+}
+
+/// TLS での syslog 受信(RFC 5425)。区切りは TCP と同じく接続ごとに自動判別する。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TlsConfig {
+    /// 待ち受けアドレス(例 0.0.0.0:6514)。
+    pub bind_addr: String,
+    /// サーバ証明書(PEM。中間証明書があれば続けて並べる)。
+    pub cert_path: String,
+    /// サーバ証明書の秘密鍵(PEM。PKCS#8 / PKCS#1 / SEC1)。
+    pub key_path: String,
+    /// クライアント証明書を検証する CA 証明書(PEM)。指定するとクライアント証明書を必須にし、
+    /// その Subject をメッセージに記録する。無ければクライアント認証はしない。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<String>,
+    /// 1 メッセージの上限バイト数。超えた接続は閉じる。
+    #[serde(default = "default_tcp_max_message_size")]
+    pub max_message_size: usize,
+    /// この秒数何も届かない接続は閉じる(ハンドシェイク中も含む)。
+    #[serde(default = "default_tcp_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// 同時に受け付ける TLS 接続数の上限([`ListenerConfig::max_connections`] と同じ)。
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+}
+
+/// RELP での syslog 受信(rsyslog の `omrelp`)。メッセージを保存し終えてから応答を返すので、
+/// 応答の前にサービスが落ちても送信元が再送する。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelpConfig {
+    /// 待ち受けアドレス(例 0.0.0.0:2514)。
+    pub bind_addr: String,
+    /// 1 メッセージの上限バイト数。超えた接続は閉じる。
+    #[serde(default = "default_tcp_max_message_size")]
+    pub max_message_size: usize,
+    /// この秒数何も届かない接続は閉じる。
+    #[serde(default = "default_tcp_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// 同時に受け付ける RELP 接続数の上限([`ListenerConfig::max_connections`] と同じ)。
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+}
+
+/// 1 メッセージの上限。rsyslog / syslog-ng の既定(8 KiB〜64 KiB)や、
+/// UDP の 1 データグラムの最大(65507 バイト)を収められる大きさ。
+fn default_tcp_max_message_size() -> usize {
+    64 * 1024
+}
+
+/// TCP の無通信タイムアウト。送信元は接続を張ったまま使い回すので長めにする。
+fn default_tcp_idle_timeout_secs() -> u64 {
+    600
+}
+
+/// UDP の受信ワーカー数。
+fn default_workers() -> usize {
+    1
+}
+
+/// 1 つの待ち受け口で同時に受け付ける接続数。
+fn default_max_connections() -> usize {
+    1024
 }
 
 /// stream_addr の既定値。ループバックの 5141 番。
 fn default_stream_addr() -> String {
-    "127.0.0.1:5141".to_string()
+    vlt_syslog_core::stream::DEFAULT_STREAM_ADDR.to_string()
+}
+
+/// stream_raw の既定値。これまでどおり生バイト列も配信する。
+fn default_stream_raw() -> bool {
+    true
 }
 
 /// control_addr の既定値。ループバックの 5142 番。
 fn default_control_addr() -> String {
-    "127.0.0.1:5142".to_string()
+    vlt_syslog_core::stream::DEFAULT_CONTROL_ADDR.to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -41,19 +471,102 @@ pub struct LoggingConfig {
     pub keep_files: usize,
 }
 
+/// 受信したメッセージの保存先・形式・ローテーション・保存期間。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageStoreConfig {
+    /// 保存するディレクトリ。空ならデータディレクトリの messages/。
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub dir: String,
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// ファイルを切り替える周期。ファイル名に日付(hourly なら時も)が入る。
+    #[serde(default)]
+    pub rotate: Rotate,
+    /// 1 ファイルの上限(MB)。超えたら同じ周期の次の番号のファイルに移る。0 なら大きさでは分けない。
+    #[serde(default = "default_message_max_size_mb")]
+    pub max_size_mb: u64,
+    /// 残しておく日数。これより前に書き終えたファイルはファイルを切り替えるときに消す。0 なら消さない。
+    #[serde(default = "default_keep_days")]
+    pub keep_days: u64,
+}
+
+impl Default for MessageStoreConfig {
+    fn default() -> Self {
+        Self {
+            dir: String::new(),
+            format: MessageFormat::default(),
+            rotate: Rotate::default(),
+            max_size_mb: default_message_max_size_mb(),
+            keep_days: default_keep_days(),
+        }
+    }
+}
+
+/// 保存する 1 行の形式。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// `[Info] [fac:..] [src:..] [rcv:..] ... 本文` のテキスト(*.log)。
+    #[default]
+    Text,
+    /// 配信ストリームと同じ JSON Lines(*.jsonl)。生バイト列(raw_b64)も含む。
+    Json,
+    /// 受信したバイト列そのまま。1 メッセージ 1 行(*.log)。
+    Raw,
+}
+
+/// ファイルを切り替える周期。
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotate {
+    #[default]
+    Daily,
+    Hourly,
+}
+
+impl MessageStoreConfig {
+    /// 実際に書き込むディレクトリ。
+    pub fn directory(&self) -> PathBuf {
+        if self.dir.is_empty() {
+            crate::platform::message_dir()
+        } else {
+            PathBuf::from(&self.dir)
+        }
+    }
+}
+
+fn default_message_max_size_mb() -> u64 {
+    100
+}
+
+fn default_keep_days() -> u64 {
+    30
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             server: ServerConfig {
-                bind_addr: "0.0.0.0:514".to_string(),
+                bind_addr: String::new(),
+                listeners: vec![ListenerConfig::new(Protocol::Udp, "[::]:514")],
+                acl: Vec::new(),
+                rate_limit: None,
+                resolve: None,
                 stream_addr: default_stream_addr(),
                 control_addr: default_control_addr(),
+                strict: false,
+                stream_raw: default_stream_raw(),
+                tcp: None,
+                tls: None,
+                relp: None,
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 max_size_mb: 10,
                 keep_files: 7,
             },
+            messages: MessageStoreConfig::default(),
+            encoding_rules: Vec::new(),
         }
     }
 }
@@ -96,3 +609,189 @@ pub fn get_config_path() -> PathBuf {
 pub fn get_log_dir() -> PathBuf {
     crate::platform::log_dir()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_sections_become_listeners() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            bind_addr = "0.0.0.0:514"
+
+            [[server.listeners]]
+            addr = "[::]:1514"
+            label = "v6"
+
+            [server.tcp]
+            bind_addr = "0.0.0.0:601"
+            max_connections = 16
+
+            [server.tls]
+            bind_addr = "0.0.0.0:6514"
+            cert_path = "server.pem"
+            key_path = "server.key"
+            max_connections = 32
+
+            [server.relp]
+            bind_addr = "0.0.0.0:2514"
+            max_connections = 48
+
+            [logging]
+            level = "info"
+            max_size_mb = 10
+            keep_files = 7
+            "#,
+        )
+        .unwrap();
+        let listeners = config.server.listeners();
+        let names: Vec<String> = listeners.iter().map(|l| l.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "v6",
+                "udp://0.0.0.0:514",
+                "tcp://0.0.0.0:601",
+                "tls://0.0.0.0:6514",
+                "relp://0.0.0.0:2514"
+            ]
+        );
+
+        // 旧形式で書いた接続数の上限もそのまま使い、0 は弾く
+        let limits: Vec<usize> = listeners[2..].iter().map(|l| l.max_connections).collect();
+        assert_eq!(limits, [16, 32, 48]);
+        for listener in &listeners[2..] {
+            assert!(listener.check().is_ok());
+            assert!(
+                ListenerConfig {
+                    max_connections: 0,
+                    ..listener.clone()
+                }
+                .check()
+                .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn default_config_round_trips_through_toml() {
+        let text = toml::to_string_pretty(&Config::default()).unwrap();
+        let config: Config = toml::from_str(&text).unwrap();
+        assert!(config.server.bind_addr.is_empty());
+        let listeners = config.server.listeners();
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].name(), "udp://[::]:514");
+        assert!(
+            ListenerConfig::new(Protocol::Tls, "[::]:6514")
+                .check()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rate_limit_section_uses_defaults_for_omitted_keys() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            [server.rate_limit]
+            rate = 50
+            burst = 100
+            by = "host"
+            policy = "summarise"
+
+            [logging]
+            level = "info"
+            max_size_mb = 10
+            keep_files = 7
+            "#,
+        )
+        .unwrap();
+        let limit = config.server.rate_limit.unwrap();
+        assert_eq!(
+            (limit.by, limit.policy),
+            (RateKey::Host, RatePolicy::Summarise)
+        );
+        assert_eq!((limit.sample_every, limit.summary_interval_secs), (100, 10));
+        assert!(limit.check().is_ok());
+        assert!(RateLimitConfig { rate: 0.0, ..limit }.check().is_err());
+    }
+
+    #[test]
+    fn messages_section_is_optional() {
+        let logging = r#"
+            [server]
+            [logging]
+            level = "warn"
+            max_size_mb = 10
+            keep_files = 7
+            "#;
+        let config: Config = toml::from_str(logging).unwrap();
+        assert_eq!(config.messages.format, MessageFormat::Text);
+        assert_eq!(config.messages.rotate, Rotate::Daily);
+        assert!(config.messages.dir.is_empty());
+
+        let config: Config = toml::from_str(&format!(
+            "{logging}
+            [messages]
+            dir = \"/srv/syslog\"
+            format = \"json\"
+            rotate = \"hourly\"
+            keep_days = 0
+            "
+        ))
+        .unwrap();
+        assert_eq!(config.messages.format, MessageFormat::Json);
+        assert_eq!(config.messages.rotate, Rotate::Hourly);
+        assert_eq!(config.messages.directory(), PathBuf::from("/srv/syslog"));
+        assert_eq!(
+            (config.messages.max_size_mb, config.messages.keep_days),
+            (100, 0)
+        );
+    }
+
+    #[test]
+    fn listeners_need_room_for_a_connection() {
+        let tcp = ListenerConfig::new(Protocol::Tcp, "[::]:601");
+        assert_eq!(tcp.max_connections, 1024);
+        assert!(
+            ListenerConfig {
+                max_connections: 0,
+                ..tcp
+            }
+            .check()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn proxy_protocol_requires_trusted_proxies_on_tcp_or_tls() {
+        let tcp = ListenerConfig {
+            proxy_protocol: true,
+            ..ListenerConfig::new(Protocol::Tcp, "[::]:514")
+        };
+        assert!(tcp.check().is_err());
+        let tcp = ListenerConfig {
+            trusted_proxies: vec!["10.0.0.0/8".to_string()],
+            ..tcp
+        };
+        assert!(tcp.check().is_ok());
+        assert!(
+            ListenerConfig {
+                trusted_proxies: vec!["10.0.0.0/99".to_string()],
+                ..tcp.clone()
+            }
+            .check()
+            .is_err()
+        );
+        assert!(
+            ListenerConfig {
+                protocol: Protocol::Udp,
+                ..tcp
+            }
+            .check()
+            .is_err()
+        );
+    }
+}
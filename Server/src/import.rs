@@ -0,0 +1,228 @@
+//! `vlt-syslogd-srv import FILE [--pace] [--port N]...`: パケットキャプチャ(pcap / pcapng)の取り込み。
+//!
+//! キャプチャから UDP / TCP の syslog を取り出し、動いているサービスの制御ポートへ
+//! `{"cmd":"import", ...}` を送って、通常の受信と同じ処理(パース・統計・ファイルログ・GUI への配信)に流す。
+//! 受信時刻はキャプチャの時刻、送信元はキャプチャの送信元アドレスのまま残す。流量制限は掛けない。
+//! `--pace` を付けるとキャプチャの時刻の間隔どおりに流し、付けなければ一度に流す。
+//! `--port` で syslog とみなす宛先ポートを指定する(既定 514 と 601)。
+//!
+//! ファイルはサービスが読むので、パスは絶対パスにして送る。アクセス制御(acl)は通さない。
+//! 読めるファイルは [`capture::MAX_FILE_SIZE`](256 MiB)まで。大きいものは分けてから取り込む。
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use vlt_syslog_core::capture::{self, CapturedMessage, DEFAULT_PORTS, Transport};
+
+use crate::config::{self, ListenerConfig, Protocol};
+use crate::pipeline::{Peer, Pipeline};
+
+/// 制御ポートの応答を待つ時間。サービスはファイルを読み終えてから応答する。
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut path = None;
+    let mut pace = false;
+    let mut ports = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pace" => pace = true,
+            "--port" => {
+                let port = args.next().ok_or("--port needs a port number")?;
+                ports.push(
+                    port.parse::<u16>()
+                        .map_err(|e| format!("invalid port {port:?}: {e}"))?,
+                );
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument {arg:?}").into()),
+        }
+    }
+    let path = path.ok_or(
+        "Usage: vlt-syslogd-srv import FILE [--pace] [--port N]...\n\
+         FILE is a pcap / pcapng capture of at most 256 MiB",
+    )?;
+    let path = std::fs::canonicalize(path).map_err(|e| format!("{path}: {e}"))?;
+    if ports.is_empty() {
+        ports = DEFAULT_PORTS.to_vec();
+    }
+
+    let config = config::load_config()?;
+    let request = serde_json::json!({
+        "cmd": "import",
+        "path": path,
+        "pace": pace,
+        "ports": ports,
+    });
+    let mut socket = TcpStream::connect(&config.server.control_addr).map_err(|e| {
+        format!(
+            "cannot connect to the service on {}: {e}",
+            config.server.control_addr
+        )
+    })?;
+    socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    socket.write_all(format!("{request}\n").as_bytes())?;
+    let mut line = String::new();
+    BufReader::new(socket).read_line(&mut line)?;
+    let response: serde_json::Value = serde_json::from_str(line.trim())?;
+    if response["ok"] != true {
+        return Err(format!("import failed: {}", response["error"]).into());
+    }
+    println!(
+        "{}: {} messages queued{}",
+        path.display(),
+        response["messages"],
+        if pace {
+            " (replaying at original pacing)"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+/// 制御ポートの import。ファイルを読んで取り出した件数を返し、流すのは別タスクで行う。
+/// 読み込みと取り出しは大きなファイルでも受信を止めないよう blocking のスレッドで行う。
+pub async fn start(request: &serde_json::Value, pipeline: &Arc<Pipeline>) -> Result<usize, String> {
+    let path = request
+        .get("path")
+        .and_then(|p| p.as_str())
+        .ok_or("missing 'path' field")?;
+    let pace = request
+        .get("pace")
+        .and_then(|p| p.as_bool())
+        .unwrap_or(false);
+    let ports = match request.get("ports") {
+        Some(ports) => serde_json::from_value::<Vec<u16>>(ports.clone())
+            .map_err(|e| format!("invalid ports: {e}"))?,
+        None => DEFAULT_PORTS.to_vec(),
+    };
+    let read_path = path.to_string();
+    let messages = tokio::task::spawn_blocking(move || {
+        let data = capture::read_file(&read_path).map_err(|e| format!("{read_path}: {e}"))?;
+        capture::read_messages(&data, &ports).map_err(|e| format!("{read_path}: {e}"))
+    })
+    .await
+    .map_err(|e| format!("{path}: {e}"))??;
+    let count = messages.len();
+    let file = Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |f| f.to_string_lossy().into_owned());
+    log::info!("Importing {} messages from {}", count, path);
+    let pipeline = Arc::clone(pipeline);
+    tokio::spawn(async move {
+        replay(&pipeline, messages, &file, pace).await;
+        log::info!("Imported {} messages from {}", count, file);
+    });
+    Ok(count)
+}
+
+/// 取り出したメッセージを時刻順に流す。待ち受け口の名前は "import:ファイル名" にする。
+async fn replay(pipeline: &Pipeline, messages: Vec<CapturedMessage>, file: &str, pace: bool) {
+    let listener = |protocol| {
+        Arc::new(ListenerConfig {
+            label: Some(format!("import:{file}")),
+            ..ListenerConfig::new(protocol, "capture")
+        })
+    };
+    let udp = listener(Protocol::Udp);
+    let tcp = listener(Protocol::Tcp);
+    let started = tokio::time::Instant::now();
+    let first = messages.first().map(|m| m.timestamp);
+    for message in messages {
+        if pace && let Some(first) = first {
+            let offset = (message.timestamp - first).to_std().unwrap_or_default();
+            tokio::time::sleep_until(started + offset).await;
+        }
+        let listener = match message.transport {
+            Transport::Udp => &udp,
+            Transport::Tcp => &tcp,
+        };
+        let peer = Peer::new(listener, message.source);
+        pipeline.handle_at(&message.raw, false, &peer, message.timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RateKey, RateLimitConfig, RatePolicy};
+    use crate::stats::Stats;
+    use tokio::sync::broadcast;
+    use vlt_syslog_core::stream::decode_line;
+
+    /// LINKTYPE_RAW の pcap に IPv4 / UDP のデータグラムを並べる。
+    fn pcap(records: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let mut file = Vec::new();
+        for field in [0xa1b2_c3d4u32, 0x0004_0002, 0, 0, 65535, 101] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+        for (secs, micros, payload) in records {
+            let mut packet = vec![0x45, 0];
+            packet.extend_from_slice(&(28 + payload.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0, 192, 0, 2, 7, 192, 0, 2, 1]);
+            packet.extend_from_slice(&40000u16.to_be_bytes());
+            packet.extend_from_slice(&514u16.to_be_bytes());
+            packet.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0]);
+            packet.extend_from_slice(payload);
+            for field in [*secs, *micros, packet.len() as u32, packet.len() as u32] {
+                file.extend_from_slice(&field.to_le_bytes());
+            }
+            file.extend_from_slice(&packet);
+        }
+        file
+    }
+
+    #[tokio::test]
+    async fn capture_is_replayed_with_original_time_and_source() {
+        let path = std::env::temp_dir().join(format!("vlt-import-{}.pcap", std::process::id()));
+        std::fs::write(
+            &path,
+            pcap(&[
+                (1_760_788_800, 0, b"<13>app: one"),
+                (1_760_788_800, 200_000, b"<13>app: two"),
+            ]),
+        )
+        .unwrap();
+
+        // 1 件しか通さない流量制限があっても、取り込んだものは全部流れる
+        let mut config = Config::default();
+        config.server.rate_limit = Some(RateLimitConfig {
+            rate: 1.0,
+            burst: 1,
+            by: RateKey::Source,
+            policy: RatePolicy::Drop,
+            sample_every: 1,
+            summary_interval_secs: 60,
+        });
+        let stats = Arc::new(Stats::new());
+        let (stream_tx, mut rx) = broadcast::channel(16);
+        let pipeline = Arc::new(Pipeline::new(&config, stats, stream_tx));
+        let request = serde_json::json!({ "cmd": "import", "path": path, "pace": true });
+        assert_eq!(start(&request, &pipeline).await, Ok(2));
+        std::fs::remove_file(&path).unwrap();
+
+        let started = tokio::time::Instant::now();
+        let first = decode_line(&rx.recv().await.unwrap()).unwrap();
+        let second = decode_line(&rx.recv().await.unwrap()).unwrap();
+        // 元の 0.2 秒の間隔をおいて流れる
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert_eq!(first.content, "one");
+        assert_eq!(first.received_at.timestamp(), 1_760_788_800);
+        assert_eq!(second.received_at.timestamp_subsec_millis(), 200);
+        assert_eq!(first.source_addr, Some("192.0.2.7".parse().unwrap()));
+        assert_eq!(
+            first.listener.as_deref(),
+            Some(format!("import:vlt-import-{}.pcap", std::process::id()).as_str())
+        );
+
+        let missing = serde_json::json!({ "cmd": "import", "path": "/nonexistent.pcap" });
+        assert!(start(&missing, &pipeline).await.is_err());
+    }
+}
@@ -1,13 +1,29 @@
-mod parser;
+mod bench;
 mod config;
+mod import;
+mod pipeline;
 mod platform;
+mod ratelimit;
+mod relp;
+mod stats;
+mod store;
+mod tcp;
+mod tls;
+mod udp;
+#[cfg(unix)]
+mod unix;
 
 use std::error::Error;
 use std::panic;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, UdpSocket};
+use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
 use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+use vlt_syslog_core::acl::AccessList;
+use vlt_syslog_core::encoding::EncodingRules;
+use vlt_syslog_core::names;
 
 // --- Windows サービス連携（Windows ターゲットでのみコンパイル）---
 #[cfg(windows)]
@@ -36,6 +52,16 @@ const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
 define_windows_service!(ffi_service_main, syslog_service_main);
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // 性能測定はサービスの設定・ログファイルに触れずに動かす
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        return bench::run(&args[2..]);
+    }
+    // キャプチャの取り込みは動いているサービスに頼むだけなので、ロガーを立てない
+    if args.get(1).map(String::as_str) == Some("import") {
+        return import::run(&args[2..]);
+    }
+
     // 1. 設定の読み込み
     let config = match config::load_config() {
         Ok(c) => c,
@@ -61,7 +87,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     setup_panic_hook();
 
     // 開発/デバッグ用：引数があればコマンドとして処理（全プラットフォーム共通）
-    let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
         match args[1].as_str() {
             "run" => {
@@ -71,7 +96,10 @@ fn main() -> Result<(), Box<dyn Error>> {
                 return Ok(());
             }
             _ => {
-                println!("Usage: vlt-syslogd-srv [run]");
+                println!(
+                    "Usage: vlt-syslogd-srv [run | bench [COUNT] [WORKERS] | import FILE [--pace] [--port N]...]"
+                );
+                println!("import reads pcap / pcapng captures of at most 256 MiB.");
                 #[cfg(windows)]
                 println!("Wait for Windows Service Manager if no args.");
                 #[cfg(not(windows))]
@@ -137,6 +165,8 @@ fn init_logger(
         let _ = std::fs::create_dir_all(&log_dir);
     }
 
+    // 受信したメッセージは store(messages/)に書くので、ここに来るのはサービス自身の診断だけ。
+    let write_mode = flexi_logger::WriteMode::Async; // 非同期書き込みでパフォーマンス向上
     let handle = flexi_logger::Logger::try_with_str(&config.logging.level)?
         .log_to_file(
             flexi_logger::FileSpec::default()
@@ -144,7 +174,7 @@ fn init_logger(
                 .basename("vlt-syslogd-srv")
                 .suffix("log"),
         )
-        .write_mode(flexi_logger::WriteMode::Async) // 非同期書き込みでパフォーマンス向上
+        .write_mode(write_mode)
         .format(flexi_logger::opt_format)
         .rotate(
             flexi_logger::Criterion::Size(config.logging.max_size_mb * 1024 * 1024),
@@ -213,10 +243,14 @@ fn run_service(config: config::Config) -> Result<(), Box<dyn Error>> {
 }
 
 async fn run_syslog_server(config: config::Config) -> Result<(), Box<dyn Error>> {
-    let addr = config.server.bind_addr.clone();
-    let socket = UdpSocket::bind(&addr).await?;
-
-    log::info!("vlt-syslogd-srv engine started on {}", addr);
+    let listeners = config.server.listeners();
+    if listeners.is_empty() {
+        return Err("no listeners configured (server.listeners is empty)".into());
+    }
+    log::info!(
+        "vlt-syslogd-srv engine started ({} listeners)",
+        listeners.len()
+    );
 
     // GUI フロントエンドへ受信ログを配信する broadcast チャネル。
     // 購読者(接続中の GUI)がいなければ送信は黙って捨てられる。
@@ -224,7 +258,7 @@ async fn run_syslog_server(config: config::Config) -> Result<(), Box<dyn Error>>
     let (stream_tx, _) = broadcast::channel::<String>(1024);
 
     // TCP 配信タスクを起動する。listen に失敗しても(ポート使用中など)
-    // サービス本体(UDP 受信 + ファイルログ)は止めない。配信だけが無効になる。
+    // サービス本体(syslog 受信 + 保存)は止めない。配信だけが無効になる。
     {
         let stream_addr = config.server.stream_addr.clone();
         let stream_tx = stream_tx.clone();
@@ -235,36 +269,87 @@ async fn run_syslog_server(config: config::Config) -> Result<(), Box<dyn Error>>
         });
     }
 
+    // 受信統計(診断の件数)。制御サーバの get_stats で返す。
+    let stats = Arc::new(stats::Stats::new());
+
+    // 受信したメッセージの処理(パース・保存・配信)は UDP / TCP / TLS / RELP / Unix ソケットで共通。
+    // 制御サーバの import(キャプチャの取り込み)も同じ処理に流す。
+    let store = store::MessageStore::new(&config.messages);
+    log::info!("Storing received messages in {}", store.dir().display());
+    let pipeline =
+        Arc::new(pipeline::Pipeline::new(&config, Arc::clone(&stats), stream_tx).with_store(store));
+
+    // 保存のバッファを 1 秒ごとに OS へ渡す(RELP は応答の前にも渡す)。
+    {
+        let pipeline = Arc::clone(&pipeline);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                pipeline.flush();
+            }
+        });
+    }
+
     // 設定の取得/変更を受け付ける制御サーバを起動する。listen に失敗しても
-    // サービス本体(UDP 受信 + 配信)は止めない。制御だけが無効になる。
+    // サービス本体(syslog 受信 + 配信)は止めない。制御だけが無効になる。
     {
         let control_addr = config.server.control_addr.clone();
+        let pipeline = Arc::clone(&pipeline);
         tokio::spawn(async move {
-            if let Err(e) = run_control_server(&control_addr).await {
+            if let Err(e) = run_control_server(&control_addr, stats, pipeline).await {
                 log::error!("Control listener on {} terminated: {}", control_addr, e);
             }
         });
     }
 
-    let mut buf = [0u8; 8192];
-    loop {
-        let (size, src) = socket.recv_from(&mut buf).await?;
-        let raw_msg = &buf[..size];
-
-        let parsed = parser::parse_syslog(raw_msg);
-
-        // サービス版：全受信メッセージをINFOレベルで記録
-        log::info!(
-            "[{:?}] [src:{}] [enc:{}] {}",
-            parsed.severity, src, parsed.encoding, parsed.content
-        );
+    // 流量制限で抑えた件数を 1 秒ごとに確認し、集計期間が過ぎたものをまとめて書く。
+    if config.server.rate_limit.is_some() {
+        let pipeline = Arc::clone(&pipeline);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                pipeline.report_suppressed();
+            }
+        });
+    }
 
-        // GUI フロントエンドへ JSON Lines(1メッセージ=1行 JSON)で配信する。
-        // 購読者がいなければ send は Err になるが、その場合は捨ててよい。
-        if let Ok(json) = serde_json::to_string(&parsed) {
-            let _ = stream_tx.send(json);
+    // 待ち受け口ごとにタスクを起動する。1 つが listen に失敗しても他の受信は止めない。
+    let mut tasks = JoinSet::new();
+    for listener in listeners {
+        if let Err(e) = listener.check() {
+            log::error!("Ignoring listener: {}", e);
+            continue;
         }
+        let listener = Arc::new(listener);
+        let pipeline = Arc::clone(&pipeline);
+        tasks.spawn(async move {
+            let name = listener.name();
+            let result = match listener.protocol {
+                config::Protocol::Udp => udp::run_udp_server(listener, pipeline).await,
+                config::Protocol::Tcp => tcp::run_tcp_server(listener, pipeline).await,
+                config::Protocol::Tls => tls::run_tls_server(listener, pipeline).await,
+                config::Protocol::Relp => relp::run_relp_server(listener, pipeline).await,
+                #[cfg(unix)]
+                config::Protocol::Unix => unix::run_unix_datagram_server(listener, pipeline).await,
+                #[cfg(unix)]
+                config::Protocol::UnixStream => {
+                    unix::run_unix_stream_server(listener, pipeline).await
+                }
+                // check() で弾いているので来ない
+                #[cfg(not(unix))]
+                config::Protocol::Unix | config::Protocol::UnixStream => {
+                    Err("unix sockets are not supported on this platform".into())
+                }
+            };
+            if let Err(e) = result {
+                log::error!("Listener {} terminated: {}", name, e);
+            }
+        });
     }
+    while tasks.join_next().await.is_some() {}
+    Err("all listeners have stopped".into())
 }
 
 /// GUI フロントエンド向けの TCP 配信サーバ(JSON Lines)。
@@ -319,12 +404,20 @@ async fn run_stream_server(
 ///
 /// set_config は config.toml を書き換えるのみで、反映はサービス再起動で行う方針
 /// (動作中プロセスのホットリロードはしない)。レスポンスで restart_required を返す。
-async fn run_control_server(addr: &str) -> Result<(), Box<dyn Error>> {
+/// get_stats は起動してからの受信統計(診断の種類ごと・送信元ごとの件数)を返す。
+/// import はキャプチャファイルを読んで取り出した件数を返し、受信と同じ処理へ流す(import モジュール)。
+async fn run_control_server(
+    addr: &str,
+    stats: Arc<stats::Stats>,
+    pipeline: Arc<pipeline::Pipeline>,
+) -> Result<(), Box<dyn Error>> {
     let listener = TcpListener::bind(addr).await?;
     log::info!("vlt-syslogd-srv control listener started on {}", addr);
 
     loop {
         let (socket, peer) = listener.accept().await?;
+        let stats = Arc::clone(&stats);
+        let pipeline = Arc::clone(&pipeline);
         tokio::spawn(async move {
             let mut reader = BufReader::new(socket);
             let mut line = String::new();
@@ -336,7 +429,7 @@ async fn run_control_server(addr: &str) -> Result<(), Box<dyn Error>> {
                     return;
                 }
             }
-            let response = handle_control(&line);
+            let response = handle_control(&line, &stats, &pipeline).await;
             let mut socket = reader.into_inner();
             if socket.write_all(response.as_bytes()).await.is_err()
                 || socket.write_all(b"\n").await.is_err()
@@ -348,7 +441,7 @@ async fn run_control_server(addr: &str) -> Result<(), Box<dyn Error>> {
 }
 
 /// 制御リクエスト 1 行を処理してレスポンス JSON(1 行ぶん)を返す。
-fn handle_control(line: &str) -> String {
+async fn handle_control(line: &str, stats: &stats::Stats, pipeline: &Arc<pipeline::Pipeline>) -> String {
     let err = |msg: String| serde_json::json!({ "ok": false, "error": msg }).to_string();
 
     let value: serde_json::Value = match serde_json::from_str(line.trim()) {
@@ -370,6 +463,27 @@ fn handle_control(line: &str) -> String {
                 Ok(c) => c,
                 Err(e) => return err(format!("invalid config: {e}")),
             };
+            if let Err(e) = EncodingRules::compile(&cfg.encoding_rules) {
+                return err(format!("invalid config: {e}"));
+            }
+            if let Err(e) = AccessList::compile(&cfg.server.acl) {
+                return err(format!("invalid config: {e}"));
+            }
+            if let Some(Err(e)) = cfg.server.rate_limit.as_ref().map(|r| r.check()) {
+                return err(format!("invalid config: {e}"));
+            }
+            if let Some(path) = cfg.server.resolve.as_ref().and_then(|r| r.hosts_file.as_ref())
+                && let Err(e) = names::load_hosts(path)
+            {
+                return err(format!("invalid config: hosts_file {path}: {e}"));
+            }
+            let listeners = cfg.server.listeners();
+            if listeners.is_empty() {
+                return err("invalid config: no listeners".to_string());
+            }
+            if let Some(e) = listeners.iter().find_map(|l| l.check().err()) {
+                return err(format!("invalid config: {e}"));
+            }
             match config::save_config(&cfg) {
                 Ok(()) => {
                     log::info!("config updated via control port (restart required to apply)");
@@ -378,6 +492,13 @@ fn handle_control(line: &str) -> String {
                 Err(e) => err(format!("failed to save config: {e}")),
             }
         }
+        Some("get_stats") => {
+            serde_json::json!({ "ok": true, "stats": stats.snapshot() }).to_string()
+        }
+        Some("import") => match import::start(&value, pipeline).await {
+            Ok(messages) => serde_json::json!({ "ok": true, "messages": messages }).to_string(),
+            Err(e) => err(e),
+        },
         other => err(format!("unknown cmd: {:?}", other)),
     }
 }
@@ -0,0 +1,268 @@
+//! 受信した 1 メッセージの処理(パース → 統計 → 流量制限 → 保存 → GUI への配信)と、
+//! その前に行う送信元のアクセス制御。
+//!
+//! UDP・TCP・TLS・RELP・Unix ソケットのどれで受けても同じ処理を通すため、受信ループからはここだけを呼ぶ。
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+use vlt_syslog_core::acl::{AccessList, AclAction, AclRule};
+use vlt_syslog_core::encoding::EncodingRules;
+use vlt_syslog_core::names::Resolver;
+use vlt_syslog_core::{
+    ParseOptions, PeerCred, Severity, SyslogMessage, parse_syslog_with, stream,
+};
+
+use crate::config::{Config, ListenerConfig, Protocol};
+use crate::ratelimit::RateLimiter;
+use crate::stats::Stats;
+use crate::store::{MessageStore, Ticket};
+
+/// メッセージを送ってきた相手と、受けた待ち受け口。
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub listener: Arc<ListenerConfig>,
+    /// TLS でクライアント証明書を提示した接続なら、その Subject。
+    pub tls_subject: Option<String>,
+    /// Unix ドメインソケットで受けたなら、送ってきたプロセスの資格情報。
+    pub cred: Option<PeerCred>,
+}
+
+impl Peer {
+    pub fn new(listener: &Arc<ListenerConfig>, addr: SocketAddr) -> Self {
+        Self {
+            // デュアルスタックの待ち受けでは IPv4 の送信元が ::ffff:a.b.c.d で届くので IPv4 に戻す
+            addr: SocketAddr::new(addr.ip().to_canonical(), addr.port()),
+            listener: Arc::clone(listener),
+            tls_subject: None,
+            cred: None,
+        }
+    }
+
+    /// Unix ドメインソケットで受けたもの。送信元 IP が無いので 127.0.0.1 とみなし、
+    /// 統計やエンコーディングルールではローカルからの送信として扱う。
+    pub fn local(listener: &Arc<ListenerConfig>, cred: Option<PeerCred>) -> Self {
+        Self {
+            cred,
+            ..Self::new(listener, SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        }
+    }
+}
+
+/// [`Pipeline::handle`] の結果。RELP はこれで応答を決める。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// 保存を頼み、配信した。番号は [`Pipeline::sync`] で書き込みを確定させるのに使う(store が無ければ None)。
+    Accepted(Option<Ticket>),
+    /// 流量制限で捨てた。
+    Suppressed,
+    /// 保存を頼めなかった(キューが溢れた等)。
+    Failed,
+}
+
+pub struct Pipeline {
+    acl: AccessList,
+    rate_limiter: Option<RateLimiter>,
+    resolver: Option<Resolver>,
+    /// 受信したメッセージの保存先。性能測定やテストでは持たない。
+    store: Option<MessageStore>,
+    encoding_rules: EncodingRules,
+    strict: bool,
+    stream_raw: bool,
+    stats: Arc<Stats>,
+    stream_tx: broadcast::Sender<String>,
+}
+
+impl Pipeline {
+    pub fn new(config: &Config, stats: Arc<Stats>, stream_tx: broadcast::Sender<String>) -> Self {
+        // 送信元ごとのエンコーディングルール。書き方に誤りがあればルール無しで動かし続ける。
+        let encoding_rules = EncodingRules::compile(&config.encoding_rules).unwrap_or_else(|e| {
+            log::error!("Ignoring encoding_rules: {}", e);
+            EncodingRules::empty()
+        });
+        // 送信元のアクセス制御。書き方に誤りがあれば全部拒否する(開けっ放しにしない)。
+        let acl = AccessList::compile(&config.server.acl).unwrap_or_else(|e| {
+            log::error!("Invalid acl; rejecting all network sources: {}", e);
+            let deny_all = ["0.0.0.0/0", "::/0"].map(|source| AclRule {
+                action: AclAction::Deny,
+                source: source.to_string(),
+                listener: None,
+            });
+            AccessList::compile(&deny_all).expect("deny-all rules compile")
+        });
+        // 流量制限。値に誤りがあれば制限せずに動かし続ける。
+        let rate_limiter = config
+            .server
+            .rate_limit
+            .clone()
+            .filter(|r| match r.check() {
+                Ok(()) => true,
+                Err(e) => {
+                    log::error!("Ignoring rate_limit: {}", e);
+                    false
+                }
+            })
+            .map(RateLimiter::new);
+        // 送信元の名前解決。hosts ファイルが読めなければ逆引きだけで動かし続ける。
+        let resolver = config.server.resolve.as_ref().map(|r| {
+            Resolver::from_config(r).unwrap_or_else(|e| {
+                log::error!("Ignoring {}", e);
+                Resolver::new(
+                    Default::default(),
+                    r.reverse_dns,
+                    std::time::Duration::from_secs(r.ttl_secs),
+                )
+            })
+        });
+        Self {
+            acl,
+            rate_limiter,
+            resolver,
+            store: None,
+            encoding_rules,
+            strict: config.server.strict,
+            stream_raw: config.server.stream_raw,
+            stats,
+            stream_tx,
+        }
+    }
+
+    /// 受信したメッセージを `store` に保存する。
+    pub fn with_store(mut self, store: MessageStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// 送信元を受け入れるか(パースの前に呼ぶ)。拒否したものは統計に数える。
+    /// Unix ソケットで受けたもの(送信元 IP が無い)には呼ばない。
+    pub fn admit(&self, peer: &Peer) -> bool {
+        if self.acl.is_empty() || self.acl.is_allowed(peer.addr.ip(), &peer.listener.name()) {
+            return true;
+        }
+        log::debug!("Rejected {} on {} by acl", peer.addr, peer.listener.name());
+        self.stats.record_rejected(peer.addr.ip());
+        false
+    }
+
+    /// 受信した 1 メッセージ分のバイト列を処理する。`truncated` はデータグラムを上限で切り詰めたもの。
+    pub fn handle(&self, raw_msg: &[u8], truncated: bool, peer: &Peer) -> Outcome {
+        self.process(raw_msg, truncated, peer, Utc::now(), true)
+    }
+
+    /// [`Pipeline::handle`] の、受信時刻を指定する版。キャプチャの取り込みで元の時刻を残すのに使う。
+    /// 流量制限は今の時刻で数えるので、取り込んだもの(一度に流すと一気に溜まる)には掛けない。
+    pub fn handle_at(
+        &self,
+        raw_msg: &[u8],
+        truncated: bool,
+        peer: &Peer,
+        received_at: DateTime<Utc>,
+    ) -> Outcome {
+        self.process(raw_msg, truncated, peer, received_at, false)
+    }
+
+    /// `limit` なら流量制限に掛ける。
+    fn process(
+        &self,
+        raw_msg: &[u8],
+        truncated: bool,
+        peer: &Peer,
+        received_at: DateTime<Utc>,
+        limit: bool,
+    ) -> Outcome {
+        let src = peer.addr;
+        let mut parsed = parse_syslog_with(
+            raw_msg,
+            &ParseOptions {
+                received_at,
+                source: Some(src.ip()),
+                encoding_rules: &self.encoding_rules,
+                strict: self.strict,
+                ..ParseOptions::default()
+            },
+        );
+        parsed.tls_subject = peer.tls_subject.clone();
+        parsed.listener = Some(peer.listener.name());
+        parsed.peer_cred = peer.cred;
+        parsed.truncated = truncated;
+        // Unix ソケットで受けたもの(Peer::local の 127.0.0.1 は仮の値)には送信元 IP を付けない
+        if !matches!(
+            peer.listener.protocol,
+            Protocol::Unix | Protocol::UnixStream
+        ) {
+            parsed.source_addr = Some(src.ip());
+            parsed.source_name = self.resolver.as_ref().and_then(|r| r.name(src.ip()));
+        }
+        self.stats.record(src.ip(), &parsed);
+        if let Some(limiter) = self.rate_limiter.as_ref().filter(|_| limit) {
+            let key = limiter.key(&parsed, src.ip());
+            if !limiter.check(&key, &parsed, Instant::now()) {
+                self.stats.record_suppressed();
+                return Outcome::Suppressed;
+            }
+        }
+        self.write(parsed, src, peer.listener.label.as_deref())
+    }
+
+    /// 流量制限で抑えた件数を "N messages suppressed from X" の 1 件ずつにして書く。定期的に呼ぶ。
+    pub fn report_suppressed(&self) {
+        let Some(limiter) = &self.rate_limiter else {
+            return;
+        };
+        for summary in limiter.summaries(Instant::now()) {
+            // 送信元 IP で数えていればその送信元から届いたものとして書き、src で絞り込めるようにする
+            let source = summary.key.parse::<IpAddr>().ok();
+            let mut msg =
+                SyslogMessage::internal(Severity::Warning, "vlt-syslogd", summary.message());
+            msg.source_addr = source;
+            let ip = source.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+            self.write(msg, SocketAddr::new(ip, 0), None);
+        }
+    }
+
+    /// 1 件を保存し、GUI へ配信する。`label` は受けた待ち受け口の名前(付けてあれば)。
+    /// 頼めなかったことは store が診断ログに書くので、ここでは書かない。
+    fn write(&self, mut parsed: SyslogMessage, src: SocketAddr, label: Option<&str>) -> Outcome {
+        let outcome = match &self.store {
+            Some(store) => match store.write(&parsed, src, label) {
+                Ok(ticket) => Outcome::Accepted(Some(ticket)),
+                Err(_) => Outcome::Failed,
+            },
+            None => Outcome::Accepted(None),
+        };
+
+        // GUI フロントエンドへ JSON Lines(1メッセージ=1行 JSON)で配信する。
+        // 購読者がいなければ send は Err になるが、その場合は捨ててよい。
+        if !self.stream_raw {
+            parsed.raw = Vec::new();
+        }
+        if let Ok(json) = stream::encode_line(&parsed) {
+            let _ = self.stream_tx.send(json);
+        }
+        outcome
+    }
+
+    /// 受信統計。受信ループがソケットのドロップ数を登録するのに使う。
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// handle したメッセージのバッファを OS に渡すよう頼む。1 秒ごとに呼ぶ。
+    pub fn flush(&self) {
+        if let Some(store) = &self.store {
+            store.flush();
+        }
+    }
+
+    /// `from` 以降に保存を頼んだメッセージをディスクまで書き込み、確定させる。RELP は応答を返す前に呼ぶ。
+    pub async fn sync(&self, from: Ticket) -> std::io::Result<()> {
+        match &self.store {
+            Some(store) => store.sync(from).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -10,7 +10,7 @@
 
 use std::path::PathBuf;
 
-/// データ(config.toml / logs)の保存先ルートディレクトリ。
+/// データ(config.toml / logs / messages)の保存先ルートディレクトリ。
 ///
 /// 既定のシステム領域:
 ///   - Windows : `C:\ProgramData\vlt-syslogd`
@@ -42,6 +42,11 @@ pub fn log_dir() -> PathBuf {
     data_dir().join("logs")
 }
 
+/// 受信したメッセージの既定の保存先(`<data_dir>/messages`)。
+pub fn message_dir() -> PathBuf {
+    data_dir().join("messages")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,6 +63,7 @@ mod tests {
         assert_eq!(data_dir(), PathBuf::from("/tmp/vlt-srv-test"));
         assert_eq!(config_path(), PathBuf::from("/tmp/vlt-srv-test/config.toml"));
         assert_eq!(log_dir(), PathBuf::from("/tmp/vlt-srv-test/logs"));
+        assert_eq!(message_dir(), PathBuf::from("/tmp/vlt-srv-test/messages"));
         unsafe { std::env::remove_var("VLT_SYSLOGD_DATA_DIR") };
     }
 
@@ -0,0 +1,416 @@
+//! 送信元ごとの流量制限(`[server.rate_limit]`)。
+//!
+//! 1 台の機器が毎秒数千件を送り続けても、他の機器のログがファイルログや Console の表示で
+//! 埋もれないようにする。送信元(または HOSTNAME / TAG)ごとのトークンバケツで数え、
+//! 超えた分は policy に従って捨てる。抑えた件数は [`RateLimiter::summaries`] で取り出し、
+//! pipeline が "N messages suppressed from X" の 1 件としてログと配信に書く。
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use vlt_syslog_core::{Severity, SyslogMessage};
+
+use crate::config::{RateKey, RateLimitConfig, RatePolicy};
+
+/// バケツを持つ上限(全体)。[`Shard`] ごとに MAX_BUCKETS / SHARDS まで持ち、超えたら
+/// 満タンに戻ったバケツを捨て、それでも空かなければいちばん長く使っていない(refilled_at の古い)バケツを捨てる。
+const MAX_BUCKETS: usize = 4096;
+
+/// バケツを分けて持つ数。数える単位で振り分け、受信ワーカーどうしでロックを取り合わないようにする。
+/// 送信元を偽った UDP が押し寄せても、バケツを捨てるときに調べるのは 1 つのシャードの分だけで済む。
+const SHARDS: usize = 16;
+
+/// 抑えている最中に捨てたバケツのまとめを、数えた単位ごとに持つ上限(シャードごと)。
+/// 超えた分は [`EVICTED_KEY`] の 1 件にまとめる。
+const MAX_EVICTED: usize = 16;
+
+/// MAX_EVICTED を超えて捨てたバケツをまとめた分の名前。
+const EVICTED_KEY: &str = "other sources";
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    shards: [Mutex<Shard>; SHARDS],
+    hasher: RandomState,
+}
+
+#[derive(Default)]
+struct Shard {
+    buckets: HashMap<String, Bucket>,
+    /// 抑えている最中に捨てたバケツの抑えた件数。次の [`RateLimiter::summaries`] で返す。
+    evicted: Vec<Summary>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// 今の集計期間に上限を超えた件数(通したものも含む)。sample の間引きに使う。
+    excess: u64,
+    suppressed: u64,
+    sampled: u64,
+    /// severity(0〜7)ごとの抑えた件数。
+    by_severity: [u64; 8],
+    last_suppressed: Option<String>,
+    /// 上限を超え始めた時刻。None なら今は抑えていない。
+    since: Option<Instant>,
+}
+
+/// 1 つのバケツの、集計期間ぶんの抑えた件数。
+#[derive(Debug)]
+pub struct Summary {
+    /// 数えた単位(送信元 IP / HOSTNAME / TAG)。
+    pub key: String,
+    pub suppressed: u64,
+    pub sampled: u64,
+    pub by_severity: [u64; 8],
+    pub last_suppressed: Option<String>,
+    pub period: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            shards: std::array::from_fn(|_| Mutex::new(Shard::default())),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// メッセージを数える単位。
+    pub fn key(&self, msg: &SyslogMessage, source: IpAddr) -> String {
+        let by_key = match self.config.by {
+            RateKey::Source => None,
+            RateKey::Host => msg.hostname.clone(),
+            RateKey::Tag => msg.tag.clone().or_else(|| msg.app_name.clone()),
+        };
+        by_key.unwrap_or_else(|| source.to_string())
+    }
+
+    fn shard(&self, key: &str) -> MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(key) as usize % SHARDS;
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 1 件を数え、通すなら true。抑えたものは集計に足す。
+    pub fn check(&self, key: &str, msg: &SyslogMessage, now: Instant) -> bool {
+        let mut shard = self.shard(key);
+        let burst = f64::from(self.config.burst);
+        if shard.buckets.len() >= MAX_BUCKETS / SHARDS && !shard.buckets.contains_key(key) {
+            shard.evict(now, self.config.rate, burst);
+        }
+        let bucket = shard
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket::new(burst, now));
+        bucket.refill(now, self.config.rate, burst);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return true;
+        }
+
+        bucket.since.get_or_insert(now);
+        bucket.excess += 1;
+        if self.config.policy == RatePolicy::Sample
+            && (bucket.excess - 1).is_multiple_of(self.config.sample_every)
+        {
+            bucket.sampled += 1;
+            return true;
+        }
+        bucket.suppressed += 1;
+        bucket.by_severity[msg.severity.clone() as usize] += 1;
+        if self.config.policy == RatePolicy::Summarise {
+            bucket.last_suppressed = Some(msg.content.clone());
+        }
+        false
+    }
+
+    /// 集計期間(`summary_interval_secs`)が過ぎたバケツの抑えた件数を取り出して 0 に戻す。
+    /// 抑えておらず満タンに戻ったバケツはここで捨てる。捨てたバケツのまとめもここで返す。定期的に呼ぶ。
+    pub fn summaries(&self, now: Instant) -> Vec<Summary> {
+        let interval = Duration::from_secs(self.config.summary_interval_secs);
+        let burst = f64::from(self.config.burst);
+        let mut out = Vec::new();
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+            out.append(&mut shard.evicted);
+            shard.buckets.retain(|key, bucket| {
+                bucket.refill(now, self.config.rate, burst);
+                let Some(since) = bucket.since else {
+                    return bucket.tokens < burst;
+                };
+                if now.saturating_duration_since(since) >= interval {
+                    out.extend(bucket.take_summary(key, now));
+                }
+                true
+            });
+        }
+        out
+    }
+}
+
+impl Shard {
+    /// 満タンに戻ったバケツを捨て、それでも空かなければいちばん長く使っていないバケツを捨てる。
+    /// 抑えている最中のバケツを捨てるときは、抑えた件数を evicted に残す。
+    fn evict(&mut self, now: Instant, rate: f64, burst: f64) {
+        self.buckets
+            .retain(|_, bucket| !bucket.is_idle(now, rate, burst));
+        if self.buckets.len() < MAX_BUCKETS / SHARDS {
+            return;
+        }
+        let Some(oldest) = self
+            .buckets
+            .iter()
+            .min_by_key(|(_, bucket)| bucket.refilled_at)
+            .map(|(key, _)| key.clone())
+        else {
+            return;
+        };
+        if let Some(mut bucket) = self.buckets.remove(&oldest)
+            && let Some(summary) = bucket.take_summary(&oldest, now)
+        {
+            if self.evicted.len() < MAX_EVICTED {
+                self.evicted.push(summary);
+            } else if let Some(other) = self.evicted.iter_mut().find(|s| s.key == EVICTED_KEY) {
+                other.merge(summary);
+            } else {
+                self.evicted.push(Summary {
+                    key: EVICTED_KEY.to_string(),
+                    ..summary
+                });
+            }
+        }
+    }
+}
+
+impl Bucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            refilled_at: now,
+            excess: 0,
+            suppressed: 0,
+            sampled: 0,
+            by_severity: [0; 8],
+            last_suppressed: None,
+            since: None,
+        }
+    }
+
+    /// 抑えておらず、補充すれば満タンに戻るか(捨てても数え方が変わらない)。
+    fn is_idle(&self, now: Instant, rate: f64, burst: f64) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.since.is_none() && self.tokens + elapsed * rate >= burst
+    }
+
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.refilled_at = now;
+    }
+
+    /// 上限を超え始めてからの件数を取り出し、抑えていない状態に戻す。抑えたものが無ければ None。
+    fn take_summary(&mut self, key: &str, now: Instant) -> Option<Summary> {
+        let since = self.since.take()?;
+        let summary = (self.suppressed > 0).then(|| Summary {
+            key: key.to_string(),
+            suppressed: self.suppressed,
+            sampled: self.sampled,
+            by_severity: self.by_severity,
+            last_suppressed: self.last_suppressed.take(),
+            period: now.saturating_duration_since(since),
+        });
+        self.excess = 0;
+        self.suppressed = 0;
+        self.sampled = 0;
+        self.by_severity = [0; 8];
+        summary
+    }
+}
+
+impl Summary {
+    /// 別の単位のまとめを足す。期間は長いほう、最後に抑えた本文は `other` のもの(あれば)にする。
+    fn merge(&mut self, other: Summary) {
+        self.suppressed += other.suppressed;
+        self.sampled += other.sampled;
+        for (total, n) in self.by_severity.iter_mut().zip(other.by_severity) {
+            *total += n;
+        }
+        if other.last_suppressed.is_some() {
+            self.last_suppressed = other.last_suppressed;
+        }
+        self.period = self.period.max(other.period);
+    }
+
+    /// ログと配信に書くまとめの本文。
+    pub fn message(&self) -> String {
+        let mut text = format!(
+            "{} messages suppressed from {} in the last {}s",
+            self.suppressed,
+            self.key,
+            self.period.as_secs()
+        );
+        if self.sampled > 0 {
+            text.push_str(&format!(" ({} sampled messages passed)", self.sampled));
+        }
+        if let Some(last) = &self.last_suppressed {
+            let counts: Vec<String> = self
+                .by_severity
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| **n > 0)
+                .map(|(i, n)| format!("{:?}={}", Severity::from_pri(i as u8), n))
+                .collect();
+            text.push_str(&format!(" [{}] last: {}", counts.join(" "), last));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vlt_syslog_core::parse_syslog;
+
+    fn limiter(policy: RatePolicy) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            rate: 1.0,
+            burst: 3,
+            by: RateKey::Source,
+            policy,
+            sample_every: 2,
+            summary_interval_secs: 10,
+        })
+    }
+
+    fn passed(limiter: &RateLimiter, key: &str, n: usize, now: Instant) -> usize {
+        let msg = parse_syslog(b"<11>app: link down");
+        (0..n).filter(|_| limiter.check(key, &msg, now)).count()
+    }
+
+    #[test]
+    fn burst_passes_then_excess_is_suppressed_and_summarised() {
+        let limiter = limiter(RatePolicy::Drop);
+        let t0 = Instant::now();
+        assert_eq!(passed(&limiter, "192.0.2.7", 10, t0), 3);
+        // 他の送信元は巻き込まない
+        assert_eq!(passed(&limiter, "192.0.2.8", 1, t0), 1);
+        // 1 秒で 1 件ぶん補充される
+        assert_eq!(
+            passed(&limiter, "192.0.2.7", 2, t0 + Duration::from_secs(1)),
+            1
+        );
+
+        assert!(limiter.summaries(t0 + Duration::from_secs(5)).is_empty());
+        let summaries = limiter.summaries(t0 + Duration::from_secs(10));
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].suppressed, 8);
+        assert_eq!(
+            summaries[0].message(),
+            "8 messages suppressed from 192.0.2.7 in the last 10s"
+        );
+        // 取り出した後は 0 から数え直す
+        assert!(limiter.summaries(t0 + Duration::from_secs(30)).is_empty());
+    }
+
+    #[test]
+    fn sample_passes_every_nth_excess_message() {
+        let limiter = limiter(RatePolicy::Sample);
+        let t0 = Instant::now();
+        // 3 件は burst、超えた 7 件のうち 1・3・5・7 件目を通す
+        assert_eq!(passed(&limiter, "sw01", 10, t0), 7);
+        let summaries = limiter.summaries(t0 + Duration::from_secs(10));
+        assert_eq!((summaries[0].suppressed, summaries[0].sampled), (3, 4));
+    }
+
+    #[test]
+    fn summarise_reports_severities_and_last_content() {
+        let limiter = limiter(RatePolicy::Summarise);
+        let t0 = Instant::now();
+        passed(&limiter, "sw01", 5, t0);
+        let summaries = limiter.summaries(t0 + Duration::from_secs(10));
+        assert_eq!(
+            summaries[0].message(),
+            "2 messages suppressed from sw01 in the last 10s [Error=2] last: link down"
+        );
+    }
+
+    #[test]
+    fn key_falls_back_to_source_address() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            by: RateKey::Host,
+            ..limiter(RatePolicy::Drop).config
+        });
+        let source: IpAddr = "192.0.2.7".parse().unwrap();
+        let with_host = parse_syslog(b"<13>Oct 18 12:00:00 sw01 app: hi");
+        assert_eq!(limiter.key(&with_host, source), "sw01");
+        let without_host = parse_syslog(b"<13>app: hi");
+        assert_eq!(limiter.key(&without_host, source), "192.0.2.7");
+    }
+
+    #[test]
+    fn new_sources_get_their_own_bucket_when_full() {
+        let limiter = limiter(RatePolicy::Drop);
+        let t0 = Instant::now();
+        // 全部の送信元が抑えられている最中でも、新しい送信元は他とバケツを共有しない
+        for i in 0..MAX_BUCKETS * 2 {
+            passed(&limiter, &i.to_string(), 4, t0);
+        }
+        assert_eq!(passed(&limiter, "flood", 10, t0), 3);
+        assert_eq!(passed(&limiter, "quiet", 1, t0), 1);
+        let buckets: usize = limiter
+            .shards
+            .iter()
+            .map(|s| s.lock().unwrap().buckets.len())
+            .sum();
+        assert!(buckets <= MAX_BUCKETS);
+    }
+
+    #[test]
+    fn evicted_buckets_keep_their_summary() {
+        let limiter = limiter(RatePolicy::Drop);
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_millis(1);
+        passed(&limiter, "0", 5, t0);
+        // "0" のシャードが一杯になり、いちばん古い "0" が捨てられる
+        for i in 1..MAX_BUCKETS * 2 {
+            passed(&limiter, &i.to_string(), 4, t1);
+        }
+        let summaries = limiter.summaries(t1);
+        let first = summaries.iter().find(|s| s.key == "0").unwrap();
+        assert_eq!(first.suppressed, 2);
+        // 捨てたものの件数は、単位ごとか other sources にまとめて全部返る
+        let evicted: u64 = summaries.iter().map(|s| s.suppressed).sum();
+        let kept: usize = limiter
+            .shards
+            .iter()
+            .map(|s| s.lock().unwrap().buckets.len())
+            .sum();
+        assert_eq!(evicted, 2 + (MAX_BUCKETS * 2 - 1 - kept) as u64);
+        assert!(summaries.iter().any(|s| s.key == EVICTED_KEY));
+    }
+
+    #[test]
+    fn idle_buckets_are_dropped_before_busy_ones() {
+        let t0 = Instant::now();
+        let mut shard = Shard::default();
+        for i in 0..MAX_BUCKETS / SHARDS {
+            let mut bucket = Bucket::new(3.0, t0);
+            bucket.tokens = 2.0;
+            shard.buckets.insert(i.to_string(), bucket);
+        }
+        let busy = shard.buckets.get_mut("0").unwrap();
+        busy.tokens = 0.0;
+        busy.since = Some(t0);
+        busy.suppressed = 5;
+        // 1 秒で満タンに戻ったバケツを捨て、抑えているバケツは残す
+        shard.evict(t0 + Duration::from_secs(1), 1.0, 3.0);
+        assert_eq!(shard.buckets.keys().collect::<Vec<_>>(), ["0"]);
+        assert!(shard.evicted.is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+//! RELP での syslog 受信(rsyslog の `omrelp`)。
+//!
+//! `syslog` コマンドで届いたメッセージは pipeline に通して保存(messages/)への書き込みを
+//! ディスクまで確定させ、その後で `200 OK` を返す。保存できなければ、また流量制限で捨てたものには
+//! `500` を返し、送信元に再送させる(捨てたものを受け取ったことにしない)。
+//! 応答を返す前に落ちれば、送信元は未応答のメッセージを再送する。
+//! 1 回の読み込みで届いた分はまとめて書き込みを確定させてから応答する。
+//! 接続数の上限(`max_connections`)と accept に失敗したときの扱いは TCP 受信と同じ。
+
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use vlt_syslog_core::listen;
+use vlt_syslog_core::relp::{RelpDecoder, encode_rsp};
+
+use crate::config::ListenerConfig;
+use crate::pipeline::{Outcome, Peer, Pipeline};
+use crate::tcp::{accept_failed, connection_permit};
+
+/// 保存できなかったメッセージへの応答。
+const STORE_FAILED: &str = "500 cannot store message";
+/// 流量制限で捨てたメッセージへの応答。
+const RATE_LIMITED: &str = "500 rate limited";
+
+pub async fn run_relp_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::from_std(listen::tcp_listener(&cfg.addr)?)?;
+    log::info!("vlt-syslogd-srv RELP listener started on {}", cfg.addr);
+
+    let idle_timeout = Duration::from_secs(cfg.idle_timeout_secs);
+    let connections = Arc::new(Semaphore::new(cfg.max_connections));
+    loop {
+        let permit = connection_permit(&connections, &cfg).await;
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                accept_failed(&cfg, &e).await;
+                continue;
+            }
+        };
+        let peer = Peer::new(&cfg, addr);
+        if !pipeline.admit(&peer) {
+            continue;
+        }
+        let pipeline = Arc::clone(&pipeline);
+        let max_message_size = cfg.max_message_size;
+        tokio::spawn(async move {
+            let _permit = permit;
+            handle_connection(socket, &peer, max_message_size, idle_timeout, &pipeline).await;
+        });
+    }
+}
+
+/// `open` への応答。受け付けるコマンドは syslog だけ。
+fn open_response() -> String {
+    format!(
+        "200 OK\nrelp_version=0\nrelp_software=vlt-syslogd,{}\ncommands=syslog",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// 1 本の RELP セッションを処理する。
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    mut socket: S,
+    peer: &Peer,
+    max_message_size: usize,
+    idle_timeout: Duration,
+    pipeline: &Pipeline,
+) {
+    let addr = peer.addr;
+    log::debug!("RELP connection from {}", addr);
+    let mut decoder = RelpDecoder::new(max_message_size);
+    let mut opened = false;
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let n = match tokio::time::timeout(idle_timeout, socket.read(&mut buf)).await {
+            Err(_) => {
+                log::info!(
+                    "RELP connection from {} idle for {}s; closing",
+                    addr,
+                    idle_timeout.as_secs()
+                );
+                return;
+            }
+            Ok(Err(e)) => {
+                log::warn!("RELP read error from {}: {}", addr, e);
+                return;
+            }
+            Ok(Ok(0)) => {
+                log::debug!("RELP connection from {} closed", addr);
+                return;
+            }
+            Ok(Ok(n)) => n,
+        };
+        decoder.extend(&buf[..n]);
+
+        // (txnr, 応答)。stored は保存を頼んだ syslog の応答の位置で、確定できなければ 500 に差し替える
+        let mut replies: Vec<(u64, String)> = Vec::new();
+        let mut stored = Vec::new();
+        let mut from = None;
+        let mut closing = false;
+        while !closing {
+            let frame = match decoder.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("RELP connection from {} closed: {}", addr, e);
+                    closing = true;
+                    break;
+                }
+            };
+            match frame.command.as_str() {
+                "open" => {
+                    opened = true;
+                    replies.push((frame.txnr, open_response()));
+                }
+                "syslog" if opened => match pipeline.handle(&frame.data, false, peer) {
+                    Outcome::Accepted(ticket) => {
+                        if let Some(ticket) = ticket {
+                            from.get_or_insert(ticket);
+                        }
+                        stored.push(replies.len());
+                        replies.push((frame.txnr, "200 OK".to_string()));
+                    }
+                    Outcome::Suppressed => replies.push((frame.txnr, RATE_LIMITED.to_string())),
+                    Outcome::Failed => replies.push((frame.txnr, STORE_FAILED.to_string())),
+                },
+                "syslog" => {
+                    log::warn!("RELP connection from {} sent syslog before open", addr);
+                    replies.push((frame.txnr, "500 session not opened".to_string()));
+                    closing = true;
+                }
+                "close" => {
+                    replies.push((frame.txnr, String::new()));
+                    closing = true;
+                }
+                other => {
+                    log::debug!(
+                        "RELP connection from {} sent unknown command {}",
+                        addr,
+                        other
+                    );
+                    replies.push((frame.txnr, "500 unknown command".to_string()));
+                }
+            }
+        }
+
+        // 書き込みをディスクまで確定させてから応答する(ここで落ちれば送信元は再送する)
+        if let Some(from) = from
+            && let Err(e) = pipeline.sync(from).await
+        {
+            log::error!("Cannot store messages from RELP connection {}: {}", addr, e);
+            for &i in &stored {
+                replies[i].1 = STORE_FAILED.to_string();
+            }
+        }
+        let replies: Vec<u8> = replies
+            .iter()
+            .flat_map(|(txnr, status)| encode_rsp(*txnr, status))
+            .collect();
+        if !replies.is_empty()
+            && let Err(e) = socket.write_all(&replies).await
+        {
+            log::warn!("RELP write error to {}: {}", addr, e);
+            return;
+        }
+        if closing {
+            let _ = socket.shutdown().await;
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        Config, MessageStoreConfig, Protocol, RateKey, RateLimitConfig, RatePolicy,
+    };
+    use crate::stats::Stats;
+    use crate::store::MessageStore;
+    use tokio::sync::broadcast;
+    use vlt_syslog_core::relp::encode_frame;
+    use vlt_syslog_core::stream::decode_line;
+
+    /// 送信側の一連のフレームを流し、返ってきた応答と配信ストリームに流れた本文を返す。
+    async fn session(input: &[u8]) -> (String, Vec<String>) {
+        session_with(input, &Config::default(), None).await
+    }
+
+    /// [`session`] の、設定を指定し、受信したメッセージを `store` に保存する版。
+    async fn session_with(
+        input: &[u8],
+        config: &Config,
+        store: Option<MessageStore>,
+    ) -> (String, Vec<String>) {
+        let (stream_tx, mut rx) = broadcast::channel(16);
+        let mut pipeline = Pipeline::new(config, Arc::new(Stats::new()), stream_tx);
+        if let Some(store) = store {
+            pipeline = pipeline.with_store(store);
+        }
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client.write_all(input).await.unwrap();
+        client.shutdown().await.unwrap();
+        let listener = Arc::new(ListenerConfig::new(Protocol::Relp, "127.0.0.1:0"));
+        let peer = Peer::new(&listener, "192.0.2.1:40000".parse().unwrap());
+        handle_connection(server, &peer, 1024, Duration::from_secs(5), &pipeline).await;
+
+        let mut replies = String::new();
+        client.read_to_string(&mut replies).await.unwrap();
+        let mut contents = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            contents.push(decode_line(&line).unwrap().content);
+        }
+        (replies, contents)
+    }
+
+    #[tokio::test]
+    async fn messages_are_acknowledged_after_handling() {
+        let mut input = encode_frame(1, "open", b"relp_version=0\ncommands=syslog");
+        input.extend(encode_frame(2, "syslog", b"<13>app: one"));
+        input.extend(encode_frame(3, "syslog", b"<13>app: two"));
+        input.extend(encode_frame(4, "close", b""));
+        let (replies, contents) = session(&input).await;
+
+        assert_eq!(contents, ["one", "two"]);
+        let replies: Vec<&str> = replies.lines().collect();
+        assert!(replies[0].starts_with("1 rsp "), "{replies:?}");
+        assert!(replies.contains(&"commands=syslog"), "{replies:?}");
+        assert_eq!(
+            replies[replies.len() - 3..],
+            ["2 rsp 6 200 OK", "3 rsp 6 200 OK", "4 rsp 0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn syslog_before_open_is_refused() {
+        let (replies, contents) = session(&encode_frame(1, "syslog", b"<13>app: hi")).await;
+        assert!(contents.is_empty());
+        assert_eq!(replies, "1 rsp 22 500 session not opened\n");
+    }
+
+    #[tokio::test]
+    async fn messages_that_cannot_be_stored_are_not_acknowledged() {
+        // 保存先がファイルなのでディレクトリを作れず、書き込めない
+        let dir = std::env::temp_dir().join(format!("vlt-relp-store-{}", std::process::id()));
+        std::fs::write(&dir, b"").unwrap();
+        let store = MessageStore::new(&MessageStoreConfig {
+            dir: dir.display().to_string(),
+            ..MessageStoreConfig::default()
+        });
+        let mut input = encode_frame(1, "open", b"relp_version=0\ncommands=syslog");
+        input.extend(encode_frame(2, "syslog", b"<13>app: one"));
+        input.extend(encode_frame(3, "close", b""));
+        let (replies, _) = session_with(&input, &Config::default(), Some(store)).await;
+        std::fs::remove_file(&dir).unwrap();
+
+        let replies: Vec<&str> = replies.lines().collect();
+        assert!(!replies.contains(&"2 rsp 6 200 OK"), "{replies:?}");
+        assert!(
+            replies.contains(&"2 rsp 24 500 cannot store message"),
+            "{replies:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_messages_are_not_acknowledged() {
+        let mut config = Config::default();
+        config.server.rate_limit = Some(RateLimitConfig {
+            rate: 1.0,
+            burst: 1,
+            by: RateKey::Source,
+            policy: RatePolicy::Drop,
+            sample_every: 1,
+            summary_interval_secs: 60,
+        });
+        let mut input = encode_frame(1, "open", b"relp_version=0\ncommands=syslog");
+        input.extend(encode_frame(2, "syslog", b"<13>app: one"));
+        input.extend(encode_frame(3, "syslog", b"<13>app: two"));
+        let (replies, contents) = session_with(&input, &config, None).await;
+
+        assert_eq!(contents, ["one"]);
+        let replies: Vec<&str> = replies.lines().collect();
+        assert_eq!(
+            replies[replies.len() - 2..],
+            ["2 rsp 6 200 OK", "3 rsp 16 500 rate limited"]
+        );
+    }
+}
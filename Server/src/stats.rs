@@ -0,0 +1,312 @@
+//! 受信統計。診断(規格外の箇所)の種類ごとの件数と、診断を出した送信元ごとの件数を数え、
+//! 制御ポートの `get_stats` で返す。どの機器が壊れた syslog を送っているかを調べるためのもの。
+//!
+//! 送信元のアクセス制御(`[[server.acl]]`)で拒否したパケット(TCP 系は接続)も送信元ごとに数える。
+//!
+//! UDP の待ち受け口については、受信バッファ溢れでカーネルが捨てたデータグラム数も返す
+//! (受信ループが追いつかずに取りこぼした数。Linux のみ)。
+//!
+//! 値はプロセス内だけに持ち、再起動で 0 に戻る。
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use vlt_syslog_core::{DiagnosticKind, SyslogMessage};
+
+/// 送信元ごとの集計を持つ上限。超えた分の送信元は種類ごとの件数(拒否は合計)にだけ数える。
+const MAX_SOURCES: usize = 1024;
+
+/// 送信元ごとの集計を分けて持つ数。送信元 IP で振り分け、別の送信元どうしでロックを取り合わないようにする。
+const SHARDS: usize = 16;
+
+/// カーネルが捨てたデータグラムの累計を読む関数。ソケットが閉じていれば None。
+type DropCounter = Box<dyn Fn() -> Option<u64> + Send + Sync>;
+
+/// 受信ループから毎件呼ばれるので、件数は atomic で数え、ロックは送信元ごとの集計(診断付き・拒否したもの)
+/// にだけ、送信元で分けた [`Shard`] ごとに取る。[`Stats::snapshot`] でまとめる。
+pub struct Stats {
+    started_at: DateTime<Utc>,
+    received: AtomicU64,
+    truncated: AtomicU64,
+    suppressed: AtomicU64,
+    with_diagnostics: AtomicU64,
+    nonconforming: AtomicU64,
+    /// 診断の種類ごとの件数([`DiagnosticKind::ALL`] の順)。
+    by_kind: [AtomicU64; DiagnosticKind::ALL.len()],
+    rejected: AtomicU64,
+    shards: [Mutex<Shard>; SHARDS],
+    hasher: std::hash::RandomState,
+    /// shards に持っている送信元の数(MAX_SOURCES で打ち切る)。
+    sources: AtomicUsize,
+    rejected_sources: AtomicUsize,
+    /// 待ち受け口の名前と、そのソケットのドロップ数を読む関数。
+    drop_counters: Mutex<Vec<(String, DropCounter)>>,
+}
+
+#[derive(Default)]
+struct Shard {
+    by_source: HashMap<IpAddr, SourceStats>,
+    rejected_by_source: HashMap<IpAddr, u64>,
+}
+
+#[derive(Default, Clone, Serialize)]
+struct SourceStats {
+    /// 診断付きで届いたメッセージ数。
+    messages: u64,
+    /// 診断の種類ごとの件数。
+    diagnostics: BTreeMap<&'static str, u64>,
+}
+
+/// `get_stats` の応答の `stats`。
+#[derive(Serialize)]
+pub struct Snapshot {
+    pub started_at: String,
+    pub received: u64,
+    /// 上限を超えて切り詰めたデータグラムの数。
+    pub truncated: u64,
+    /// 流量制限で抑えた(ログにも配信にも出さなかった)メッセージの数。
+    pub suppressed: u64,
+    pub with_diagnostics: u64,
+    pub nonconforming: u64,
+    pub diagnostics: BTreeMap<&'static str, u64>,
+    /// 待ち受け口ごとの、受信バッファ溢れでカーネルが捨てたデータグラム数(読めた分だけ)。
+    pub kernel_drops: BTreeMap<String, u64>,
+    /// 診断の多い順。
+    pub sources: Vec<SourceSnapshot>,
+    /// アクセス制御で拒否したパケット(TCP 系は接続)の数。
+    pub rejected: u64,
+    /// 拒否した数の多い順。
+    pub rejected_sources: Vec<RejectedSnapshot>,
+}
+
+#[derive(Serialize)]
+pub struct SourceSnapshot {
+    pub addr: IpAddr,
+    pub messages: u64,
+    pub diagnostics: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Serialize)]
+pub struct RejectedSnapshot {
+    pub addr: IpAddr,
+    pub packets: u64,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            received: AtomicU64::new(0),
+            truncated: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            with_diagnostics: AtomicU64::new(0),
+            nonconforming: AtomicU64::new(0),
+            by_kind: std::array::from_fn(|_| AtomicU64::new(0)),
+            rejected: AtomicU64::new(0),
+            shards: std::array::from_fn(|_| Mutex::new(Shard::default())),
+            hasher: std::hash::RandomState::new(),
+            sources: AtomicUsize::new(0),
+            rejected_sources: AtomicUsize::new(0),
+            drop_counters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 待ち受け口 `listener` のカーネルでのドロップ数を `get_stats` で返すようにする。
+    pub fn add_drop_counter(
+        &self,
+        listener: String,
+        counter: impl Fn() -> Option<u64> + Send + Sync + 'static,
+    ) {
+        let mut counters = self.drop_counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.push((listener, Box::new(counter)));
+    }
+
+    /// 受信した 1 件を数える。
+    pub fn record(&self, src: IpAddr, msg: &SyslogMessage) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        if msg.truncated {
+            self.truncated.fetch_add(1, Ordering::Relaxed);
+        }
+        if msg.diagnostics.is_empty() {
+            return;
+        }
+        self.with_diagnostics.fetch_add(1, Ordering::Relaxed);
+        if msg.nonconforming {
+            self.nonconforming.fetch_add(1, Ordering::Relaxed);
+        }
+        for d in &msg.diagnostics {
+            self.by_kind[d.kind as usize].fetch_add(1, Ordering::Relaxed);
+        }
+        let mut shard = self.shard(src);
+        if !shard.by_source.contains_key(&src) && !reserve(&self.sources) {
+            return;
+        }
+        let source = shard.by_source.entry(src).or_default();
+        source.messages += 1;
+        for d in &msg.diagnostics {
+            *source.diagnostics.entry(d.kind.as_str()).or_default() += 1;
+        }
+    }
+
+    /// 流量制限で抑えた 1 件を数える(受信は [`Stats::record`] で数え済み)。
+    pub fn record_suppressed(&self) {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// アクセス制御で拒否した 1 件を数える。
+    pub fn record_rejected(&self, src: IpAddr) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        let mut shard = self.shard(src);
+        if !shard.rejected_by_source.contains_key(&src) && !reserve(&self.rejected_sources) {
+            return;
+        }
+        *shard.rejected_by_source.entry(src).or_default() += 1;
+    }
+
+    fn shard(&self, src: IpAddr) -> std::sync::MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(src) as usize % SHARDS;
+        self.shards[index].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// その時点の値をまとめる。数えている最中の 1 件が一部の項目にだけ入っていることはある。
+    pub fn snapshot(&self) -> Snapshot {
+        let mut sources = Vec::new();
+        let mut rejected_sources = Vec::new();
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+            sources.extend(shard.by_source.iter().map(|(addr, s)| SourceSnapshot {
+                addr: *addr,
+                messages: s.messages,
+                diagnostics: s.diagnostics.clone(),
+            }));
+            rejected_sources.extend(shard.rejected_by_source.iter().map(|(addr, packets)| {
+                RejectedSnapshot {
+                    addr: *addr,
+                    packets: *packets,
+                }
+            }));
+        }
+        sources.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.addr.cmp(&b.addr)));
+        rejected_sources.sort_by(|a, b| b.packets.cmp(&a.packets).then(a.addr.cmp(&b.addr)));
+        let mut kernel_drops = BTreeMap::new();
+        for (listener, counter) in self
+            .drop_counters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            if let Some(drops) = counter() {
+                *kernel_drops.entry(listener.clone()).or_default() += drops;
+            }
+        }
+        Snapshot {
+            started_at: self.started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            received: self.received.load(Ordering::Relaxed),
+            truncated: self.truncated.load(Ordering::Relaxed),
+            suppressed: self.suppressed.load(Ordering::Relaxed),
+            with_diagnostics: self.with_diagnostics.load(Ordering::Relaxed),
+            nonconforming: self.nonconforming.load(Ordering::Relaxed),
+            diagnostics: DiagnosticKind::ALL
+                .iter()
+                .zip(&self.by_kind)
+                .map(|(k, n)| (k.as_str(), n.load(Ordering::Relaxed)))
+                .filter(|(_, n)| *n > 0)
+                .collect(),
+            kernel_drops,
+            sources,
+            rejected: self.rejected.load(Ordering::Relaxed),
+            rejected_sources,
+        }
+    }
+}
+
+/// 送信元の枠を 1 つ取る。MAX_SOURCES に達していれば false。
+fn reserve(count: &AtomicUsize) -> bool {
+    count
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+            (n < MAX_SOURCES).then_some(n + 1)
+        })
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vlt_syslog_core::parse_syslog;
+
+    #[test]
+    fn counts_diagnostics_per_kind_and_source() {
+        let stats = Stats::new();
+        let good: IpAddr = "192.0.2.1".parse().unwrap();
+        let bad: IpAddr = "192.0.2.2".parse().unwrap();
+        stats.record(good, &parse_syslog(b"<13>Oct 18 12:00:00 host app: ok"));
+        stats.record(bad, &parse_syslog(b"<999>broken"));
+        stats.record(bad, &parse_syslog(b"<13>1 - host app - - [oops hello"));
+
+        let snap = stats.snapshot();
+        assert_eq!(snap.received, 3);
+        assert_eq!(snap.with_diagnostics, 2);
+        assert_eq!(snap.nonconforming, 0);
+        assert_eq!(snap.diagnostics.get("invalid_pri"), Some(&1));
+        assert_eq!(snap.diagnostics.get("malformed_sd"), Some(&1));
+        assert_eq!(snap.sources.len(), 1);
+        assert_eq!(snap.sources[0].addr, bad);
+        assert_eq!(snap.sources[0].messages, 2);
+    }
+
+    #[test]
+    fn sources_beyond_the_limit_are_only_counted_in_totals() {
+        let stats = Stats::new();
+        let addr = |i: usize| IpAddr::from([10, 0, (i / 256) as u8, (i % 256) as u8]);
+        let broken = parse_syslog(b"<999>broken");
+        for i in 0..MAX_SOURCES + 10 {
+            stats.record(addr(i), &broken);
+            stats.record_rejected(addr(i));
+        }
+        // 上限に達した後も、既に持っている送信元は数え続ける
+        stats.record(addr(0), &broken);
+        stats.record_rejected(addr(0));
+
+        let snap = stats.snapshot();
+        assert_eq!(snap.with_diagnostics, MAX_SOURCES as u64 + 11);
+        assert_eq!(
+            snap.diagnostics.get("invalid_pri"),
+            Some(&(MAX_SOURCES as u64 + 11))
+        );
+        assert_eq!(snap.sources.len(), MAX_SOURCES);
+        assert_eq!(
+            (snap.sources[0].addr, snap.sources[0].messages),
+            (addr(0), 2)
+        );
+        assert_eq!(snap.rejected, MAX_SOURCES as u64 + 11);
+        assert_eq!(snap.rejected_sources.len(), MAX_SOURCES);
+        assert_eq!(snap.rejected_sources[0].packets, 2);
+    }
+
+    #[test]
+    fn dropped_messages_are_counted() {
+        let stats = Stats::new();
+        let src: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut truncated = parse_syslog(b"<13>app: long");
+        truncated.truncated = true;
+        stats.record(src, &truncated);
+        stats.record_suppressed();
+        stats.record_suppressed();
+        // ワーカーごとのソケットは同じ待ち受け口の名前で足し、閉じたソケットは数えない
+        stats.add_drop_counter("udp://[::]:514".to_string(), || Some(3));
+        stats.add_drop_counter("udp://[::]:514".to_string(), || Some(4));
+        stats.add_drop_counter("udp://[::]:1514".to_string(), || None);
+
+        let snap = stats.snapshot();
+        assert_eq!((snap.received, snap.truncated, snap.suppressed), (1, 1, 2));
+        assert_eq!(
+            snap.kernel_drops.into_iter().collect::<Vec<_>>(),
+            [("udp://[::]:514".to_string(), 7)]
+        );
+    }
+}
@@ -0,0 +1,600 @@
+//! 受信したメッセージの保存(`[messages]`)。
+//!
+//! サービス自身の診断ログ(flexi_logger の vlt-syslogd-srv.log)とは別のディレクトリ・ファイルに書くので、
+//! logging.level を warn に上げても受信したメッセージの記録は止まらない。
+//!
+//! ファイル名は `messages-YYYYMMDD.log`(hourly なら `messages-YYYYMMDD-HH.log`、json なら拡張子は .jsonl)。
+//! 日時はサービスの動いている地域の時刻で、書き込んだ時点のもの。max_size_mb を超えたら
+//! `messages-YYYYMMDD.1.log` のように番号を足した次のファイルに移る。
+//! 起動したとき・1 分ごと・ファイルを切り替えるときに、keep_days より前に書き終えたファイルを消す。
+//! raw 形式は 1 件 1 行に保つため、本文中の LF / CR を rsyslog と同じ `#012` / `#015` に置き換える。
+//!
+//! ファイルへの書き込みは専用のスレッドで行い、受信側(tokio のワーカー)はキューに積むだけにする。
+//! バッファは [`MessageStore::flush`] で OS に渡す(1 秒ごと)。RELP は応答の前に
+//! [`MessageStore::sync`] でディスクまで書き込みを確定させ、書けなかったことを受け取る。
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use tokio::sync::oneshot;
+use vlt_syslog_core::{SyslogMessage, stream};
+
+use crate::config::{MessageFormat, MessageStoreConfig, Rotate};
+
+/// 保存ファイルの名前の先頭。保存期間を過ぎたファイルを消すときもこれで見分ける。
+const PREFIX: &str = "messages-";
+
+/// 書き込みスレッドのキューの長さ。溢れた分は保存せずに捨てる(受信側を待たせない)。
+const QUEUE_LEN: usize = 64 * 1024;
+
+/// 保存期間を過ぎたファイルを探す間隔(flush のたびには探さない)。
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct MessageStore {
+    format: MessageFormat,
+    dir: PathBuf,
+    queue: SyncSender<Command>,
+    /// 書き込みを頼んだ件数。[`Ticket`] の番号に使う。
+    requested: AtomicU64,
+    /// キューが溢れていることを診断ログに書いたか(空くまで繰り返し書かない)。
+    overflowing: AtomicBool,
+}
+
+/// 書き込みを頼んだ 1 件の番号。[`MessageStore::sync`] で確かめる範囲の始まりに使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ticket(u64);
+
+/// 書き込みスレッドへの依頼。
+enum Command {
+    Append(Ticket, Vec<u8>, DateTime<Local>),
+    Flush,
+    /// 指定した番号以降の書き込みをディスクまで確定させ、どれかが書けていなければ Err を返す。
+    Sync(Ticket, oneshot::Sender<io::Result<()>>),
+}
+
+struct Writer {
+    dir: PathBuf,
+    rotate: Rotate,
+    extension: &'static str,
+    /// 1 ファイルの上限バイト数。0 なら分けない。
+    max_size: u64,
+    keep: Option<Duration>,
+    current: Option<Current>,
+    /// ここまでに受け取った番号の最大。
+    appended: Option<Ticket>,
+    /// 書けなかった番号の最大。バッファごと書けなかったときは appended にする。
+    failed: Option<Ticket>,
+    pruned_at: Instant,
+    /// 開けない・書けないことを診断ログに書いたか(直るまで繰り返し書かない)。
+    failing: bool,
+}
+
+/// 書き込み中のファイル。
+struct Current {
+    period: String,
+    index: u32,
+    size: u64,
+    file: BufWriter<File>,
+}
+
+impl MessageStore {
+    /// 保存先のディレクトリを作り、書き込みスレッドを立てる(MessageStore を捨てるとスレッドも終わる)。
+    pub fn new(config: &MessageStoreConfig) -> Self {
+        let writer = Writer::new(config);
+        let dir = writer.dir.clone();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::error!("Cannot create message directory {}: {}", dir.display(), e);
+        }
+        let (queue, rx) = mpsc::sync_channel(QUEUE_LEN);
+        std::thread::spawn(move || writer.run(rx));
+        Self {
+            format: config.format,
+            dir,
+            queue,
+            requested: AtomicU64::new(0),
+            overflowing: AtomicBool::new(false),
+        }
+    }
+
+    /// 保存先のディレクトリ。
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 1 件の書き込みを頼む。`src` と `label` はテキスト形式の [src:] / [lsn:] に使う。
+    /// 書けたかどうかは返した番号を [`MessageStore::sync`] に渡して確かめる。
+    /// Err(頼めなかった)のときは診断ログに書いてあるので、呼び出し側は書かなくてよい。
+    pub fn write(
+        &self,
+        msg: &SyslogMessage,
+        src: SocketAddr,
+        label: Option<&str>,
+    ) -> io::Result<Ticket> {
+        let mut line = match self.format {
+            MessageFormat::Text => text_line(msg, src, label).into_bytes(),
+            MessageFormat::Json => match stream::encode_line(msg) {
+                Ok(json) => json.into_bytes(),
+                Err(e) => {
+                    log::warn!("Cannot encode message from {}: {}", src, e);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+            },
+            MessageFormat::Raw => raw_line(&msg.raw),
+        };
+        line.push(b'\n');
+        let ticket = Ticket(self.requested.fetch_add(1, Ordering::Relaxed) + 1);
+        match self
+            .queue
+            .try_send(Command::Append(ticket, line, Local::now()))
+        {
+            Ok(()) => {
+                self.overflowing.store(false, Ordering::Relaxed);
+                Ok(ticket)
+            }
+            Err(TrySendError::Full(_)) => {
+                if !self.overflowing.swap(true, Ordering::Relaxed) {
+                    log::error!("Message store queue is full; dropping received messages");
+                }
+                Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "message store queue is full",
+                ))
+            }
+            Err(TrySendError::Disconnected(_)) => Err(stopped()),
+        }
+    }
+
+    /// バッファに溜めた分を OS に渡すよう頼む(待たない)。保存期間を過ぎたファイルもここで消す。
+    pub fn flush(&self) {
+        // 溢れていれば次の回に任せる
+        let _ = self.queue.try_send(Command::Flush);
+    }
+
+    /// `from` 以降に頼んだ書き込みをディスクまで確定させる。どれかが書けていなければ Err。
+    pub async fn sync(&self, from: Ticket) -> io::Result<()> {
+        let (reply, rx) = oneshot::channel();
+        match self.queue.try_send(Command::Sync(from, reply)) {
+            Ok(()) => {}
+            // 一杯なら空くまで待つ。ワーカーを止めないよう blocking のスレッドで積む
+            Err(TrySendError::Full(command)) => {
+                let queue = self.queue.clone();
+                tokio::task::spawn_blocking(move || queue.send(command))
+                    .await
+                    .map_err(io::Error::other)?
+                    .map_err(|_| stopped())?;
+            }
+            Err(TrySendError::Disconnected(_)) => return Err(stopped()),
+        }
+        rx.await.map_err(|_| stopped())?
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "message store writer has stopped",
+    )
+}
+
+/// raw 形式の 1 行(改行は含まない)。末尾の空白・改行を落とし、途中の LF / CR は `#012` / `#015` にする。
+fn raw_line(raw: &[u8]) -> Vec<u8> {
+    let mut line = Vec::with_capacity(raw.len());
+    for &b in raw.trim_ascii_end() {
+        match b {
+            b'\n' => line.extend_from_slice(b"#012"),
+            b'\r' => line.extend_from_slice(b"#015"),
+            _ => line.push(b),
+        }
+    }
+    line
+}
+
+impl Writer {
+    fn new(config: &MessageStoreConfig) -> Self {
+        Self {
+            dir: config.directory(),
+            rotate: config.rotate,
+            extension: match config.format {
+                MessageFormat::Json => "jsonl",
+                MessageFormat::Text | MessageFormat::Raw => "log",
+            },
+            max_size: config.max_size_mb * 1024 * 1024,
+            keep: (config.keep_days > 0)
+                .then(|| Duration::from_secs(config.keep_days * 24 * 60 * 60)),
+            current: None,
+            appended: None,
+            failed: None,
+            pruned_at: Instant::now(),
+            failing: false,
+        }
+    }
+
+    /// 書き込みスレッドの本体。依頼が途絶えたら(MessageStore を捨てたら)バッファを書いて終わる。
+    fn run(mut self, rx: Receiver<Command>) {
+        self.prune();
+        for command in rx {
+            match command {
+                Command::Append(ticket, line, now) => {
+                    self.appended = self.appended.max(Some(ticket));
+                    match self.append(&line, now) {
+                        Ok(()) => self.failing = false,
+                        Err(e) => {
+                            self.failed = self.failed.max(Some(ticket));
+                            self.report(&e);
+                        }
+                    }
+                }
+                Command::Flush => {
+                    if let Err(e) = self.flush() {
+                        self.report(&e);
+                    }
+                    if self.pruned_at.elapsed() >= PRUNE_INTERVAL {
+                        self.prune();
+                    }
+                }
+                Command::Sync(from, reply) => {
+                    let _ = reply.send(self.sync(from));
+                }
+            }
+        }
+        let _ = self.flush();
+    }
+
+    fn append(&mut self, line: &[u8], now: DateTime<Local>) -> io::Result<()> {
+        let period = now
+            .format(match self.rotate {
+                Rotate::Daily => "%Y%m%d",
+                Rotate::Hourly => "%Y%m%d-%H",
+            })
+            .to_string();
+        let len = line.len() as u64;
+        let switch_to = match &self.current {
+            Some(c) if c.period != period => Some(0),
+            Some(c) if self.max_size > 0 && c.size > 0 && c.size + len > self.max_size => {
+                Some(c.index + 1)
+            }
+            Some(_) => None,
+            None => Some(0),
+        };
+        if let Some(index) = switch_to {
+            self.switch(period, index)?;
+        }
+        let Some(current) = &mut self.current else {
+            return Err(io::Error::other("no message file is open"));
+        };
+        current.file.write_all(line).map_err(|e| {
+            io::Error::new(e.kind(), format!("Cannot write received messages: {e}"))
+        })?;
+        current.size += len;
+        Ok(())
+    }
+
+    /// バッファに溜めた分を OS に渡す。渡せなかった分はどれが書けたか分からないので、
+    /// ここまでに受け取ったもの全部を書けなかったものとする。
+    fn flush(&mut self) -> io::Result<()> {
+        let Some(current) = &mut self.current else {
+            return Ok(());
+        };
+        current.file.flush().map_err(|e| {
+            self.failed = self.appended;
+            io::Error::new(e.kind(), format!("Cannot write received messages: {e}"))
+        })
+    }
+
+    fn sync(&mut self, from: Ticket) -> io::Result<()> {
+        self.flush()?;
+        if let Some(current) = &self.current {
+            current.file.get_ref().sync_data().map_err(|e| {
+                io::Error::new(e.kind(), format!("Cannot sync received messages: {e}"))
+            })?;
+        }
+        if self.failed >= Some(from) {
+            return Err(io::Error::other("received messages could not be stored"));
+        }
+        Ok(())
+    }
+
+    /// 書き込み先を `period` の `index` 番以降で上限に達していない最初のファイルに切り替える。
+    fn switch(&mut self, period: String, mut index: u32) -> io::Result<()> {
+        if let Some(mut old) = self.current.take() {
+            let _ = old.file.flush();
+        }
+        self.prune();
+        loop {
+            let path = self.path(&period, index);
+            let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+            if self.max_size > 0 && size >= self.max_size {
+                index += 1;
+                continue;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| {
+                    io::Error::new(e.kind(), format!("Cannot open {}: {e}", path.display()))
+                })?;
+            self.current = Some(Current {
+                period,
+                index,
+                size,
+                file: BufWriter::new(file),
+            });
+            return Ok(());
+        }
+    }
+
+    fn path(&self, period: &str, index: u32) -> PathBuf {
+        let name = match index {
+            0 => format!("{PREFIX}{period}.{}", self.extension),
+            n => format!("{PREFIX}{period}.{n}.{}", self.extension),
+        };
+        self.dir.join(name)
+    }
+
+    /// 保存期間を過ぎたファイルを消す。
+    fn prune(&mut self) {
+        self.pruned_at = Instant::now();
+        let Some(keep) = self.keep else {
+            return;
+        };
+        let Some(cutoff) = SystemTime::now().checked_sub(keep) else {
+            return;
+        };
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let ours =
+                name.starts_with(PREFIX) && (name.ends_with(".log") || name.ends_with(".jsonl"));
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t < cutoff);
+            if ours && expired {
+                match std::fs::remove_file(entry.path()) {
+                    Ok(()) => log::info!("Removed expired message file {}", name),
+                    Err(e) => log::warn!("Cannot remove expired message file {}: {}", name, e),
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, error: &io::Error) {
+        if !self.failing {
+            log::error!("{}", error);
+            self.failing = true;
+        }
+    }
+}
+
+/// テキスト形式の 1 行。
+/// facility は PRI が無ければ "-"、規格外(192 以上等)なら "invalid" と書く。
+/// 受信時刻(rcv)は UTC、機器時刻(dev)は機器のオフセットのまま RFC 3339 で書き、
+/// どの地域で読んでも時刻を取り違えないようにする。
+fn text_line(parsed: &SyslogMessage, src: SocketAddr, label: Option<&str>) -> String {
+    let facility = if parsed.invalid_pri {
+        "invalid"
+    } else {
+        parsed.facility.map_or("-", |f| f.name())
+    };
+    let device_time = parsed.device_timestamp.map_or_else(
+        || "-".to_string(),
+        |t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    );
+    // 方言(JSON / logfmt / CEF 等)で項目を取り出せたものは、本文の後ろに logfmt で並べる。
+    // 本文が項目の並びそのもの(msg キーの無い JSON など)なら二重に書かない。
+    let mut text = parsed.content.clone();
+    if !parsed.fields.is_empty() {
+        let fields = parsed.fields_logfmt();
+        if fields != text {
+            text = format!("{text} {fields}");
+        }
+    }
+    // raw 形式と同じく、1 メッセージを 1 行に収めるため LF / CR は #012 / #015 にする。
+    let text = text.trim_end().replace('\n', "#012").replace('\r', "#015");
+    // 規格外の箇所があれば [diag:種類@位置,...] を足す(strict なら先頭に "nonconforming;")。
+    let mut diag = String::new();
+    if !parsed.diagnostics.is_empty() {
+        let list: Vec<String> = parsed.diagnostics.iter().map(|d| d.to_string()).collect();
+        let mark = if parsed.nonconforming {
+            "nonconforming;"
+        } else {
+            ""
+        };
+        diag = format!(" [diag:{}{}]", mark, list.join(","));
+    }
+    // 上限で切り詰めたものは [truncated] を足す(本文・raw は切り詰めた後のもの)。
+    if parsed.truncated {
+        diag = format!(" [truncated]{diag}");
+    }
+    // TLS のクライアント証明書で相手が分かっていれば [tls:Subject] を足す。
+    if let Some(subject) = &parsed.tls_subject {
+        diag = format!(" [tls:{subject}]{diag}");
+    }
+    // Unix ソケットで受けたものは送ってきたプロセスを [cred:pid=..,uid=..,gid=..] で足す。
+    if let Some(cred) = &parsed.peer_cred {
+        diag = format!(" [cred:{cred}]{diag}");
+    }
+    // 送信元の名前が引けていれば [name:名前] を足す。
+    if let Some(name) = &parsed.source_name {
+        diag = format!(" [name:{name}]{diag}");
+    }
+    // 名前(label)を付けた待ち受け口で受けたものは [lsn:名前] を足す。
+    if let Some(label) = label {
+        diag = format!(" [lsn:{label}]{diag}");
+    }
+    format!(
+        "[{:?}] [fac:{}] [src:{}] [rcv:{}] [dev:{}] [enc:{}] [dialect:{}]{} {}",
+        parsed.severity,
+        facility,
+        src,
+        parsed
+            .received_at
+            .to_rfc3339_opts(SecondsFormat::Millis, true),
+        device_time,
+        parsed.encoding,
+        parsed.dialect.as_deref().unwrap_or("-"),
+        diag,
+        text
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use vlt_syslog_core::parse_syslog;
+
+    fn config(name: &str, format: MessageFormat) -> (MessageStoreConfig, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("vlt-store-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = MessageStoreConfig {
+            dir: dir.display().to_string(),
+            format,
+            ..MessageStoreConfig::default()
+        };
+        (config, dir)
+    }
+
+    fn files(dir: &PathBuf) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn today(dir: &Path, extension: &str) -> PathBuf {
+        dir.join(format!(
+            "messages-{}.{extension}",
+            Local::now().format("%Y%m%d")
+        ))
+    }
+
+    #[test]
+    fn files_rotate_by_day_and_size() {
+        let (config, dir) = config("rotate", MessageFormat::Raw);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut writer = Writer::new(&config);
+        writer.max_size = 20;
+        let day1 = Local.with_ymd_and_hms(2026, 10, 18, 23, 59, 0).unwrap();
+        let day2 = Local.with_ymd_and_hms(2026, 10, 19, 0, 0, 1).unwrap();
+        writer.append(b"<13>app: first\n", day1).unwrap();
+        // 上限を超えるので同じ日の次の番号へ
+        writer.append(b"<13>app: second\n", day1).unwrap();
+        writer.append(b"<13>app: third\n", day2).unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            files(&dir),
+            [
+                "messages-20261018.1.log",
+                "messages-20261018.log",
+                "messages-20261019.log"
+            ]
+        );
+        let first = std::fs::read_to_string(dir.join("messages-20261018.log")).unwrap();
+        assert_eq!(first, "<13>app: first\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_files_are_removed_at_startup() {
+        let (config, dir) = config("prune", MessageFormat::Text);
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("messages-20200101.log");
+        let unrelated = dir.join("notes.log");
+        for path in [&old, &unrelated] {
+            let file = File::create(path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_836_800))
+                .unwrap();
+        }
+
+        let store = MessageStore::new(&config);
+        let msg = parse_syslog(b"<13>Oct 18 12:00:00 host app: hello");
+        let ticket = store
+            .write(&msg, "192.0.2.7:40000".parse().unwrap(), Some("edge"))
+            .unwrap();
+        store.sync(ticket).await.unwrap();
+
+        let names = files(&dir);
+        assert!(!names.contains(&"messages-20200101.log".to_string()));
+        assert!(names.contains(&"notes.log".to_string()));
+        let line = std::fs::read_to_string(today(&dir, "log")).unwrap();
+        assert!(line.starts_with("[Notice] [fac:user] [src:192.0.2.7:40000] [rcv:"));
+        assert!(line.ends_with("[lsn:edge] hello\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn json_format_round_trips_through_the_stream_decoder() {
+        let (config, dir) = config("json", MessageFormat::Json);
+        let store = MessageStore::new(&config);
+        let msg = parse_syslog(b"<13>Oct 18 12:00:00 host app: hello");
+        let ticket = store
+            .write(&msg, "192.0.2.7:40000".parse().unwrap(), None)
+            .unwrap();
+        store.sync(ticket).await.unwrap();
+
+        let text = std::fs::read_to_string(today(&dir, "jsonl")).unwrap();
+        let decoded = stream::decode_line(text.trim_end()).unwrap();
+        assert_eq!(decoded.content, "hello");
+        assert_eq!(decoded.raw, msg.raw);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn raw_format_keeps_one_message_per_line() {
+        let (config, dir) = config("raw", MessageFormat::Raw);
+        let store = MessageStore::new(&config);
+        let msg = parse_syslog(b"<13>app: first line\r\nsecond line\n");
+        let ticket = store
+            .write(&msg, "192.0.2.7:40000".parse().unwrap(), None)
+            .unwrap();
+        store.sync(ticket).await.unwrap();
+
+        let text = std::fs::read_to_string(today(&dir, "log")).unwrap();
+        assert_eq!(text, "<13>app: first line#015#012second line\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn text_format_keeps_one_message_per_line() {
+        let (config, dir) = config("text-lines", MessageFormat::Text);
+        let store = MessageStore::new(&config);
+        let msg = parse_syslog(b"<13>app: first line\r\nsecond line\n");
+        let ticket = store
+            .write(&msg, "192.0.2.7:40000".parse().unwrap(), None)
+            .unwrap();
+        store.sync(ticket).await.unwrap();
+
+        let text = std::fs::read_to_string(today(&dir, "log")).unwrap();
+        assert_eq!(text.lines().count(), 1, "{text}");
+        assert!(text.ends_with(" first line#015#012second line\n"), "{text}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sync_reports_messages_that_could_not_be_stored() {
+        // 保存先がファイルなのでディレクトリを作れず、書き込めない
+        let (config, dir) = config("unwritable", MessageFormat::Text);
+        std::fs::write(&dir, b"").unwrap();
+        let store = MessageStore::new(&config);
+        let msg = parse_syslog(b"<13>app: lost");
+        let ticket = store
+            .write(&msg, "192.0.2.7:40000".parse().unwrap(), None)
+            .unwrap();
+        assert!(store.sync(ticket).await.is_err());
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+//! TCP での syslog 受信(RFC 6587)。
+//!
+//! 区切り方(Octet Counting / LF)は接続ごとに自動で判定する([`vlt_syslog_core::framing`])。
+//! 1 メッセージの上限を超えた・区切りが読めない接続と、一定時間何も送ってこない接続は閉じる。
+//! 同時に受け付ける接続は `max_connections` まで。accept に失敗しても(ファイルディスクリプタが
+//! 尽きた等)待ち受けは止めず、少し待ってから続ける。
+//! `proxy_protocol` の待ち受け口では、`trusted_proxies` からの接続の先頭で PROXY ヘッダを読み、
+//! そこに書かれた送信元をメッセージの送信元とする(TLS 受信も同じ)。
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::TcpListener;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use vlt_syslog_core::framing::FrameDecoder;
+use vlt_syslog_core::listen;
+use vlt_syslog_core::proxy::{self, Parse};
+
+use crate::config::ListenerConfig;
+use crate::pipeline::{Peer, Pipeline};
+
+/// accept に失敗したときに、次を試すまで待つ時間。
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+pub async fn run_tcp_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::from_std(listen::tcp_listener(&cfg.addr)?)?;
+    log::info!("vlt-syslogd-srv TCP listener started on {}", cfg.addr);
+
+    let idle_timeout = Duration::from_secs(cfg.idle_timeout_secs);
+    let trusted_proxies = cfg.trusted_proxies()?;
+    let connections = Arc::new(Semaphore::new(cfg.max_connections));
+    loop {
+        let permit = connection_permit(&connections, &cfg).await;
+        let (mut socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                accept_failed(&cfg, &e).await;
+                continue;
+            }
+        };
+        let mut peer = Peer::new(&cfg, addr);
+        // ロードバランサからの接続は、PROXY ヘッダにある本来の送信元で弾く
+        let via_proxy = trusted_proxies
+            .as_ref()
+            .is_some_and(|t| t.contains(addr.ip()));
+        if !via_proxy && !pipeline.admit(&peer) {
+            continue;
+        }
+        let pipeline = Arc::clone(&pipeline);
+        let max_message_size = cfg.max_message_size;
+        tokio::spawn(async move {
+            let _permit = permit;
+            if via_proxy && !accept_proxy(&mut socket, &mut peer, idle_timeout, &pipeline).await {
+                return;
+            }
+            handle_connection(socket, &peer, max_message_size, idle_timeout, &pipeline).await;
+        });
+    }
+}
+
+/// 接続 1 本ぶんの枠を取る。上限に達していれば診断ログに書き、どれかの接続が閉じるまで待つ。
+/// 枠は接続を処理するタスクに渡し、閉じたときに返す。
+pub(crate) async fn connection_permit(
+    connections: &Arc<Semaphore>,
+    cfg: &ListenerConfig,
+) -> OwnedSemaphorePermit {
+    if let Ok(permit) = Arc::clone(connections).try_acquire_owned() {
+        return permit;
+    }
+    log::warn!(
+        "Listener {} reached max_connections ({}); waiting for connections to close",
+        cfg.name(),
+        cfg.max_connections
+    );
+    Arc::clone(connections)
+        .acquire_owned()
+        .await
+        .expect("connection semaphore is never closed")
+}
+
+/// accept の失敗を診断ログに書き、少し待つ。待ち受けは続ける。
+pub(crate) async fn accept_failed(cfg: &ListenerConfig, e: &std::io::Error) {
+    log::error!("Cannot accept a connection on {}: {}", cfg.name(), e);
+    tokio::time::sleep(ACCEPT_RETRY).await;
+}
+
+/// ロードバランサからの接続の先頭で PROXY ヘッダを読み、`peer` の送信元をそこに書かれたものにして
+/// アクセス制御にかける。続けてよければ true。ヘッダの後ろは読まない(TLS はその直後に ClientHello が来る)。
+pub(crate) async fn accept_proxy<S: AsyncRead + Unpin>(
+    socket: &mut S,
+    peer: &mut Peer,
+    idle_timeout: Duration,
+    pipeline: &Pipeline,
+) -> bool {
+    let proxy_addr = peer.addr;
+    let header = match tokio::time::timeout(idle_timeout, read_proxy_header(socket)).await {
+        Ok(Ok(header)) => header,
+        Ok(Err(e)) => {
+            log::warn!("Closing connection from proxy {}: {}", proxy_addr, e);
+            return false;
+        }
+        Err(_) => {
+            log::info!("PROXY header from {} timed out; closing", proxy_addr);
+            return false;
+        }
+    };
+    // LOCAL(ロードバランサ自身のヘルスチェック)などは送信元を書き換えない
+    if let Some(source) = header.source {
+        peer.addr = SocketAddr::new(source.ip().to_canonical(), source.port());
+    }
+    log::debug!("Connection from {} via proxy {}", peer.addr, proxy_addr);
+    pipeline.admit(peer)
+}
+
+/// PROXY ヘッダ(v1 / v2)をちょうど読み切る。
+async fn read_proxy_header<S: AsyncRead + Unpin>(
+    socket: &mut S,
+) -> Result<proxy::ProxyHeader, Box<dyn Error>> {
+    let mut buf = Vec::new();
+    loop {
+        match proxy::parse(&buf)? {
+            Parse::Need(n) => {
+                let len = buf.len();
+                buf.resize(len + n, 0);
+                socket.read_exact(&mut buf[len..]).await?;
+            }
+            Parse::Done(header) => return Ok(header),
+        }
+    }
+}
+
+/// 1 本の接続を読み切る。切り出したメッセージは順に pipeline へ渡す。
+/// TLS 受信も復号後のストリームをここへ渡す。
+pub(crate) async fn handle_connection<S: AsyncRead + Unpin>(
+    mut socket: S,
+    peer: &Peer,
+    max_message_size: usize,
+    idle_timeout: Duration,
+    pipeline: &Pipeline,
+) {
+    let addr = peer.addr;
+    log::debug!("TCP syslog connection from {}", addr);
+    let mut decoder = FrameDecoder::new(max_message_size);
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let n = match tokio::time::timeout(idle_timeout, socket.read(&mut buf)).await {
+            Err(_) => {
+                log::info!(
+                    "TCP syslog connection from {} idle for {}s; closing",
+                    addr,
+                    idle_timeout.as_secs()
+                );
+                return;
+            }
+            Ok(Err(e)) => {
+                log::warn!("TCP syslog read error from {}: {}", addr, e);
+                return;
+            }
+            Ok(Ok(0)) => {
+                if let Some(frame) = decoder.finish() {
+                    pipeline.handle(&frame, false, peer);
+                }
+                log::debug!("TCP syslog connection from {} closed", addr);
+                return;
+            }
+            Ok(Ok(n)) => n,
+        };
+        decoder.extend(&buf[..n]);
+        loop {
+            match decoder.next_frame() {
+                Ok(Some(frame)) => {
+                    pipeline.handle(&frame, false, peer);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let framing = decoder.framing().map_or("-", |f| f.as_str());
+                    log::warn!(
+                        "TCP syslog connection from {} closed ({} framing): {}",
+                        addr,
+                        framing,
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Protocol};
+    use crate::stats::Stats;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+    use tokio::sync::broadcast;
+    use vlt_syslog_core::SyslogMessage;
+    use vlt_syslog_core::stream::decode_line;
+
+    /// 1 本の接続で送ったメッセージが、区切りごとに配信ストリームへ流れること。
+    async fn received(payload: &[u8]) -> Vec<SyslogMessage> {
+        let (stream_tx, mut rx) = broadcast::channel(16);
+        let pipeline = Pipeline::new(&Config::default(), Arc::new(Stats::new()), stream_tx);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (socket, peer) = listener.accept().await.unwrap();
+        client.write_all(payload).await.unwrap();
+        drop(client);
+        let listener = Arc::new(ListenerConfig {
+            label: Some("edge".to_string()),
+            ..ListenerConfig::new(Protocol::Tcp, "127.0.0.1:0")
+        });
+        let peer = Peer::new(&listener, peer);
+        handle_connection(socket, &peer, 1024, Duration::from_secs(5), &pipeline).await;
+
+        let mut messages = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            messages.push(decode_line(&line).unwrap());
+        }
+        messages
+    }
+
+    fn contents(messages: &[SyslogMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn octet_counted_and_lf_framed_connections() {
+        let messages = received(b"11 <13>app: hi12 <13>app: bye").await;
+        assert_eq!(contents(&messages), ["hi", "bye"]);
+        // 受けた待ち受け口の名前(label)が付く
+        assert_eq!(messages[0].listener.as_deref(), Some("edge"));
+        assert_eq!(
+            contents(&received(b"<13>app: one\r\n<13>app: two\n<13>app: three").await),
+            ["one", "two", "three"]
+        );
+    }
+
+    /// PROXY ヘッダで始まる接続を、ロードバランサ(127.0.0.1)から受けたものとして読む。
+    /// ヘッダを受け入れなかったときは None。
+    async fn received_via_proxy(config: &Config, payload: &[u8]) -> Option<Vec<SyslogMessage>> {
+        let (stream_tx, mut rx) = broadcast::channel(16);
+        let pipeline = Pipeline::new(config, Arc::new(Stats::new()), stream_tx);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut socket, addr) = listener.accept().await.unwrap();
+        client.write_all(payload).await.unwrap();
+        drop(client);
+        let listener = Arc::new(ListenerConfig::new(Protocol::Tcp, "127.0.0.1:0"));
+        let mut peer = Peer::new(&listener, addr);
+        let idle_timeout = Duration::from_secs(5);
+        if !accept_proxy(&mut socket, &mut peer, idle_timeout, &pipeline).await {
+            return None;
+        }
+        handle_connection(socket, &peer, 1024, idle_timeout, &pipeline).await;
+
+        let mut messages = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            messages.push(decode_line(&line).unwrap());
+        }
+        Some(messages)
+    }
+
+    #[tokio::test]
+    async fn proxy_header_carries_the_client_address() {
+        let config = Config::default();
+        let messages = received_via_proxy(
+            &config,
+            b"PROXY TCP4 192.0.2.7 198.51.100.1 40000 514\r\n<13>app: hi\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(contents(&messages), ["hi"]);
+        assert_eq!(messages[0].source_addr, Some("192.0.2.7".parse().unwrap()));
+
+        // ヘッダの無い接続は閉じる
+        assert!(
+            received_via_proxy(&config, b"<13>app: hi\n")
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn acl_applies_to_the_address_in_the_proxy_header() {
+        use vlt_syslog_core::acl::{AclAction, AclRule};
+
+        let mut config = Config::default();
+        config.server.acl = vec![AclRule {
+            action: AclAction::Deny,
+            source: "192.0.2.0/24".to_string(),
+            listener: None,
+        }];
+        let header = b"PROXY TCP4 192.0.2.7 198.51.100.1 40000 514\r\n<13>app: hi\n";
+        assert!(received_via_proxy(&config, header).await.is_none());
+        let header = b"PROXY TCP4 203.0.113.9 198.51.100.1 40000 514\r\n<13>app: hi\n";
+        assert!(received_via_proxy(&config, header).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn connections_over_the_limit_wait_for_one_to_close() {
+        let cfg = ListenerConfig {
+            max_connections: 1,
+            ..ListenerConfig::new(Protocol::Tcp, "127.0.0.1:0")
+        };
+        let connections = Arc::new(Semaphore::new(cfg.max_connections));
+        let first = connection_permit(&connections, &cfg).await;
+        let second = connection_permit(&connections, &cfg);
+        tokio::pin!(second);
+        let waited = tokio::time::timeout(Duration::from_millis(50), &mut second).await;
+        assert!(waited.is_err());
+        drop(first);
+        let _second = tokio::time::timeout(Duration::from_secs(5), second)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,354 @@
+//! TLS での syslog 受信(RFC 5425)。
+//!
+//! 復号した後は TCP 受信と同じ処理([`crate::tcp::handle_connection`])で区切りを読む。
+//! RFC 5425 の区切りは Octet Counting だが、LF 区切りで送ってくる実装もあるので自動判別のままにする。
+//! `client_ca_path` を指定するとクライアント証明書を必須にし、その Subject をメッセージに記録する。
+//! 接続数の上限(`max_connections`)と accept に失敗したときの扱いも TCP 受信と同じ。
+
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::RootCertStore;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
+use vlt_syslog_core::listen;
+
+use crate::config::ListenerConfig;
+use crate::pipeline::{Peer, Pipeline};
+use crate::tcp::{accept_failed, accept_proxy, connection_permit, handle_connection};
+
+pub async fn run_tls_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let acceptor = TlsAcceptor::from(Arc::new(server_config(&cfg)?));
+    let listener = TcpListener::from_std(listen::tcp_listener(&cfg.addr)?)?;
+    log::info!(
+        "vlt-syslogd-srv TLS listener started on {} (client auth: {})",
+        cfg.addr,
+        if cfg.client_ca_path.is_some() {
+            "required"
+        } else {
+            "off"
+        }
+    );
+    serve(listener, acceptor, cfg, pipeline).await
+}
+
+/// 証明書・鍵を読み込んで rustls の設定を作る。暗号実装は ring を使う。
+fn server_config(cfg: &ListenerConfig) -> Result<rustls::ServerConfig, Box<dyn Error>> {
+    let (Some(cert_path), Some(key_path)) = (&cfg.cert_path, &cfg.key_path) else {
+        return Err("tls listener requires cert_path and key_path".into());
+    };
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read cert_path {}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate in cert_path {}", cert_path).into());
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("cannot read key_path {}: {}", key_path, e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let builder = match &cfg.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(path)
+                .map_err(|e| format!("cannot read client_ca_path {}: {}", path, e))?
+            {
+                roots.add(
+                    cert.map_err(|e| format!("cannot read client_ca_path {}: {}", path, e))?,
+                )?;
+            }
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(builder.with_single_cert(certs, key)?)
+}
+
+async fn serve(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let idle_timeout = Duration::from_secs(cfg.idle_timeout_secs);
+    let trusted_proxies = cfg.trusted_proxies()?;
+    let connections = Arc::new(Semaphore::new(cfg.max_connections));
+    loop {
+        let permit = connection_permit(&connections, &cfg).await;
+        let (mut socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                accept_failed(&cfg, &e).await;
+                continue;
+            }
+        };
+        // ハンドシェイクの前に送信元で弾く。ロードバランサからの接続は PROXY ヘッダにある本来の送信元で
+        let mut peer = Peer::new(&cfg, addr);
+        let via_proxy = trusted_proxies
+            .as_ref()
+            .is_some_and(|t| t.contains(addr.ip()));
+        if !via_proxy && !pipeline.admit(&peer) {
+            continue;
+        }
+        let acceptor = acceptor.clone();
+        let pipeline = Arc::clone(&pipeline);
+        let max_message_size = cfg.max_message_size;
+        tokio::spawn(async move {
+            let _permit = permit;
+            if via_proxy && !accept_proxy(&mut socket, &mut peer, idle_timeout, &pipeline).await {
+                return;
+            }
+            let addr = peer.addr;
+            // ハンドシェイクを始めないまま張りっぱなしの接続も無通信タイムアウトで閉じる
+            let stream = match tokio::time::timeout(idle_timeout, acceptor.accept(socket)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    log::warn!("TLS handshake with {} failed: {}", addr, e);
+                    return;
+                }
+                Err(_) => {
+                    log::info!("TLS handshake with {} timed out; closing", addr);
+                    return;
+                }
+            };
+            let tls_subject = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| subject_of(cert));
+            log::debug!(
+                "TLS syslog connection from {} (subject: {})",
+                addr,
+                tls_subject.as_deref().unwrap_or("-")
+            );
+            let peer = Peer {
+                tls_subject,
+                ..peer
+            };
+            handle_connection(stream, &peer, max_message_size, idle_timeout, &pipeline).await;
+        });
+    }
+}
+
+/// 証明書の Subject を "CN=..., O=..." の形で返す。読めなければ None。
+fn subject_of(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    Some(parsed.subject().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Protocol};
+    use crate::stats::Stats;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    };
+    use rustls::pki_types::ServerName;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+    use tokio::sync::broadcast;
+    use tokio_rustls::TlsConnector;
+    use vlt_syslog_core::stream::decode_line;
+
+    /// テスト用の CA・サーバ証明書・クライアント証明書(いずれも PEM)。
+    struct Pki {
+        ca: String,
+        server_cert: String,
+        server_key: String,
+        client_cert: String,
+        client_key: String,
+    }
+
+    fn pki() -> Pki {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "vlt test ca");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        let server = server_params.signed_by(&server_key, &ca, &ca_key).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "device01");
+        client_params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example");
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        Pki {
+            ca: ca.pem(),
+            server_cert: server.pem(),
+            server_key: server_key.serialize_pem(),
+            client_cert: client.pem(),
+            client_key: client_key.serialize_pem(),
+        }
+    }
+
+    /// PEM をテスト用の一時ディレクトリへ書き出し、TLS 受信の設定を作る。
+    fn tls_config(pki: &Pki, name: &str, client_auth: bool) -> ListenerConfig {
+        let dir =
+            std::env::temp_dir().join(format!("vlt-syslogd-tls-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |file: &str, pem: &str| {
+            let path = dir.join(file);
+            std::fs::write(&path, pem).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        ListenerConfig {
+            cert_path: Some(write("server.pem", &pki.server_cert)),
+            key_path: Some(write("server.key", &pki.server_key)),
+            client_ca_path: client_auth.then(|| write("ca.pem", &pki.ca)),
+            max_message_size: 1024,
+            idle_timeout_secs: 5,
+            ..ListenerConfig::new(Protocol::Tls, "127.0.0.1:0")
+        }
+    }
+
+    /// 設定どおりの TLS 受信を立て、接続先アドレスと配信ストリームの受け口を返す。
+    async fn start(cfg: ListenerConfig) -> (std::net::SocketAddr, broadcast::Receiver<String>) {
+        let (stream_tx, rx) = broadcast::channel(16);
+        let pipeline = Arc::new(Pipeline::new(
+            &Config::default(),
+            Arc::new(Stats::new()),
+            stream_tx,
+        ));
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(&cfg).unwrap()));
+        let listener = TcpListener::bind(&cfg.addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = serve(listener, acceptor, Arc::new(cfg), pipeline).await;
+        });
+        (addr, rx)
+    }
+
+    fn connector(pki: &Pki, with_client_cert: bool) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(pki.ca.as_bytes()).unwrap())
+            .unwrap();
+        let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+        let config = if with_client_cert {
+            let cert = CertificateDer::from_pem_slice(pki.client_cert.as_bytes()).unwrap();
+            let key = PrivateKeyDer::from_pem_slice(pki.client_key.as_bytes()).unwrap();
+            builder.with_client_auth_cert(vec![cert], key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+
+    /// TLS で送って接続を閉じ、配信ストリームに流れた行を返す(届かなければ None)。
+    async fn send(
+        connector: TlsConnector,
+        addr: std::net::SocketAddr,
+        rx: &mut broadcast::Receiver<String>,
+        payload: &[u8],
+    ) -> Option<String> {
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        if let Ok(mut tls) = connector.connect(name, tcp).await {
+            // TLS 1.3 ではクライアント証明書の拒否が書き込みの後で分かるため、ここでは失敗を無視する
+            let _ = tls.write_all(payload).await;
+            let _ = tls.shutdown().await;
+        }
+        tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .ok()
+            .and_then(|line| line.ok())
+    }
+
+    #[tokio::test]
+    async fn client_certificate_subject_is_recorded() {
+        let pki = pki();
+        let (addr, mut rx) = start(tls_config(&pki, "mtls", true)).await;
+
+        let line = send(connector(&pki, true), addr, &mut rx, b"11 <13>app: hi")
+            .await
+            .expect("message over TLS was not delivered");
+        let msg = decode_line(&line).unwrap();
+        assert_eq!(msg.content, "hi");
+        let subject = msg.tls_subject.unwrap();
+        assert!(subject.contains("CN=device01"), "{subject}");
+        assert!(subject.contains("O=Example"), "{subject}");
+
+        // CA を指定しているので、証明書を出さないクライアントは受け付けない
+        assert_eq!(
+            send(
+                connector(&pki, false),
+                addr,
+                &mut rx,
+                b"<13>app: anonymous\n"
+            )
+            .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn without_client_ca_anyone_can_send() {
+        let pki = pki();
+        let (addr, mut rx) = start(tls_config(&pki, "plain", false)).await;
+
+        let line = send(connector(&pki, false), addr, &mut rx, b"<13>app: one\n")
+            .await
+            .expect("message over TLS was not delivered");
+        let msg = decode_line(&line).unwrap();
+        assert_eq!(msg.content, "one");
+        assert_eq!(msg.tls_subject, None);
+    }
+
+    #[tokio::test]
+    async fn proxy_header_before_the_handshake_sets_the_source() {
+        let pki = pki();
+        let cfg = ListenerConfig {
+            proxy_protocol: true,
+            trusted_proxies: vec!["127.0.0.1".to_string()],
+            ..tls_config(&pki, "proxy", false)
+        };
+        let (addr, mut rx) = start(cfg).await;
+
+        let mut tcp = TcpStream::connect(addr).await.unwrap();
+        tcp.write_all(b"PROXY TCP4 192.0.2.7 127.0.0.1 40000 6514\r\n")
+            .await
+            .unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut tls = connector(&pki, false).connect(name, tcp).await.unwrap();
+        tls.write_all(b"<13>app: via lb\n").await.unwrap();
+        tls.shutdown().await.unwrap();
+        let line = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = decode_line(&line).unwrap();
+        assert_eq!(msg.content, "via lb");
+        assert_eq!(msg.source_addr, Some("192.0.2.7".parse().unwrap()));
+    }
+}
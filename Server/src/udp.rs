@@ -0,0 +1,276 @@
+//! UDP での syslog 受信(RFC 5426)。1 データグラム = 1 メッセージ。
+//! 上限(`max_message_size`)を超えたデータグラムは切り詰めて受け、truncated を付ける。
+//!
+//! `workers` が 2 以上なら受信ループをその数だけ並べる。SO_REUSEPORT が使える OS では
+//! ワーカーごとに同じアドレスへソケットを開き、カーネルに振り分けさせる。
+//! 受信ループが追いつかずにカーネルが捨てた数は `get_stats` の kernel_drops で見える(Linux のみ)。
+
+use std::error::Error;
+use std::io;
+use std::sync::{Arc, Weak};
+
+use tokio::net::UdpSocket;
+use tokio::task::JoinSet;
+use vlt_syslog_core::listen::{self, DatagramBuf, SocketOptions};
+
+use crate::config::ListenerConfig;
+use crate::pipeline::{Peer, Pipeline};
+
+pub async fn run_udp_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let sockets = bind(&cfg)?;
+    serve(sockets, cfg, pipeline).await
+}
+
+/// 待ち受けソケットを開く。SO_REUSEPORT が使えて workers が 2 以上ならワーカーの数だけ、
+/// それ以外は 1 つ。
+pub fn bind(cfg: &ListenerConfig) -> io::Result<Vec<UdpSocket>> {
+    let count = if listen::REUSE_PORT_SUPPORTED {
+        cfg.workers.max(1)
+    } else {
+        1
+    };
+    let opts = SocketOptions {
+        reuse_port: count > 1,
+        recv_buffer_size: cfg.recv_buffer_size,
+    };
+    let first = listen::udp_socket_with(&cfg.addr, &opts)?;
+    if let Some(requested) = cfg.recv_buffer_size {
+        let actual = listen::recv_buffer_size(&first)?;
+        if actual < requested {
+            log::warn!(
+                "SO_RCVBUF on {} is {} bytes (requested {}); raise the OS limit (e.g. net.core.rmem_max)",
+                cfg.addr,
+                actual,
+                requested
+            );
+        }
+    }
+    // ポート 0 なら最初のソケットに割り当てられたポートに揃える
+    let addr = first.local_addr()?.to_string();
+    let mut sockets = vec![UdpSocket::from_std(first)?];
+    for _ in 1..count {
+        sockets.push(UdpSocket::from_std(listen::udp_socket_with(&addr, &opts)?)?);
+    }
+    Ok(sockets)
+}
+
+/// 開いたソケットで受信ループを回す。どれか 1 つが止まれば全体を止める。
+pub async fn serve(
+    sockets: Vec<UdpSocket>,
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let sockets: Vec<Arc<UdpSocket>> = sockets.into_iter().map(Arc::new).collect();
+    let watched: Vec<Weak<UdpSocket>> = sockets.iter().map(Arc::downgrade).collect();
+    pipeline.stats().add_drop_counter(cfg.name(), move || {
+        watched
+            .iter()
+            .filter_map(|socket| kernel_drops(&*socket.upgrade()?))
+            .reduce(|a, b| a + b)
+    });
+    log::info!(
+        "vlt-syslogd-srv UDP listener started on {} ({} workers, {} sockets)",
+        cfg.addr,
+        cfg.workers,
+        sockets.len()
+    );
+
+    let mut workers = JoinSet::new();
+    for i in 0..cfg.workers.max(1) {
+        let socket = Arc::clone(&sockets[i % sockets.len()]);
+        let cfg = Arc::clone(&cfg);
+        let pipeline = Arc::clone(&pipeline);
+        workers.spawn(async move { recv_loop(&socket, &cfg, &pipeline).await });
+    }
+    match workers.join_next().await {
+        Some(Ok(Err(e))) => Err(e.into()),
+        Some(Err(e)) => Err(e.into()),
+        Some(Ok(Ok(()))) | None => Ok(()),
+    }
+}
+
+async fn recv_loop(
+    socket: &UdpSocket,
+    cfg: &Arc<ListenerConfig>,
+    pipeline: &Pipeline,
+) -> io::Result<()> {
+    let mut buf = DatagramBuf::new(cfg.max_message_size);
+    loop {
+        let (size, src) = match socket.recv_from(buf.as_mut_slice()).await {
+            Ok(received) => received,
+            Err(e) if listen::is_oversized_datagram(&e) => {
+                log::warn!(
+                    "Dropped a datagram larger than {} bytes on {}",
+                    cfg.max_message_size,
+                    cfg.addr
+                );
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let peer = Peer::new(cfg, src);
+        if !pipeline.admit(&peer) {
+            continue;
+        }
+        let (raw_msg, truncated) = buf.received(size);
+        pipeline.handle(raw_msg, truncated, &peer);
+    }
+}
+
+/// 受信バッファ溢れでカーネルが捨てたデータグラムの累計。
+/// `/proc/net/udp{,6}` からソケットの inode の行を探し、末尾の drops 列を読む。
+#[cfg(target_os = "linux")]
+fn kernel_drops(socket: &UdpSocket) -> Option<u64> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+
+    let inode = std::fs::metadata(format!("/proc/self/fd/{}", socket.as_raw_fd()))
+        .ok()?
+        .ino()
+        .to_string();
+    ["/proc/net/udp", "/proc/net/udp6"].iter().find_map(|path| {
+        let table = std::fs::read_to_string(path).ok()?;
+        table.lines().skip(1).find_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.get(9) == Some(&inode.as_str()) {
+                cols.last()?.parse().ok()
+            } else {
+                None
+            }
+        })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_drops(_socket: &UdpSocket) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Protocol};
+    use crate::stats::Stats;
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn workers_share_one_address() {
+        let cfg = Arc::new(ListenerConfig {
+            workers: 3,
+            recv_buffer_size: Some(256 * 1024),
+            ..ListenerConfig::new(Protocol::Udp, "127.0.0.1:0")
+        });
+        let sockets = bind(&cfg).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        if listen::REUSE_PORT_SUPPORTED {
+            assert_eq!(sockets.len(), 3);
+            assert!(sockets.iter().all(|s| s.local_addr().unwrap() == addr));
+        }
+
+        let stats = Arc::new(Stats::new());
+        let (stream_tx, _rx) = broadcast::channel(64);
+        let pipeline = Arc::new(Pipeline::new(
+            &Config::default(),
+            Arc::clone(&stats),
+            stream_tx,
+        ));
+        tokio::spawn(async move {
+            let _ = serve(sockets, cfg, pipeline).await;
+        });
+
+        // 送信元ポートが違えば振り分け先のソケットも変わりうるので、複数のソケットから送る
+        for i in 0..8 {
+            let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            sender
+                .send_to(format!("<13>app: {i}").as_bytes(), addr)
+                .unwrap();
+        }
+        for _ in 0..100 {
+            if stats.snapshot().received == 8 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let snap = stats.snapshot();
+        assert_eq!(snap.received, 8);
+        if cfg!(target_os = "linux") {
+            assert_eq!(snap.kernel_drops.get("udp://127.0.0.1:0"), Some(&0));
+        }
+    }
+
+    #[tokio::test]
+    async fn acl_rejects_before_parsing_and_counts_per_source() {
+        use vlt_syslog_core::acl::{AclAction, AclRule};
+
+        let mut config = Config::default();
+        config.server.acl = vec![AclRule {
+            action: AclAction::Allow,
+            source: "192.0.2.0/24".to_string(),
+            listener: Some("lan".to_string()),
+        }];
+        let cfg = Arc::new(ListenerConfig {
+            label: Some("lan".to_string()),
+            ..ListenerConfig::new(Protocol::Udp, "127.0.0.1:0")
+        });
+        let sockets = bind(&cfg).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        let stats = Arc::new(Stats::new());
+        let (stream_tx, _rx) = broadcast::channel(64);
+        let pipeline = Arc::new(Pipeline::new(&config, Arc::clone(&stats), stream_tx));
+        tokio::spawn(async move {
+            let _ = serve(sockets, cfg, pipeline).await;
+        });
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        for _ in 0..3 {
+            sender.send_to(b"<13>app: hi", addr).unwrap();
+        }
+        for _ in 0..100 {
+            if stats.snapshot().rejected == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let snap = stats.snapshot();
+        assert_eq!(snap.received, 0);
+        assert_eq!(snap.rejected, 3);
+        assert_eq!(snap.rejected_sources[0].addr, std::net::Ipv4Addr::LOCALHOST);
+        assert_eq!(snap.rejected_sources[0].packets, 3);
+    }
+
+    #[tokio::test]
+    async fn source_address_and_hosts_name_are_attached() {
+        use vlt_syslog_core::names::ResolveConfig;
+
+        let hosts = std::env::temp_dir().join(format!("vlt-syslogd-hosts-{}", std::process::id()));
+        std::fs::write(&hosts, "127.0.0.1 loghost.example\n").unwrap();
+        let mut config = Config::default();
+        config.server.resolve = Some(ResolveConfig {
+            hosts_file: Some(hosts.display().to_string()),
+            ..ResolveConfig::default()
+        });
+        let cfg = Arc::new(ListenerConfig::new(Protocol::Udp, "127.0.0.1:0"));
+        let sockets = bind(&cfg).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        let (stream_tx, mut rx) = broadcast::channel(64);
+        let pipeline = Arc::new(Pipeline::new(&config, Arc::new(Stats::new()), stream_tx));
+        std::fs::remove_file(&hosts).unwrap();
+        tokio::spawn(async move {
+            let _ = serve(sockets, cfg, pipeline).await;
+        });
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(b"<13>app: hi", addr).unwrap();
+        let line = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = vlt_syslog_core::stream::decode_line(&line).unwrap();
+        assert_eq!(msg.source_addr, Some(std::net::Ipv4Addr::LOCALHOST.into()));
+        assert_eq!(msg.source_name.as_deref(), Some("loghost.example"));
+    }
+}
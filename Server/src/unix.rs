@@ -0,0 +1,311 @@
+//! Unix ドメインソケットでの syslog 受信(`/dev/log` の代わり)。Unix 系のみ。
+//!
+//! - unix(データグラム): `syslog(3)` の既定。1 データグラム = 1 メッセージ。Linux では
+//!   SO_PASSCRED を立て、届いたデータグラムごとに送信元の PID / UID / GID を受け取る。
+//! - unix-stream(ストリーム): LF / NUL 区切り(TCP と同じ [`crate::tcp::handle_connection`])。
+//!   資格情報は接続ごとに SO_PEERCRED で取る。接続数の上限(`max_connections`)と accept に
+//!   失敗したときの扱いは TCP 受信と同じ。
+//!
+//! ソケットファイルが残っていれば作り直し、設定のパーミッション(既定 0666)にする。
+
+use std::error::Error;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{UnixDatagram, UnixListener};
+use tokio::sync::Semaphore;
+use vlt_syslog_core::PeerCred;
+use vlt_syslog_core::listen::DatagramBuf;
+
+use crate::config::ListenerConfig;
+use crate::pipeline::{Peer, Pipeline};
+use crate::tcp::{accept_failed, connection_permit, handle_connection};
+
+pub async fn run_unix_datagram_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let socket = bind_path(&cfg, |path| UnixDatagram::bind(path))?;
+    pass_credentials(&socket)?;
+    log::info!(
+        "vlt-syslogd-srv Unix datagram listener started on {}",
+        cfg.addr
+    );
+
+    let mut buf = DatagramBuf::new(cfg.max_message_size);
+    loop {
+        let (size, cred) = recv_with_cred(&socket, buf.as_mut_slice()).await?;
+        let (raw_msg, truncated) = buf.received(size);
+        pipeline.handle(raw_msg, truncated, &Peer::local(&cfg, cred));
+    }
+}
+
+pub async fn run_unix_stream_server(
+    cfg: Arc<ListenerConfig>,
+    pipeline: Arc<Pipeline>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = bind_path(&cfg, |path| UnixListener::bind(path))?;
+    log::info!(
+        "vlt-syslogd-srv Unix stream listener started on {}",
+        cfg.addr
+    );
+
+    let idle_timeout = Duration::from_secs(cfg.idle_timeout_secs);
+    let connections = Arc::new(Semaphore::new(cfg.max_connections));
+    loop {
+        let permit = connection_permit(&connections, &cfg).await;
+        let (stream, _) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                accept_failed(&cfg, &e).await;
+                continue;
+            }
+        };
+        let pipeline = Arc::clone(&pipeline);
+        let cfg = Arc::clone(&cfg);
+        tokio::spawn(async move {
+            let _permit = permit;
+            let cred = stream.peer_cred().ok().map(|c| PeerCred {
+                pid: c.pid(),
+                uid: c.uid(),
+                gid: c.gid(),
+            });
+            let peer = Peer::local(&cfg, cred);
+            handle_connection(stream, &peer, cfg.max_message_size, idle_timeout, &pipeline).await;
+        });
+    }
+}
+
+/// 残っているソケットファイルを消してから bind し、パーミッションを設定する。
+/// 同じパスに通常のファイルなどがある場合は消さずにエラーにする。
+fn bind_path<T>(
+    cfg: &ListenerConfig,
+    bind: impl FnOnce(&Path) -> std::io::Result<T>,
+) -> Result<T, Box<dyn Error>> {
+    let path = Path::new(&cfg.addr);
+    let mode = cfg.socket_mode()?;
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", cfg.addr).into());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let socket = bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(socket)
+}
+
+/// データグラムごとに送信元の資格情報を受け取るようにする(Linux のみ。他では何もしない)。
+#[cfg(target_os = "linux")]
+fn pass_credentials(socket: &UnixDatagram) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let on: libc::c_int = 1;
+    // SAFETY: 有効な fd に対し、c_int 1 つぶんのオプション値を渡している
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PASSCRED,
+            (&on as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pass_credentials(_socket: &UnixDatagram) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// データグラムを 1 つ受け取り、付いてきた SCM_CREDENTIALS を読む。
+#[cfg(target_os = "linux")]
+async fn recv_with_cred(
+    socket: &UnixDatagram,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, Option<PeerCred>)> {
+    use std::os::fd::AsRawFd;
+
+    let fd = socket.as_raw_fd();
+    socket
+        .async_io(tokio::io::Interest::READABLE, || {
+            let mut iov = libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            };
+            // CMSG_SPACE(sizeof(ucred)) が収まり、cmsghdr の境界にそろう大きさ
+            let mut control = [0u64; 8];
+            // SAFETY: msghdr はゼロ初期化で有効な値になる。ポインタは下の recvmsg の間だけ使う
+            let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+            // SAFETY: msg は有効な iovec と制御バッファを指している
+            let n = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_DONTWAIT) };
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut cred = None;
+            // SAFETY: recvmsg が埋めた msg の制御データを CMSG_* マクロの約束どおりに辿る
+            unsafe {
+                let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+                while !cmsg.is_null() {
+                    if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                        && (*cmsg).cmsg_type == libc::SCM_CREDENTIALS
+                    {
+                        let ucred: libc::ucred =
+                            std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast());
+                        cred = Some(PeerCred {
+                            pid: Some(ucred.pid),
+                            uid: ucred.uid,
+                            gid: ucred.gid,
+                        });
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+                }
+            }
+            Ok((n as usize, cred))
+        })
+        .await
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn recv_with_cred(
+    socket: &UnixDatagram,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, Option<PeerCred>)> {
+    Ok((socket.recv(buf).await?, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Protocol};
+    use crate::stats::Stats;
+    use tokio::io::AsyncWriteExt;
+    use tokio::sync::broadcast;
+    use vlt_syslog_core::SyslogMessage;
+    use vlt_syslog_core::stream::decode_line;
+
+    /// テストごとのソケットパスの待ち受け設定。
+    fn listener(protocol: Protocol, name: &str) -> ListenerConfig {
+        let dir = std::env::temp_dir().join(format!("vlt-syslogd-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        ListenerConfig {
+            mode: Some("0660".to_string()),
+            ..ListenerConfig::new(protocol, &dir.join(name).to_string_lossy())
+        }
+    }
+
+    /// 待ち受けを立て、配信ストリームの受け口と統計を返す。
+    fn start(
+        cfg: ListenerConfig,
+    ) -> (Arc<ListenerConfig>, broadcast::Receiver<String>, Arc<Stats>) {
+        let cfg = Arc::new(cfg);
+        let (stream_tx, rx) = broadcast::channel(16);
+        let stats = Arc::new(Stats::new());
+        let pipeline = Arc::new(Pipeline::new(
+            &Config::default(),
+            Arc::clone(&stats),
+            stream_tx,
+        ));
+        let server = Arc::clone(&cfg);
+        tokio::spawn(async move {
+            let _ = match server.protocol {
+                Protocol::Unix => run_unix_datagram_server(server, pipeline).await,
+                _ => run_unix_stream_server(server, pipeline).await,
+            };
+        });
+        (cfg, rx, stats)
+    }
+
+    async fn next(rx: &mut broadcast::Receiver<String>) -> SyslogMessage {
+        let line = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        decode_line(&line).unwrap()
+    }
+
+    /// 待ち受けが bind し終わるまで待つ。
+    async fn wait_for_socket(path: &str) {
+        for _ in 0..100 {
+            if Path::new(path).exists() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{path} was not created");
+    }
+
+    fn own_uid() -> u32 {
+        // SAFETY: getuid は常に成功する
+        unsafe { libc::getuid() }
+    }
+
+    #[tokio::test]
+    async fn datagrams_carry_sender_credentials() {
+        let (cfg, mut rx, _) = start(listener(Protocol::Unix, "dgram.sock"));
+        wait_for_socket(&cfg.addr).await;
+
+        let client = std::os::unix::net::UnixDatagram::unbound().unwrap();
+        client
+            .send_to(b"<13>Oct 18 12:00:00 app[42]: hello", &cfg.addr)
+            .unwrap();
+        let msg = next(&mut rx).await;
+        assert_eq!(msg.content, "hello");
+        let mode = std::fs::metadata(&cfg.addr).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o660);
+        assert_eq!(msg.tag.as_deref(), Some("app"));
+        assert!(!msg.truncated);
+        assert_eq!(msg.source_addr, None);
+        if cfg!(target_os = "linux") {
+            let cred = msg.peer_cred.unwrap();
+            assert_eq!(cred.pid, Some(std::process::id() as i32));
+            assert_eq!(cred.uid, own_uid());
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_connections_are_split_on_nul_and_lf() {
+        let (cfg, mut rx, _) = start(listener(Protocol::UnixStream, "stream.sock"));
+        wait_for_socket(&cfg.addr).await;
+
+        let mut client = tokio::net::UnixStream::connect(&cfg.addr).await.unwrap();
+        client
+            .write_all(b"<13>app: one\0<13>app: two\n")
+            .await
+            .unwrap();
+        let first = next(&mut rx).await;
+        assert_eq!(first.content, "one");
+        assert_eq!(first.peer_cred.unwrap().uid, own_uid());
+        assert_eq!(next(&mut rx).await.content, "two");
+    }
+
+    #[tokio::test]
+    async fn oversized_datagrams_are_truncated_and_counted() {
+        let (cfg, mut rx, stats) = start(ListenerConfig {
+            max_message_size: 16,
+            ..listener(Protocol::Unix, "small.sock")
+        });
+        wait_for_socket(&cfg.addr).await;
+
+        let client = std::os::unix::net::UnixDatagram::unbound().unwrap();
+        client
+            .send_to(b"<13>app: 0123456789abcdef", &cfg.addr)
+            .unwrap();
+        let msg = next(&mut rx).await;
+        assert!(msg.truncated);
+        assert_eq!(msg.content, "0123456");
+        assert_eq!(stats.snapshot().truncated, 1);
+    }
+}